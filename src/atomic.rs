@@ -0,0 +1,52 @@
+//! Crash-safe file writes: the new contents go to a temporary file next to
+//! the target, are flushed to disk and then renamed over it, so a crash or a
+//! full disk leaves either the old file or the new one, never a truncated one.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Replaces `path` with `contents`.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    write_with(path, |w| Ok(w.write_all(contents.as_ref())?))
+}
+
+/// Replaces `path` with what `write` writes; the file is left untouched if
+/// `write` fails.
+pub fn write_with<T>(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<T>) -> Result<T> {
+    let tmp = temp_path(path);
+    let result = replace(&tmp, path, write);
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    let value = result.with_context(|| format!("writing {}", path.display()))?;
+    sync_dir(path);
+    Ok(value)
+}
+
+fn replace<T>(tmp: &Path, path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<T>) -> Result<T> {
+    let mut w = BufWriter::new(File::create(tmp)?);
+    let value = write(&mut w)?;
+    w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    fs::rename(tmp, path)?;
+    Ok(value)
+}
+
+// `.name.tmp` in the same folder, so the rename stays on one file system
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+// makes the rename itself durable; folders can't be opened for this on Windows
+#[cfg(unix)]
+fn sync_dir(path: &Path) {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if let Ok(d) = File::open(dir) {
+        let _ = d.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) {}
@@ -0,0 +1,107 @@
+//! Earlier versions of label and class files, kept under the dataset root in
+//! `.annotator/history/<path of the file>/<milliseconds since 1970>.<ext>`.
+//!
+//! A version is taken right before a file is overwritten, unless it matches
+//! the newest one already kept, and old versions are pruned by count and age.
+
+use crate::atomic;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const HISTORY_DIR: &str = ".annotator/history";
+
+/// How many versions of a file are kept; 0 means no limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retention {
+    pub max_versions: usize,
+    pub max_age_days: u64,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self { max_versions: 50, max_age_days: 30 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Version {
+    pub path: PathBuf,
+    pub time: SystemTime,
+}
+
+impl Version {
+    pub fn age(&self) -> Duration {
+        SystemTime::now().duration_since(self.time).unwrap_or_default()
+    }
+}
+
+/// Folder holding the versions of `file`.
+pub fn history_dir(root: &Path, file: &Path) -> PathBuf {
+    let rel = file.strip_prefix(root).ok().filter(|r| !r.as_os_str().is_empty())
+        .map_or_else(|| PathBuf::from(file.file_name().unwrap_or_default()), Path::to_path_buf);
+    root.join(HISTORY_DIR).join(rel)
+}
+
+/// Versions of `file`, newest first.
+pub fn versions(root: &Path, file: &Path) -> Result<Vec<Version>> {
+    let dir = history_dir(root, file);
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut out = vec![];
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let Some(millis) = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse::<u64>().ok()) else { continue };
+        out.push(Version { path, time: UNIX_EPOCH + Duration::from_millis(millis) });
+    }
+    out.sort_by_key(|v| std::cmp::Reverse(v.time));
+    Ok(out)
+}
+
+/// Keeps the current contents of `file` as a new version, then prunes the
+/// old ones. Does nothing if the file doesn't exist or matches the newest
+/// version.
+pub fn save_version(root: &Path, file: &Path, retention: &Retention) -> Result<()> {
+    if !file.exists() {
+        return Ok(());
+    }
+    let contents = fs::read(file)?;
+    let existing = versions(root, file)?;
+    if existing.first().is_some_and(|v| fs::read(&v.path).is_ok_and(|c| c == contents)) {
+        return Ok(());
+    }
+    let dir = history_dir(root, file);
+    fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+    let ext = file.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    // two saves within a millisecond
+    if let Some(newest) = existing.first() {
+        millis = millis.max(newest.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() + 1);
+    }
+    atomic::write(&dir.join(format!("{}{}", millis, ext)), contents)?;
+    prune(root, file, retention)
+}
+
+/// Drops the versions of `file` beyond the retention limits.
+pub fn prune(root: &Path, file: &Path, retention: &Retention) -> Result<()> {
+    let max_age = Duration::from_secs(retention.max_age_days * 24 * 60 * 60);
+    for (i, v) in versions(root, file)?.into_iter().enumerate() {
+        let too_many = retention.max_versions > 0 && i >= retention.max_versions;
+        let too_old = retention.max_age_days > 0 && v.age() > max_age;
+        if too_many || too_old {
+            fs::remove_file(&v.path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Puts `version` back in place of `file`, first keeping the current
+/// contents as a version so the revert can be undone.
+pub fn restore(root: &Path, file: &Path, version: &Version, retention: &Retention) -> Result<()> {
+    // read first: pruning may drop the version
+    let contents = fs::read(&version.path)?;
+    save_version(root, file, retention)?;
+    atomic::write(file, contents)
+}
@@ -0,0 +1,213 @@
+//! Renaming, reordering, merging and deleting classes across a whole dataset.
+//! The class list changes together with every label file of every split, so
+//! the ids in YOLO files keep pointing at the same classes.
+//!
+//! `plan` is a dry run listing the files an edit would rewrite; `apply`
+//! rewrites them and returns their old contents, which `undo` puts back.
+
+use crate::atomic;
+use crate::classes::ClassRegistry;
+use crate::dataset::{Dataset, LabelFormat};
+use crate::yolo::{self, ClassToken};
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClassEdit {
+    /// Keeps the class's place and id under a new name.
+    Rename { from: String, to: String },
+    /// Moves a class to another place in the list, shifting the ids of the
+    /// classes in between.
+    Move { name: String, to: usize },
+    /// Gives the boxes of `from` to `into` and drops `from`.
+    Merge { from: String, into: String },
+    /// Drops a class together with its boxes.
+    Delete { name: String },
+}
+
+impl ClassEdit {
+    pub fn describe(&self) -> String {
+        match self {
+            ClassEdit::Rename { from, to } => format!("Rename '{}' to '{}'", from, to),
+            ClassEdit::Move { name, to } => format!("Move '{}' to place {}", name, to + 1),
+            ClassEdit::Merge { from, into } => format!("Merge '{}' into '{}'", from, into),
+            ClassEdit::Delete { name } => format!("Delete '{}'", name),
+        }
+    }
+
+    /// The class list after the edit.
+    pub fn apply_to(&self, classes: &ClassRegistry) -> Result<ClassRegistry> {
+        let mut out = classes.clone();
+        match self {
+            ClassEdit::Rename { from, to } => out.rename(from, to)?,
+            ClassEdit::Move { name, to } => out.move_to(name, *to)?,
+            ClassEdit::Merge { from, into } => {
+                if from == into { bail!("can't merge '{}' into itself", from); }
+                if out.id_of(into).is_none() { bail!("no class '{}'", into); }
+                out.remove(from)?;
+            }
+            ClassEdit::Delete { name } => out.remove(name)?,
+        }
+        Ok(out)
+    }
+
+    /// The class a box of class `name` gets; `None` if the box is deleted.
+    pub fn map_class(&self, name: &str) -> Option<String> {
+        match self {
+            ClassEdit::Rename { from, to } | ClassEdit::Merge { from, into: to } if from == name => Some(to.clone()),
+            ClassEdit::Delete { name: deleted } if deleted == name => None,
+            _ => Some(name.to_owned()),
+        }
+    }
+}
+
+/// A label file an edit rewrites.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileChange {
+    pub image: PathBuf,
+    pub path: PathBuf,
+    pub format: LabelFormat,
+    /// Boxes whose entry in the file changes: another id in a YOLO file,
+    /// another name in a VOC one.
+    pub changed: usize,
+    pub removed: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Plan {
+    pub edit: ClassEdit,
+    /// The class list after the edit.
+    pub classes: ClassRegistry,
+    pub files: Vec<FileChange>,
+}
+
+impl Plan {
+    pub fn changed_boxes(&self) -> usize {
+        self.files.iter().map(|f| f.changed).sum()
+    }
+
+    pub fn removed_boxes(&self) -> usize {
+        self.files.iter().map(|f| f.removed).sum()
+    }
+}
+
+/// An applied edit with the previous contents of the files it wrote.
+#[derive(Clone, Debug)]
+pub struct Applied {
+    pub edit: ClassEdit,
+    /// The class list before the edit.
+    pub classes: ClassRegistry,
+    // `None` for files that didn't exist
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl Applied {
+    /// The class files and the label files the edit wrote.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(p, _)| p.as_path())
+    }
+}
+
+/// The label files `edit` would rewrite; changes nothing on disk. Refuses
+/// while YOLO files use ids missing from the class list, as the rewrite would
+/// give their boxes new ids.
+pub fn plan(dataset: &Dataset, edit: &ClassEdit) -> Result<Plan> {
+    let classes = edit.apply_to(&dataset.classes)?;
+    let mut old = dataset.classes.clone();
+    let mut files = vec![];
+    let mut unknown = vec![];
+    for image in dataset.all_images()? {
+        for format in LabelFormat::ALL {
+            let path = dataset.annotation_path(&image, format);
+            if !path.exists() {
+                continue;
+            }
+            if format == LabelFormat::Yolo {
+                let mut ids: Vec<usize> = fs::read_to_string(&path)?.lines()
+                    .filter_map(|l| match yolo::parse_line(l, dataset.kpt_shape())?.class {
+                        ClassToken::Id(id) if dataset.classes.lookup_file_id(id).is_none() => Some(id),
+                        _ => None,
+                    })
+                    .collect();
+                if !ids.is_empty() {
+                    ids.sort();
+                    ids.dedup();
+                    unknown.push(format!("{}: {}", path.display(), ids.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", ")));
+                    continue;
+                }
+            }
+            let ann = dataset.read_label_file(&image, &path, format, &mut old)?;
+            let (mut changed, mut removed) = (0, 0);
+            for b in &ann.boxes {
+                match edit.map_class(&b.class_name) {
+                    None => removed += 1,
+                    Some(name) => {
+                        let differs = match format {
+                            LabelFormat::Yolo => old.file_id(&b.class_name) != classes.file_id(&name),
+                            LabelFormat::Voc => name != b.class_name,
+                        };
+                        if differs {
+                            changed += 1;
+                        }
+                    }
+                }
+            }
+            if changed + removed > 0 {
+                files.push(FileChange { image: image.clone(), path, format, changed, removed });
+            }
+        }
+    }
+    if !unknown.is_empty() {
+        let more = if unknown.len() > 10 { format!("\n... and {} more", unknown.len() - 10) } else { String::new() };
+        bail!("label files use class ids that aren't in the class list; open those images to give the ids placeholder classes, or fix the ids:\n{}{}", unknown[..unknown.len().min(10)].join("\n"), more);
+    }
+    Ok(Plan { edit: edit.clone(), classes, files })
+}
+
+/// Changes the class list and rewrites the label files as `plan` would show
+/// right now. On failure the files written so far are put back.
+pub fn apply(dataset: &mut Dataset, edit: &ClassEdit) -> Result<Applied> {
+    let plan = plan(dataset, edit)?;
+    let mut applied = Applied { edit: edit.clone(), classes: dataset.classes.clone(), files: vec![] };
+    for path in [dataset.classes_file_path(), dataset.id_map_file_path()] {
+        let contents = if path.exists() { Some(fs::read(&path)?) } else { None };
+        applied.files.push((path, contents));
+    }
+    if let Err(e) = rewrite(dataset, &plan, &mut applied) {
+        undo(dataset, &applied).context("putting the label files back")?;
+        return Err(e);
+    }
+    Ok(applied)
+}
+
+fn rewrite(dataset: &mut Dataset, plan: &Plan, applied: &mut Applied) -> Result<()> {
+    let mut old = applied.classes.clone();
+    dataset.classes = plan.classes.clone();
+    dataset.save_classes_file()?;
+    for f in &plan.files {
+        let mut ann = dataset.read_label_file(&f.image, &f.path, f.format, &mut old)?;
+        ann.boxes = ann.boxes.into_iter().filter_map(|mut b| {
+            b.class_name = plan.edit.map_class(&b.class_name)?;
+            Some(b)
+        }).collect();
+        applied.files.push((f.path.clone(), Some(fs::read(&f.path)?)));
+        dataset.save_annotations_as(&ann, f.format).with_context(|| format!("writing {}", f.path.display()))?;
+    }
+    Ok(())
+}
+
+/// Puts back the class list and label files as they were before `applied`.
+/// Changes made to those files since are kept as versions when backups are on.
+pub fn undo(dataset: &mut Dataset, applied: &Applied) -> Result<()> {
+    for (path, contents) in applied.files.iter().rev() {
+        dataset.back_up(path)?;
+        match contents {
+            Some(c) => atomic::write(path, c)?,
+            None if path.exists() => fs::remove_file(path)?,
+            None => {}
+        }
+    }
+    dataset.classes = applied.classes.clone();
+    Ok(())
+}
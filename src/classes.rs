@@ -0,0 +1,262 @@
+use crate::atomic;
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+pub const DEFAULT_CLASS: &str = "object";
+pub const ID_MAP_FILE: &str = "_darknet.idmap";
+
+/// How class ids written in label files relate to the class list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IdMapping {
+    /// file id = index in the class list - offset
+    Offset(usize),
+    /// explicit file id -> class name table
+    Table(BTreeMap<usize, String>),
+}
+
+impl Default for IdMapping {
+    fn default() -> Self { IdMapping::Offset(0) }
+}
+
+impl IdMapping {
+    /// Either a single `offset N` line or one `<id> <name>` line per class.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn read_from(reader: impl BufRead) -> Result<Self> {
+        let mut offset = None;
+        let mut table = BTreeMap::new();
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            let s = line.trim();
+            if s.is_empty() || s.starts_with('#') { continue; }
+            let (key, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+            let rest = rest.trim();
+            if key == "offset" {
+                let v = rest.parse::<usize>().with_context(|| format!("line {}: bad offset '{}'", n + 1, rest))?;
+                offset = Some(v);
+            } else {
+                let id = key.parse::<usize>().with_context(|| format!("line {}: bad class id '{}'", n + 1, key))?;
+                if rest.is_empty() { bail!("line {}: class id {} has no name", n + 1, id); }
+                if table.insert(id, rest.to_owned()).is_some() { bail!("line {}: class id {} listed twice", n + 1, id); }
+            }
+        }
+        match (offset, table.is_empty()) {
+            (Some(_), false) => bail!("id mapping mixes 'offset' with an id table"),
+            (Some(k), true) => Ok(IdMapping::Offset(k)),
+            (None, false) => Ok(IdMapping::Table(table)),
+            (None, true) => Ok(IdMapping::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?)).with_context(|| format!("reading {}", path.display()))
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> Result<()> {
+        match self {
+            IdMapping::Offset(k) => writeln!(writer, "offset {}", k)?,
+            IdMapping::Table(t) => {
+                for (id, name) in t {
+                    writeln!(writer, "{} {}", id, name)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        atomic::write_with(path, |w| self.write_to(w))
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == IdMapping::Offset(0)
+    }
+}
+
+/// Ordered list of class names plus the mapping used to turn them into the
+/// ids stored in label files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassRegistry {
+    names: Vec<String>,
+    mapping: IdMapping,
+}
+
+impl Default for ClassRegistry {
+    fn default() -> Self {
+        Self { names: vec![DEFAULT_CLASS.to_owned()], mapping: IdMapping::default() }
+    }
+}
+
+impl ClassRegistry {
+    pub fn new(names: Vec<String>) -> Self {
+        if names.is_empty() { Self::default() } else { Self { names, mapping: IdMapping::default() } }
+    }
+
+    /// A registry without the default class, for datasets whose classes are
+    /// only known from their label files.
+    pub fn empty() -> Self {
+        Self { names: vec![], mapping: IdMapping::default() }
+    }
+
+    /// One class name per line; blank lines are ignored.
+    pub fn read_from(reader: impl BufRead) -> Result<Self> {
+        let mut names = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            let s = line.trim();
+            if !s.is_empty() {
+                names.push(s.to_owned());
+            }
+        }
+        Ok(Self::new(names))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> Result<()> {
+        for c in &self.names {
+            writeln!(writer, "{}", c)?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        atomic::write_with(path, |w| self.write_to(w))
+    }
+
+    pub fn names(&self) -> &[String] { &self.names }
+    pub fn len(&self) -> usize { self.names.len() }
+    pub fn is_empty(&self) -> bool { self.names.is_empty() }
+    pub fn iter(&self) -> std::slice::Iter<'_, String> { self.names.iter() }
+
+    pub fn name(&self, id: usize) -> Option<&str> {
+        self.names.get(id).map(|s| s.as_str())
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|c| c == name)
+    }
+
+    /// Returns the id of `name`, appending it as a new class if needed.
+    /// The flag tells whether the list changed.
+    pub fn ensure(&mut self, name: &str) -> (usize, bool) {
+        match self.id_of(name) {
+            Some(i) => (i, false),
+            None => {
+                self.names.push(name.to_owned());
+                (self.names.len() - 1, true)
+            }
+        }
+    }
+
+    /// Renames a class, keeping its place in the list and its file id.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        let Some(i) = self.id_of(from) else { bail!("no class '{}'", from) };
+        if to.is_empty() || to.trim() != to || to.contains('\n') { bail!("'{}' is not a valid class name", to); }
+        if self.id_of(to).is_some() { bail!("class '{}' already exists", to); }
+        self.names[i] = to.to_owned();
+        if let IdMapping::Table(t) = &mut self.mapping {
+            for n in t.values_mut().filter(|n| n.as_str() == from) {
+                *n = to.to_owned();
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a class; with an offset mapping the classes after it move up
+    /// one id.
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        let Some(i) = self.id_of(name) else { bail!("no class '{}'", name) };
+        self.names.remove(i);
+        if let IdMapping::Table(t) = &mut self.mapping {
+            t.retain(|_, n| n != name);
+        }
+        Ok(())
+    }
+
+    /// Moves a class to `index` in the list.
+    pub fn move_to(&mut self, name: &str, index: usize) -> Result<()> {
+        let Some(i) = self.id_of(name) else { bail!("no class '{}'", name) };
+        let name = self.names.remove(i);
+        self.names.insert(index.min(self.names.len()), name);
+        Ok(())
+    }
+
+    pub fn mapping(&self) -> &IdMapping { &self.mapping }
+
+    pub fn set_mapping(&mut self, mapping: IdMapping) {
+        if let IdMapping::Table(t) = &mapping {
+            for name in t.values() {
+                self.ensure(name);
+            }
+        }
+        self.mapping = mapping;
+    }
+
+    /// Id written to label files for `name`, if it has one.
+    pub fn file_id(&self, name: &str) -> Option<usize> {
+        match &self.mapping {
+            IdMapping::Offset(k) => self.id_of(name)?.checked_sub(*k),
+            IdMapping::Table(t) => t.iter().find(|(_, n)| n.as_str() == name).map(|(id, _)| *id),
+        }
+    }
+
+    /// Class name for an id read from a label file, without creating
+    /// placeholders.
+    pub fn lookup_file_id(&self, id: usize) -> Option<&str> {
+        match &self.mapping {
+            IdMapping::Offset(k) => self.name(id + k),
+            IdMapping::Table(t) => t.get(&id).map(|s| s.as_str()),
+        }
+    }
+
+    /// Class name for an id read from a label file. Ids without a class get a
+    /// `class_N` placeholder. The flag tells whether the registry changed.
+    pub fn name_for_file_id(&mut self, id: usize) -> (String, bool) {
+        match &mut self.mapping {
+            IdMapping::Offset(k) => {
+                let idx = id + *k;
+                let changed = self.names.len() <= idx;
+                while self.names.len() <= idx {
+                    self.names.push(format!("class_{}", self.names.len()));
+                }
+                (self.names[idx].clone(), changed)
+            }
+            IdMapping::Table(t) => {
+                let mut changed = false;
+                let name = t.entry(id).or_insert_with(|| {
+                    changed = true;
+                    format!("class_{}", id)
+                }).clone();
+                changed |= self.ensure(&name).1;
+                (name, changed)
+            }
+        }
+    }
+
+    /// Id to write for `name`, registering the class (and, for a table
+    /// mapping, the next free id) when it is new.
+    pub fn file_id_or_insert(&mut self, name: &str) -> Result<(usize, bool)> {
+        let (idx, mut changed) = self.ensure(name);
+        let id = match &mut self.mapping {
+            IdMapping::Offset(k) => match idx.checked_sub(*k) {
+                Some(id) => id,
+                None => bail!("class '{}' (index {}) has no id with offset {}", name, idx, k),
+            },
+            IdMapping::Table(t) => match t.iter().find(|(_, n)| n.as_str() == name) {
+                Some((id, _)) => *id,
+                None => {
+                    let id = t.keys().next_back().map_or(0, |m| m + 1);
+                    t.insert(id, name.to_owned());
+                    changed = true;
+                    id
+                }
+            },
+        };
+        Ok((id, changed))
+    }
+}
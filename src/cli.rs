@@ -0,0 +1,304 @@
+//! Command-line interface. Every subcommand except `annotate` runs without
+//! opening a window, on the same annotation model as the GUI.
+
+use anyhow::{Context, Result, anyhow, bail};
+use img_annotator::split::SplitOptions;
+use img_annotator::{Dataset, LabelFormat, coco, render, split, stats, validate};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+Usage: img-annotator [COMMAND] [ARGS]
+
+Commands:
+  annotate [DIR]                        open the annotator (default; `img-annotator DIR` works too)
+  validate DIR                          check label files; exits with 1 when there are errors
+  stats DIR [--format text|csv|json] [--output FILE]
+                                        class balance, boxes per image and box size / aspect
+                                        ratio distributions, over all splits
+  convert DIR --to yolo|voc|coco [--output FILE]
+                                        rewrite every label file in another format, or export
+                                        COCO json (default DIR/instances.json); covers all
+                                        splits of an images/ + labels/ dataset
+  convert DIR --from coco --input FILE  write labels and the class list from COCO json
+  split DIR --output OUT [--val 0.2] [--test 0] [--seed 0]
+                                        copy DIR into an Ultralytics images/ + labels/ dataset
+  render DIR --output OUT               write copies of the images with their boxes drawn
+  help                                  show this message
+
+DIR is an image folder, or the root or an image folder of an Ultralytics dataset.";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Annotate(Option<PathBuf>),
+    Validate { dir: PathBuf },
+    Stats { dir: PathBuf, format: StatsFormat, output: Option<PathBuf> },
+    Convert { dir: PathBuf, to: ConvertTarget, output: Option<PathBuf> },
+    ImportCoco { dir: PathBuf, input: PathBuf },
+    Split { dir: PathBuf, output: PathBuf, options: SplitOptions },
+    Render { dir: PathBuf, output: PathBuf },
+    Help,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConvertTarget {
+    Labels(LabelFormat),
+    Coco,
+}
+
+impl FromStr for ConvertTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "yolo" | "txt" => Ok(ConvertTarget::Labels(LabelFormat::Yolo)),
+            "voc" | "xml" => Ok(ConvertTarget::Labels(LabelFormat::Voc)),
+            "coco" | "json" => Ok(ConvertTarget::Coco),
+            _ => bail!("unknown format '{}', expected yolo, voc or coco", s),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatsFormat {
+    #[default]
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for StatsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(StatsFormat::Text),
+            "csv" => Ok(StatsFormat::Csv),
+            "json" => Ok(StatsFormat::Json),
+            _ => bail!("unknown format '{}', expected text, csv or json", s),
+        }
+    }
+}
+
+// positional arguments and `--name value` / `--name=value` options
+struct Opts {
+    positional: Vec<String>,
+    named: HashMap<String, String>,
+}
+
+impl Opts {
+    fn parse(args: &[String], allowed: &[&str]) -> Result<Self> {
+        let mut opts = Opts { positional: vec![], named: HashMap::new() };
+        let mut it = args.iter();
+        while let Some(arg) = it.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                opts.positional.push(arg.clone());
+                continue;
+            };
+            let (name, value) = match name.split_once('=') {
+                Some((n, v)) => (n.to_owned(), v.to_owned()),
+                None => (name.to_owned(), it.next().cloned().with_context(|| format!("--{} needs a value", name))?),
+            };
+            if !allowed.contains(&name.as_str()) {
+                bail!("unknown option --{}", name);
+            }
+            opts.named.insert(name, value);
+        }
+        Ok(opts)
+    }
+
+    fn dir(&self) -> Result<PathBuf> {
+        match self.positional.as_slice() {
+            [dir] => Ok(PathBuf::from(dir)),
+            [] => bail!("missing DIR"),
+            _ => bail!("unexpected argument '{}'", self.positional[1]),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.named.get(name).map(String::as_str)
+    }
+
+    fn required(&self, name: &str) -> Result<&str> {
+        self.get(name).with_context(|| format!("missing --{}", name))
+    }
+
+    fn parsed<T: FromStr>(&self, name: &str, default: T) -> Result<T> {
+        match self.get(name) {
+            Some(v) => v.parse().map_err(|_| anyhow!("invalid value for --{}: '{}'", name, v)),
+            None => Ok(default),
+        }
+    }
+}
+
+pub fn parse(args: &[String]) -> Result<Command> {
+    let Some(first) = args.first() else { return Ok(Command::Annotate(None)) };
+    let rest = &args[1..];
+    let cmd = match first.as_str() {
+        "annotate" => {
+            let opts = Opts::parse(rest, &[])?;
+            Command::Annotate(if opts.positional.is_empty() { None } else { Some(opts.dir()?) })
+        }
+        "validate" => Command::Validate { dir: Opts::parse(rest, &[])?.dir()? },
+        "stats" => {
+            let opts = Opts::parse(rest, &["format", "output"])?;
+            Command::Stats {
+                dir: opts.dir()?,
+                format: opts.get("format").map(str::parse).transpose()?.unwrap_or_default(),
+                output: opts.get("output").map(PathBuf::from),
+            }
+        }
+        "convert" => {
+            let opts = Opts::parse(rest, &["to", "from", "output", "input"])?;
+            match (opts.get("to"), opts.get("from")) {
+                (Some(to), None) => Command::Convert {
+                    dir: opts.dir()?,
+                    to: to.parse()?,
+                    output: opts.get("output").map(PathBuf::from),
+                },
+                (None, Some(from)) => {
+                    if from.parse::<ConvertTarget>()? != ConvertTarget::Coco {
+                        bail!("--from only supports coco; use --to to convert between label formats");
+                    }
+                    Command::ImportCoco { dir: opts.dir()?, input: PathBuf::from(opts.required("input")?) }
+                }
+                _ => bail!("convert needs exactly one of --to or --from"),
+            }
+        }
+        "split" => {
+            let opts = Opts::parse(rest, &["output", "val", "test", "seed"])?;
+            let d = SplitOptions::default();
+            Command::Split {
+                dir: opts.dir()?,
+                output: PathBuf::from(opts.required("output")?),
+                options: SplitOptions {
+                    val: opts.parsed("val", d.val)?,
+                    test: opts.parsed("test", d.test)?,
+                    seed: opts.parsed("seed", d.seed)?,
+                },
+            }
+        }
+        "render" => {
+            let opts = Opts::parse(rest, &["output"])?;
+            Command::Render { dir: opts.dir()?, output: PathBuf::from(opts.required("output")?) }
+        }
+        "help" | "-h" | "--help" => Command::Help,
+        // plain `img-annotator DIR`
+        other if !other.starts_with('-') && rest.is_empty() => Command::Annotate(Some(PathBuf::from(other))),
+        other => bail!("unknown command '{}'", other),
+    };
+    Ok(cmd)
+}
+
+fn open_dataset(dir: &Path) -> Result<Dataset> {
+    if !dir.is_dir() {
+        bail!("Provided path is not a directory: {}", dir.display());
+    }
+    let ds = Dataset::open(dir)?;
+    if ds.may_need_legacy_offset() {
+        eprintln!("Note: the first class is 'object'. If these labels were made before class id mappings, \
+                   put 'offset 1' in {}.", ds.id_map_file_path().display());
+    }
+    Ok(ds)
+}
+
+fn write_stats_text(s: &stats::Stats, out: &mut dyn Write) -> Result<()> {
+    writeln!(out, "images: {} ({} without boxes)", s.images, s.unlabeled_images)?;
+    writeln!(out, "boxes: {}", s.boxes)?;
+    writeln!(out)?;
+    writeln!(out, "{:<24} {:>6} {:>8} {:>8}", "class", "id", "boxes", "images")?;
+    for c in &s.classes {
+        let id = c.id.map(|i| i.to_string()).unwrap_or_else(|| "-".into());
+        writeln!(out, "{:<24} {:>6} {:>8} {:>8}", c.name, id, c.boxes, c.images)?;
+    }
+    writeln!(out)?;
+    writeln!(out, "{:<24} {:>8}", "boxes per image", "images")?;
+    for (n, count) in s.boxes_per_image.iter().enumerate().filter(|(_, c)| **c > 0) {
+        writeln!(out, "{:<24} {:>8}", n, count)?;
+    }
+    for (title, d) in [("box size (px)", &s.box_size_px), ("aspect ratio (w/h)", &s.aspect_ratio)] {
+        writeln!(out)?;
+        writeln!(out, "{}: min {:.1}, median {:.1}, mean {:.1}, max {:.1}", title, d.min, d.median, d.mean, d.max)?;
+        for b in &d.bins {
+            writeln!(out, "  {:<22} {:>8}", b.label, b.count)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs a headless command. `Annotate` is handled by the GUI in `main`.
+pub fn run(cmd: Command) -> Result<ExitCode> {
+    match cmd {
+        Command::Annotate(_) => bail!("annotate opens the GUI and can't run headless"),
+        Command::Help => println!("{}", USAGE),
+        Command::Validate { dir } => {
+            let ds = open_dataset(&dir)?;
+            let issues = validate::validate(&ds)?;
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            let errors = issues.iter().filter(|i| i.severity == validate::Severity::Error).count();
+            println!("{} images checked, {} errors, {} warnings", ds.images.len(), errors, issues.len() - errors);
+            if errors > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Stats { dir, format, output } => {
+            let ds = open_dataset(&dir)?;
+            let s = stats::compute(&ds)?;
+            let mut out: Box<dyn Write> = match &output {
+                Some(path) => Box::new(BufWriter::new(File::create(path).with_context(|| format!("creating {}", path.display()))?)),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            match format {
+                StatsFormat::Text => write_stats_text(&s, &mut out)?,
+                StatsFormat::Csv => stats::write_csv(&s, &mut out)?,
+                StatsFormat::Json => {
+                    stats::write_json(&s, &mut out)?;
+                    writeln!(out)?;
+                }
+            }
+            out.flush()?;
+            if let Some(path) = output {
+                println!("Wrote {}", path.display());
+            }
+        }
+        Command::Convert { dir, to: ConvertTarget::Coco, output } => {
+            let ds = open_dataset(&dir)?;
+            let out = output.unwrap_or_else(|| ds.root.join(coco::DEFAULT_FILE));
+            let s = coco::export_to_file(&ds, &out)?;
+            println!("Wrote {} images, {} boxes, {} categories to {}", s.images, s.boxes, s.categories, out.display());
+        }
+        Command::Convert { dir, to: ConvertTarget::Labels(to), output } => {
+            if output.is_some() {
+                bail!("--output is only used with --to coco; label files are written next to the images");
+            }
+            let mut ds = open_dataset(&dir)?;
+            let from = LabelFormat::ALL.into_iter().find(|f| *f != to).unwrap_or_default();
+            let n = ds.convert_all(from, to)?;
+            println!("Wrote {} {} label files in {}", n, to.label(), ds.root.display());
+        }
+        Command::ImportCoco { dir, input } => {
+            let mut ds = open_dataset(&dir)?;
+            let s = coco::import_file(&input, &mut ds)?;
+            println!("Imported {} images, {} boxes, {} categories into {}", s.images, s.boxes, s.categories, ds.root.display());
+        }
+        Command::Split { dir, output, options } => {
+            let ds = open_dataset(&dir)?;
+            for (name, n) in split::split(&ds, &output, &options)? {
+                println!("{}: {} images", name, n);
+            }
+            println!("Wrote {}", output.display());
+        }
+        Command::Render { dir, output } => {
+            let ds = open_dataset(&dir)?;
+            let n = render::render(&ds, &output)?;
+            println!("Rendered {} images to {}", n, output.display());
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
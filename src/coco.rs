@@ -0,0 +1,284 @@
+//! COCO detection JSON (`instances.json`) import and export. Polygons are
+//! stored in `segmentation`, pose keypoints in `keypoints` with the skeleton
+//! in the categories.
+//!
+//! Category ids are the YOLO class ids plus one, since COCO tooling commonly
+//! reserves 0 for the background.
+
+use crate::atomic;
+use crate::classes::{ClassRegistry, IdMapping};
+use crate::dataset::Dataset;
+use crate::model::{BBox, ImageAnnotations, Keypoint};
+use crate::skeleton::Skeleton;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_FILE: &str = "instances.json";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CocoFile {
+    pub images: Vec<CocoImage>,
+    pub annotations: Vec<CocoAnnotation>,
+    pub categories: Vec<CocoCategory>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CocoImage {
+    pub id: u64,
+    pub file_name: String,
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CocoAnnotation {
+    pub id: u64,
+    pub image_id: u64,
+    pub category_id: u64,
+    /// `[x_min, y_min, width, height]` in pixels
+    pub bbox: [f64; 4],
+    #[serde(default)]
+    pub area: f64,
+    #[serde(default)]
+    pub iscrowd: u8,
+    #[serde(default)]
+    pub segmentation: Segmentation,
+    /// `[x, y, v, ...]` in pixels
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keypoints: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_keypoints: Option<usize>,
+}
+
+/// Polygons, or a run-length encoded mask (`{"counts": .., "size": ..}`) as
+/// used by `iscrowd` annotations.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Segmentation {
+    Polygons(Vec<Vec<f64>>),
+    Rle(serde_json::Value),
+}
+
+impl Default for Segmentation {
+    fn default() -> Self { Segmentation::Polygons(vec![]) }
+}
+
+impl Segmentation {
+    /// The polygons; none for a run-length mask, which is imported as its bbox.
+    pub fn polygons(&self) -> &[Vec<f64>] {
+        match self {
+            Segmentation::Polygons(p) => p,
+            Segmentation::Rle(_) => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CocoCategory {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub supercategory: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keypoints: Vec<String>,
+    /// 1-based keypoint index pairs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skeleton: Vec<[usize; 2]>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    pub images: usize,
+    pub boxes: usize,
+    pub categories: usize,
+    /// Files an import wrote: the label files, then the class and skeleton
+    /// files.
+    pub written: Vec<PathBuf>,
+}
+
+/// Builds a COCO document from every image of the dataset, in all splits,
+/// and its labels; leaves the dataset and its class file untouched.
+pub fn export(ds: &Dataset) -> Result<CocoFile> {
+    let mut coco = CocoFile::default();
+    // ids without a class get placeholders while reading
+    let mut classes = ds.classes.clone();
+    for (i, path) in ds.all_images()?.iter().enumerate() {
+        let (w, h) = image::image_dimensions(path)
+            .with_context(|| format!("reading size of {}", path.display()))?;
+        let image_id = i as u64 + 1;
+        let file_name = path.strip_prefix(&ds.root).unwrap_or(path);
+        coco.images.push(CocoImage {
+            id: image_id,
+            file_name: file_name.to_string_lossy().replace('\\', "/"),
+            width: w,
+            height: h,
+        });
+        let ann = ds.read_annotations(path, &mut classes)?;
+        for b in &ann.boxes {
+            let Some(cid) = classes.file_id(&b.class_name) else {
+                bail!("{}: class '{}' has no id", path.display(), b.class_name);
+            };
+            let (fw, fh) = (w as f64, h as f64);
+            let bbox = [b.left() as f64 * fw, b.top() as f64 * fh, b.w as f64 * fw, b.h as f64 * fh];
+            let (area, segmentation) = if b.is_polygon() {
+                let pts: Vec<(f64, f64)> = b.polygon.iter().map(|(x, y)| (*x as f64 * fw, *y as f64 * fh)).collect();
+                (polygon_area(&pts), Segmentation::Polygons(vec![pts.iter().flat_map(|(x, y)| [*x, *y]).collect()]))
+            } else {
+                (bbox[2] * bbox[3], Segmentation::default())
+            };
+            let keypoints: Vec<f64> = b.keypoints.iter()
+                .flat_map(|k| if k.v > 0 { [k.x as f64 * fw, k.y as f64 * fh, k.v as f64] } else { [0.0; 3] })
+                .collect();
+            coco.annotations.push(CocoAnnotation {
+                id: coco.annotations.len() as u64 + 1,
+                image_id,
+                category_id: cid as u64 + 1,
+                bbox,
+                area,
+                iscrowd: 0,
+                segmentation,
+                num_keypoints: (!keypoints.is_empty()).then(|| b.keypoints.iter().filter(|k| k.v > 0).count()),
+                keypoints,
+            });
+        }
+    }
+    // categories come from the class file, after reading so placeholders are included
+    for name in classes.iter() {
+        if let Some(cid) = classes.file_id(name) {
+            let (keypoints, skeleton) = match &ds.skeleton {
+                Some(sk) => (sk.keypoints.clone(), sk.edges.iter().map(|&(a, b)| [a + 1, b + 1]).collect()),
+                None => (vec![], vec![]),
+            };
+            coco.categories.push(CocoCategory { id: cid as u64 + 1, name: name.clone(), supercategory: String::new(), keypoints, skeleton });
+        }
+    }
+    coco.categories.sort_by_key(|c| c.id);
+    Ok(coco)
+}
+
+// shoelace formula
+fn polygon_area(pts: &[(f64, f64)]) -> f64 {
+    let n = pts.len();
+    (0..n).map(|i| {
+        let (a, b) = (pts[i], pts[(i + 1) % n]);
+        a.0 * b.1 - b.0 * a.1
+    }).sum::<f64>().abs() / 2.0
+}
+
+pub fn export_to_file(ds: &Dataset, out: &Path) -> Result<Summary> {
+    let coco = export(ds)?;
+    atomic::write_with(out, |w| Ok(serde_json::to_writer_pretty(w, &coco)?))?;
+    Ok(Summary { images: coco.images.len(), boxes: coco.annotations.len(), categories: coco.categories.len(), ..Default::default() })
+}
+
+pub fn read_file(path: &Path) -> Result<CocoFile> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    serde_json::from_reader(BufReader::new(file)).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// Writes one label file per COCO image (relative to `ds.root`, in the
+/// dataset's format) and replaces the class list with the COCO categories,
+/// keeping the dataset's id mapping. Categories sharing a name become one
+/// class.
+pub fn import(coco: &CocoFile, ds: &mut Dataset) -> Result<Summary> {
+    let mut cats: Vec<&CocoCategory> = coco.categories.iter().collect();
+    cats.sort_by_key(|c| c.id);
+    let names: HashMap<u64, &str> = cats.iter().map(|c| (c.id, c.name.as_str())).collect();
+    ds.classes = classes_for(&cats, &ds.classes);
+    // a pose file brings its skeleton along unless the dataset has one already
+    if ds.skeleton.is_none() && let Some(c) = cats.iter().find(|c| !c.keypoints.is_empty()) {
+        let edges = c.skeleton.iter().filter(|e| e[0] > 0 && e[1] > 0).map(|e| (e[0] - 1, e[1] - 1)).collect();
+        ds.skeleton = Some(Skeleton { keypoints: c.keypoints.clone(), edges, ..Default::default() });
+        ds.save_skeleton()?;
+    }
+
+    let mut per_image: HashMap<u64, Vec<&CocoAnnotation>> = HashMap::new();
+    for a in &coco.annotations {
+        per_image.entry(a.image_id).or_default().push(a);
+    }
+
+    let mut summary = Summary { categories: cats.len(), ..Default::default() };
+    for img in &coco.images {
+        let path = ds.root.join(&img.file_name);
+        let (w, h) = if img.width > 0 && img.height > 0 {
+            (img.width, img.height)
+        } else {
+            image::image_dimensions(&path).with_context(|| format!("{} has no size in the COCO file", img.file_name))?
+        };
+        let mut ann = ImageAnnotations::new(&path);
+        ann.size = Some((w, h));
+        for a in per_image.get(&img.id).map(|v| v.as_slice()).unwrap_or_default() {
+            let Some(name) = names.get(&a.category_id) else {
+                bail!("annotation {} uses unknown category {}", a.id, a.category_id);
+            };
+            let [x, y, bw, bh] = a.bbox;
+            let (fw, fh) = (w as f64, h as f64);
+            // YOLO lines hold a single polygon, so only the first part of a
+            // multi-part mask is kept
+            if let Some(poly) = a.segmentation.polygons().iter().find(|p| p.len() >= 6) {
+                let points: Vec<(f32, f32)> = poly.chunks_exact(2).map(|c| ((c[0] / fw) as f32, (c[1] / fh) as f32)).collect();
+                // an OBB dataset reads four-vertex lines as rotated boxes
+                ann.boxes.push(if ds.obb && points.len() == 4 { BBox::from_corners(*name, points) } else { BBox::from_polygon(*name, points) });
+                continue;
+            }
+            let mut b = BBox::new(
+                *name,
+                ((x + bw / 2.0) / fw) as f32,
+                ((y + bh / 2.0) / fh) as f32,
+                (bw / fw) as f32,
+                (bh / fh) as f32,
+            );
+            b.keypoints = a.keypoints.chunks_exact(3)
+                .map(|k| Keypoint { x: (k[0] / fw) as f32, y: (k[1] / fh) as f32, v: k[2].round().clamp(0.0, 2.0) as u8 })
+                .collect();
+            ann.boxes.push(b);
+        }
+        summary.boxes += ann.boxes.len();
+        summary.images += 1;
+        ds.save_annotations(&ann)?;
+        summary.written.push(ds.annotation_path(&path, ds.format));
+    }
+    ds.save_classes_file()?;
+    summary.written.extend([ds.classes_file_path(), ds.id_map_file_path(), ds.skeleton_file_path()]);
+    Ok(summary)
+}
+
+// With an offset, the classes follow the categories in id order after the
+// names below the offset; a table takes the category ids less one (or as
+// they are, when the file counts from 0).
+fn classes_for(cats: &[&CocoCategory], old: &ClassRegistry) -> ClassRegistry {
+    let mut unique: Vec<&CocoCategory> = vec![];
+    for c in cats {
+        if !unique.iter().any(|u| u.name == c.name) {
+            unique.push(c);
+        }
+    }
+    match old.mapping() {
+        IdMapping::Offset(k) => {
+            let below = (0..*k).map(|i| match old.name(i) {
+                Some(n) if !unique.iter().any(|c| c.name == n) => n.to_owned(),
+                _ => format!("class_{}", i),
+            });
+            let mut classes = ClassRegistry::new(below.chain(unique.iter().map(|c| c.name.clone())).collect());
+            classes.set_mapping(IdMapping::Offset(*k));
+            classes
+        }
+        IdMapping::Table(_) => {
+            let base = if cats.iter().any(|c| c.id == 0) { 0 } else { 1 };
+            let table: BTreeMap<usize, String> = unique.iter().map(|c| ((c.id - base) as usize, c.name.clone())).collect();
+            let mut classes = ClassRegistry::new(table.values().cloned().collect());
+            classes.set_mapping(IdMapping::Table(table));
+            classes
+        }
+    }
+}
+
+pub fn import_file(path: &Path, ds: &mut Dataset) -> Result<Summary> {
+    import(&read_file(path)?, ds)
+}
@@ -0,0 +1,57 @@
+//! Reconciling the boxes being edited with a label file another program
+//! changed in the meantime: which boxes differ, and a three-way merge against
+//! the version both sides started from.
+
+use crate::model::BBox;
+
+// label files round coordinates, VOC files even to whole pixels
+const TOLERANCE: f32 = 1e-3;
+
+/// Same class, flags and shape, up to the rounding of label files.
+pub fn same_box(a: &BBox, b: &BBox) -> bool {
+    let close = |x: f32, y: f32| (x - y).abs() <= TOLERANCE;
+    a.class_name == b.class_name
+        && a.difficult == b.difficult
+        && a.truncated == b.truncated
+        && a.rotated == b.rotated
+        && close(a.cx, b.cx) && close(a.cy, b.cy) && close(a.w, b.w) && close(a.h, b.h)
+        && a.polygon.len() == b.polygon.len()
+        && a.polygon.iter().zip(&b.polygon).all(|(p, q)| close(p.0, q.0) && close(p.1, q.1))
+        && a.keypoints.len() == b.keypoints.len()
+        && a.keypoints.iter().zip(&b.keypoints).all(|(p, q)| p.v == q.v && close(p.x, q.x) && close(p.y, q.y))
+}
+
+/// The boxes of `a` that have no match in `b`, each box of `b` matching once.
+pub fn missing_from(a: &[BBox], b: &[BBox]) -> Vec<BBox> {
+    let mut unmatched: Vec<&BBox> = b.iter().collect();
+    let mut out = vec![];
+    for x in a {
+        match unmatched.iter().position(|y| same_box(x, y)) {
+            Some(i) => { unmatched.remove(i); }
+            None => out.push(x.clone()),
+        }
+    }
+    out
+}
+
+/// Whether both hold the same boxes, in any order.
+pub fn same_boxes(a: &[BBox], b: &[BBox]) -> bool {
+    a.len() == b.len() && missing_from(a, b).is_empty()
+}
+
+/// `ours` without the boxes `theirs` removed from `base` and with the ones
+/// it added. A box both sides changed is kept in both versions.
+pub fn merge(base: &[BBox], ours: &[BBox], theirs: &[BBox]) -> Vec<BBox> {
+    let mut out = ours.to_vec();
+    for gone in missing_from(base, theirs) {
+        if let Some(i) = out.iter().position(|b| same_box(b, &gone)) {
+            out.remove(i);
+        }
+    }
+    for new in missing_from(theirs, base) {
+        if !out.iter().any(|b| same_box(b, &new)) {
+            out.push(new);
+        }
+    }
+    out
+}
@@ -0,0 +1,471 @@
+use crate::backup::{self, Retention};
+use crate::classes::{ClassRegistry, DEFAULT_CLASS, ID_MAP_FILE, IdMapping};
+use crate::model::ImageAnnotations;
+use crate::settings::{PROJECT_FILE, ProjectConfig};
+use crate::skeleton::{SKELETON_FILE, Skeleton};
+use crate::yolo::KptShape;
+use crate::{atomic, ultralytics, voc, yolo};
+use anyhow::{Context, Result, bail};
+use glob::glob;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+pub const CLASSES_FILE: &str = "_darknet.labels";
+pub const IMAGE_PATTERNS: [&str; 6] = ["*.png", "*.jpg", "*.jpeg", "*.bmp", "*.webp", "*.tif"];
+
+/// On-disk label format, one label file per image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LabelFormat {
+    /// `<image>.txt` with `<class_id> <cx> <cy> <w> <h>` ratios
+    #[default]
+    Yolo,
+    /// `<image>.xml` Pascal VOC with pixel corners
+    Voc,
+}
+
+impl LabelFormat {
+    pub const ALL: [LabelFormat; 2] = [LabelFormat::Yolo, LabelFormat::Voc];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            LabelFormat::Yolo => "txt",
+            LabelFormat::Voc => "xml",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LabelFormat::Yolo => "YOLO",
+            LabelFormat::Voc => "Pascal VOC",
+        }
+    }
+}
+
+/// How images, labels and the class list are arranged on disk.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Layout {
+    /// images, label files and `_darknet.labels` in one folder
+    #[default]
+    Flat,
+    /// `images/<split>` and `labels/<split>` under the root, class names in
+    /// `data.yaml`; only the current split is loaded
+    Ultralytics { splits: Vec<String>, split: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct ImageEntry {
+    pub path: PathBuf,
+}
+
+/// An image folder together with its class list and label format.
+#[derive(Clone, Debug, Default)]
+pub struct Dataset {
+    /// The image folder for a flat layout, the folder holding `images/`,
+    /// `labels/` and `data.yaml` for an Ultralytics one.
+    pub root: PathBuf,
+    pub layout: Layout,
+    pub images: Vec<ImageEntry>,
+    pub classes: ClassRegistry,
+    /// Format label files are read and written in.
+    pub format: LabelFormat,
+    /// Keypoint skeleton of a pose dataset.
+    pub skeleton: Option<Skeleton>,
+    /// Four-corner YOLO lines are rotated boxes, as in an OBB dataset, not
+    /// polygons. Kept in the project file.
+    pub obb: bool,
+    /// Keep the previous versions of label and class files when they are
+    /// overwritten; off unless set.
+    pub backup: Option<Retention>,
+}
+
+impl Dataset {
+    /// Loads the class file (if any) and scans for images. `dir` is either a
+    /// plain image folder, or the root or an image folder of an Ultralytics
+    /// `images/` + `labels/` dataset.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let mut ds = match ultralytics::find_root(dir) {
+            Some((root, split)) => {
+                let splits = ultralytics::splits(&root);
+                let split = split.filter(|s| splits.contains(s)).unwrap_or_else(|| splits[0].clone());
+                Self { root, layout: Layout::Ultralytics { splits, split }, ..Default::default() }
+            }
+            None => Self { root: dir.to_path_buf(), ..Default::default() },
+        };
+        ds.load_classes_file()?;
+        ds.skeleton = Skeleton::load(&ds.skeleton_file_path())?;
+        ds.obb = ds.read_obb();
+        ds.images = Self::load_images_from_dir(&ds.image_dir())?;
+        ds.format = ds.detect_format();
+        if ds.format == LabelFormat::Voc && !ds.classes_file_path().exists() {
+            // VOC files carry class names; build the list from them as they're read
+            ds.classes = ClassRegistry::empty();
+        }
+        Ok(ds)
+    }
+
+    /// Re-scans the images (and splits) and re-reads the class and skeleton
+    /// files.
+    pub fn reload(&mut self) -> Result<()> {
+        if let Layout::Ultralytics { splits, split } = &mut self.layout {
+            *splits = ultralytics::splits(&self.root);
+            if !splits.contains(split) {
+                *split = splits[0].clone();
+            }
+        }
+        self.images = Self::load_images_from_dir(&self.image_dir())?;
+        self.skeleton = Skeleton::load(&self.skeleton_file_path())?;
+        self.obb = self.read_obb();
+        self.load_classes_file()
+    }
+
+    /// Folder the current images are read from.
+    pub fn image_dir(&self) -> PathBuf {
+        match &self.layout {
+            Layout::Flat => self.root.clone(),
+            Layout::Ultralytics { split, .. } => ultralytics::split_dir(&self.root, split),
+        }
+    }
+
+    /// Images of every split, for changes that must reach all label files.
+    pub fn all_images(&self) -> Result<Vec<PathBuf>> {
+        let Layout::Ultralytics { splits, .. } = &self.layout else {
+            return Ok(self.images.iter().map(|e| e.path.clone()).collect());
+        };
+        let mut out = vec![];
+        for split in splits {
+            out.extend(Self::load_images_from_dir(&ultralytics::split_dir(&self.root, split))?.into_iter().map(|e| e.path));
+        }
+        Ok(out)
+    }
+
+    /// Folders holding the images, label files and class file of the current
+    /// split.
+    pub fn watched_dirs(&self) -> Vec<PathBuf> {
+        match &self.layout {
+            Layout::Flat => vec![self.root.clone()],
+            Layout::Ultralytics { split, .. } => vec![self.root.clone(), self.image_dir(), ultralytics::labels_dir(&self.root, split)],
+        }
+    }
+
+    /// Switches to another split of an Ultralytics dataset and loads its images.
+    pub fn set_split(&mut self, name: &str) -> Result<()> {
+        let Layout::Ultralytics { splits, split } = &mut self.layout else {
+            bail!("{} has no splits", self.root.display());
+        };
+        if !splits.iter().any(|s| s == name) {
+            bail!("unknown split '{}'", name);
+        }
+        *split = name.to_owned();
+        self.images = Self::load_images_from_dir(&self.image_dir())?;
+        Ok(())
+    }
+
+    /// Whether `path` has one of the `IMAGE_PATTERNS` extensions.
+    pub fn is_image_file(path: &Path) -> bool {
+        let ext = path.extension().unwrap_or_default();
+        IMAGE_PATTERNS.iter().any(|p| ext == p.trim_start_matches("*."))
+    }
+
+    pub fn load_images_from_dir(dir: &Path) -> Result<Vec<ImageEntry>> {
+        let mut imgs = vec![];
+        for pat in IMAGE_PATTERNS.iter() {
+            let globpat = dir.join(pat).to_string_lossy().to_string();
+            for p in glob(&globpat)?.flatten() {
+                imgs.push(ImageEntry { path: p });
+            }
+        }
+        imgs.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(imgs)
+    }
+
+    /// `_darknet.labels`, or `data.yaml` for an Ultralytics layout.
+    pub fn classes_file_path(&self) -> PathBuf {
+        match self.layout {
+            Layout::Flat => self.root.join(CLASSES_FILE),
+            Layout::Ultralytics { .. } => self.root.join(ultralytics::DATA_YAML),
+        }
+    }
+
+    pub fn id_map_file_path(&self) -> PathBuf {
+        self.root.join(ID_MAP_FILE)
+    }
+
+    /// Where the annotator left off in this dataset, next to the class file.
+    pub fn project_file_path(&self) -> PathBuf {
+        self.root.join(PROJECT_FILE)
+    }
+
+    /// `_darknet.skeleton`, or `data.yaml` for an Ultralytics layout.
+    pub fn skeleton_file_path(&self) -> PathBuf {
+        match self.layout {
+            Layout::Flat => self.root.join(SKELETON_FILE),
+            Layout::Ultralytics { .. } => self.root.join(ultralytics::DATA_YAML),
+        }
+    }
+
+    /// Writes the skeleton, or drops its keys from the file without one.
+    pub fn save_skeleton(&self) -> Result<()> {
+        match &self.skeleton {
+            Some(sk) => sk.save(&self.skeleton_file_path()),
+            None => Skeleton::clear(&self.skeleton_file_path()),
+        }
+    }
+
+    /// Changes the skeleton, rewriting the YOLO label files of every split
+    /// when the keypoint columns change; `removed` is a keypoint dropped from
+    /// the old skeleton. Refuses, writing nothing, when a file has lines the
+    /// old skeleton doesn't read or polygons a pose dataset can't hold.
+    /// Returns the files rewritten.
+    pub fn set_skeleton(&mut self, skeleton: Option<Skeleton>, removed: Option<usize>) -> Result<Vec<PathBuf>> {
+        let pose = |k: Option<KptShape>| k.filter(|k| k.count > 0);
+        let (old, new) = (self.kpt_shape(), skeleton.as_ref().map(Skeleton::kpt_shape));
+        let mut anns = vec![];
+        if pose(old) != pose(new) {
+            let mut classes = self.classes.clone();
+            let mut bad = vec![];
+            for image in self.all_images()? {
+                let path = self.annotation_path(&image, LabelFormat::Yolo);
+                if !path.exists() {
+                    continue;
+                }
+                for (n, line) in fs::read_to_string(&path)?.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+                    match yolo::parse_line_checked(line, old) {
+                        Err(e) => bad.push(format!("{}:{}: {}", path.display(), n + 1, e)),
+                        Ok(rec) if pose(new).is_some() && !rec.points.is_empty() => bad.push(format!("{}:{}: polygon", path.display(), n + 1)),
+                        Ok(_) => {}
+                    }
+                }
+                let mut ann = self.read_label_file(&image, &path, LabelFormat::Yolo, &mut classes)?;
+                if let Some(k) = removed {
+                    for b in ann.boxes.iter_mut().filter(|b| k < b.keypoints.len()) {
+                        b.keypoints.remove(k);
+                    }
+                }
+                anns.push(ann);
+            }
+            if !bad.is_empty() {
+                let more = if bad.len() > 10 { format!("\n... and {} more", bad.len() - 10) } else { String::new() };
+                bail!("the label files don't fit the new keypoints:\n{}{}", bad[..bad.len().min(10)].join("\n"), more);
+            }
+            // placeholders for unknown ids keep those ids when written back
+            self.update_classes(classes)?;
+        }
+        self.skeleton = skeleton;
+        let mut written = vec![];
+        for ann in &anns {
+            self.save_annotations_as(ann, LabelFormat::Yolo)?;
+            written.push(self.annotation_path(&ann.image, LabelFormat::Yolo));
+        }
+        self.save_skeleton()?;
+        Ok(written)
+    }
+
+    // a project file that doesn't parse is reported when the annotator
+    // restores it; its lines are then read as polygons
+    fn read_obb(&self) -> bool {
+        ProjectConfig::load(&self.project_file_path()).is_ok_and(|p| p.obb)
+    }
+
+    /// Keypoint columns of YOLO pose lines, when there is a skeleton.
+    pub fn kpt_shape(&self) -> Option<KptShape> {
+        self.skeleton.as_ref().map(Skeleton::kpt_shape)
+    }
+
+    /// Replaces the class list with the file contents; keeps the current list
+    /// when there is no class file yet. The id mapping is read from its own
+    /// file and defaults to ids being plain indices into the class list;
+    /// data.yaml holds the mapping itself.
+    pub fn load_classes_file(&mut self) -> Result<()> {
+        let path = self.classes_file_path();
+        if let Layout::Ultralytics { .. } = self.layout {
+            if path.exists() {
+                self.classes = ultralytics::read_classes(&path)?;
+            }
+            return Ok(());
+        }
+        if path.exists() {
+            self.classes = ClassRegistry::load(&path)?;
+        }
+        let map_path = self.id_map_file_path();
+        let mapping = if map_path.exists() { IdMapping::load(&map_path)? } else { IdMapping::default() };
+        self.classes.set_mapping(mapping);
+        Ok(())
+    }
+
+    /// Writes the class list, and the id mapping unless it is the default one
+    /// and was never stored.
+    pub fn save_classes_file(&self) -> Result<()> {
+        self.back_up(&self.classes_file_path())?;
+        if let Layout::Ultralytics { .. } = self.layout {
+            return ultralytics::write_classes(&self.root, &self.classes);
+        }
+        self.classes.save(&self.classes_file_path())?;
+        let map_path = self.id_map_file_path();
+        if !self.classes.mapping().is_identity() || map_path.exists() {
+            self.back_up(&map_path)?;
+            self.classes.mapping().save(&map_path)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the class file starts with the `object` placeholder that
+    /// versions before the id mapping left out when numbering classes, with
+    /// no mapping stored. Labels made with those versions need `offset 1`.
+    pub fn may_need_legacy_offset(&self) -> bool {
+        self.layout == Layout::Flat
+            && self.classes.len() > 1
+            && self.classes.name(0) == Some(DEFAULT_CLASS)
+            && self.classes.mapping().is_identity()
+            && self.classes_file_path().exists()
+            && !self.id_map_file_path().exists()
+    }
+
+    /// Keeps the current contents of `file` as a version, if backups are on.
+    pub fn back_up(&self, file: &Path) -> Result<()> {
+        match &self.backup {
+            Some(retention) => backup::save_version(&self.root, file, retention),
+            None => Ok(()),
+        }
+    }
+
+    /// Label file of `image`: next to it, or under `labels/` instead of
+    /// `images/` for an Ultralytics layout.
+    pub fn annotation_path(&self, image: &Path, format: LabelFormat) -> PathBuf {
+        let mut out = match self.layout {
+            Layout::Flat => image.to_path_buf(),
+            Layout::Ultralytics { .. } => ultralytics::label_path_for_image(image),
+        };
+        out.set_extension(format.extension());
+        out
+    }
+
+    /// A folder is treated as Pascal VOC when images have `.xml` labels and
+    /// none has a `.txt` label.
+    pub fn detect_format(&self) -> LabelFormat {
+        let has = |f: LabelFormat| self.images.iter().any(|e| self.annotation_path(&e.path, f).exists());
+        if has(LabelFormat::Voc) && !has(LabelFormat::Yolo) { LabelFormat::Voc } else { LabelFormat::Yolo }
+    }
+
+    /// Reads the label file of `image` in the dataset's format, falling back
+    /// to the other format when there is none yet. No label file means no
+    /// boxes. Newly discovered classes are written back to the class file.
+    pub fn load_annotations(&mut self, image: &Path) -> Result<ImageAnnotations> {
+        let mut classes = self.classes.clone();
+        let ann = self.read_annotations(image, &mut classes)?;
+        self.update_classes(classes)?;
+        Ok(ann)
+    }
+
+    /// Reads the `format` label file of `image`, `None` when it doesn't exist.
+    pub fn load_annotations_as(&mut self, image: &Path, format: LabelFormat) -> Result<Option<ImageAnnotations>> {
+        let mut classes = self.classes.clone();
+        let ann = self.read_annotations_as(image, format, &mut classes)?;
+        self.update_classes(classes)?;
+        Ok(ann)
+    }
+
+    // keeps classes discovered while reading, persisting them if there are any
+    fn update_classes(&mut self, classes: ClassRegistry) -> Result<()> {
+        if classes != self.classes {
+            self.classes = classes;
+            self.save_classes_file()?;
+        }
+        Ok(())
+    }
+
+    /// Like `load_annotations`, but resolves classes against `classes` and
+    /// leaves the dataset and its class file untouched. Used by read-only
+    /// tools such as `stats` and `render`.
+    pub fn read_annotations(&self, image: &Path, classes: &mut ClassRegistry) -> Result<ImageAnnotations> {
+        let preferred = self.format;
+        let fallback = LabelFormat::ALL.into_iter().filter(|f| *f != preferred);
+        for format in std::iter::once(preferred).chain(fallback) {
+            if let Some(ann) = self.read_annotations_as(image, format, classes)? {
+                return Ok(ann);
+            }
+        }
+        Ok(ImageAnnotations::new(image))
+    }
+
+    pub fn read_annotations_as(&self, image: &Path, format: LabelFormat, classes: &mut ClassRegistry) -> Result<Option<ImageAnnotations>> {
+        let annp = self.annotation_path(image, format);
+        if !annp.exists() {
+            return Ok(None);
+        }
+        self.read_label_file(image, &annp, format, classes).map(Some)
+    }
+
+    /// Reads the boxes of `image` from the `format` label file `annp`, which
+    /// may be somewhere other than `annotation_path`, e.g. a kept version.
+    pub fn read_label_file(&self, image: &Path, annp: &Path, format: LabelFormat, classes: &mut ClassRegistry) -> Result<ImageAnnotations> {
+        let mut ann = ImageAnnotations::new(image);
+        match format {
+            LabelFormat::Yolo => {
+                ann.boxes = yolo::read_boxes(BufReader::new(File::open(annp)?), classes, self.kpt_shape(), self.obb)?.0;
+            }
+            LabelFormat::Voc => {
+                let doc = voc::parse(&fs::read_to_string(annp)?).with_context(|| format!("reading {}", annp.display()))?;
+                ann.size = doc.size();
+                let size = ann.pixel_size()?;
+                ann.size = Some(size);
+                ann.boxes = doc.to_boxes(size);
+                for b in &ann.boxes {
+                    classes.ensure(&b.class_name);
+                }
+            }
+        }
+        Ok(ann)
+    }
+
+    /// Writes the label file of `ann.image` in the dataset's format, appending
+    /// unknown classes to the class file.
+    pub fn save_annotations(&mut self, ann: &ImageAnnotations) -> Result<()> {
+        self.save_annotations_as(ann, self.format)
+    }
+
+    pub fn save_annotations_as(&mut self, ann: &ImageAnnotations, format: LabelFormat) -> Result<()> {
+        let annp = self.annotation_path(&ann.image, format);
+        if let Some(dir) = annp.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.back_up(&annp)?;
+        let changed = match format {
+            LabelFormat::Yolo => {
+                let kpt = self.kpt_shape();
+                atomic::write_with(&annp, |w| yolo::write_boxes(w, &ann.boxes, &mut self.classes, kpt))?
+            }
+            LabelFormat::Voc => {
+                let mut changed = false;
+                for b in &ann.boxes {
+                    changed |= self.classes.ensure(&b.class_name).1;
+                }
+                let file_name = ann.image.file_name().unwrap_or_default().to_string_lossy();
+                let mut doc = voc::VocAnnotation::from_boxes(&file_name, ann.pixel_size()?, &ann.boxes);
+                if let Some(dir) = ann.image.parent().and_then(|d| d.file_name()) {
+                    doc.folder = dir.to_string_lossy().to_string();
+                }
+                atomic::write(&annp, voc::to_xml(&doc))?;
+                changed
+            }
+        };
+        if changed {
+            self.save_classes_file()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites every `from` label file, in all splits, in the `to` format.
+    /// Images without a `from` label are skipped. Returns the number of files
+    /// written.
+    pub fn convert_all(&mut self, from: LabelFormat, to: LabelFormat) -> Result<usize> {
+        let mut n = 0;
+        for image in self.all_images()? {
+            if let Some(ann) = self.load_annotations_as(&image, from)? {
+                self.save_annotations_as(&ann, to)?;
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+}
@@ -0,0 +1,231 @@
+//! Undo / redo for the boxes of one image, kept as a list of commands that
+//! each know how to apply and revert themselves.
+//!
+//! Edits are made as transactions: `begin` snapshots the boxes when a gesture
+//! starts and `commit` records whatever changed since as one command, or
+//! nothing if the gesture changed nothing.
+
+use crate::model::BBox;
+use std::collections::VecDeque;
+
+pub const DEFAULT_LIMIT: usize = 200;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+    Insert { index: usize, bbox: BBox },
+    Remove { index: usize, bbox: BBox },
+    Update { index: usize, before: BBox, after: BBox },
+}
+
+impl Edit {
+    fn apply(&self, boxes: &mut Vec<BBox>) {
+        match self {
+            Edit::Insert { index, bbox } => boxes.insert((*index).min(boxes.len()), bbox.clone()),
+            Edit::Remove { index, .. } => {
+                if *index < boxes.len() {
+                    boxes.remove(*index);
+                }
+            }
+            Edit::Update { index, after, .. } => {
+                if let Some(b) = boxes.get_mut(*index) {
+                    *b = after.clone();
+                }
+            }
+        }
+    }
+
+    fn revert(&self, boxes: &mut Vec<BBox>) {
+        match self {
+            Edit::Insert { index, .. } => {
+                if *index < boxes.len() {
+                    boxes.remove(*index);
+                }
+            }
+            Edit::Remove { index, bbox } => boxes.insert((*index).min(boxes.len()), bbox.clone()),
+            Edit::Update { index, before, .. } => {
+                if let Some(b) = boxes.get_mut(*index) {
+                    *b = before.clone();
+                }
+            }
+        }
+    }
+}
+
+/// One user action, with the label shown in the history list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    pub label: String,
+    /// Applied in order, reverted in reverse order.
+    pub edits: Vec<Edit>,
+}
+
+impl Command {
+    pub fn new(label: impl Into<String>, edits: Vec<Edit>) -> Self {
+        Self { label: label.into(), edits }
+    }
+
+    pub fn insert(label: impl Into<String>, index: usize, bbox: BBox) -> Self {
+        Self::new(label, vec![Edit::Insert { index, bbox }])
+    }
+
+    pub fn remove(label: impl Into<String>, index: usize, bbox: BBox) -> Self {
+        Self::new(label, vec![Edit::Remove { index, bbox }])
+    }
+
+    pub fn update(label: impl Into<String>, index: usize, before: BBox, after: BBox) -> Self {
+        Self::new(label, vec![Edit::Update { index, before, after }])
+    }
+
+    pub fn apply(&self, boxes: &mut Vec<BBox>) {
+        for e in &self.edits {
+            e.apply(boxes);
+        }
+    }
+
+    pub fn revert(&self, boxes: &mut Vec<BBox>) {
+        for e in self.edits.iter().rev() {
+            e.revert(boxes);
+        }
+    }
+
+    /// The edits turning `before` into `after`: updates in place when the
+    /// count is unchanged, otherwise the differing middle is removed and
+    /// re-inserted.
+    pub fn diff(label: impl Into<String>, before: &[BBox], after: &[BBox]) -> Self {
+        let prefix = before.iter().zip(after).take_while(|(a, b)| a == b).count();
+        let suffix = before[prefix..].iter().rev().zip(after[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+        let (old, new) = (&before[prefix..before.len() - suffix], &after[prefix..after.len() - suffix]);
+        let mut edits = vec![];
+        if old.len() == new.len() {
+            for (i, (a, b)) in old.iter().zip(new).enumerate() {
+                if a != b {
+                    edits.push(Edit::Update { index: prefix + i, before: a.clone(), after: b.clone() });
+                }
+            }
+        } else {
+            edits.extend(old.iter().map(|b| Edit::Remove { index: prefix, bbox: b.clone() }));
+            edits.extend(new.iter().enumerate().map(|(i, b)| Edit::Insert { index: prefix + i, bbox: b.clone() }));
+        }
+        Self::new(label, edits)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct History {
+    done: VecDeque<Command>,
+    // most recently undone last
+    undone: Vec<Command>,
+    limit: usize,
+    // boxes as they were when the open transaction began
+    pending: Option<Vec<BBox>>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::with_limit(DEFAULT_LIMIT)
+    }
+}
+
+impl History {
+    pub fn with_limit(limit: usize) -> Self {
+        Self { done: VecDeque::new(), undone: vec![], limit: limit.max(1), pending: None }
+    }
+
+    /// Starts a transaction; replaces one that was left open.
+    pub fn begin(&mut self, boxes: &[BBox]) {
+        self.pending = Some(boxes.to_vec());
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Ends the transaction, recording the changes since `begin` as one
+    /// command. Returns false if nothing changed (or none was open).
+    pub fn commit(&mut self, label: impl Into<String>, boxes: &[BBox]) -> bool {
+        let Some(before) = self.pending.take() else { return false };
+        let cmd = Command::diff(label, &before, boxes);
+        if cmd.is_empty() {
+            return false;
+        }
+        self.record(cmd);
+        true
+    }
+
+    /// Drops the open transaction without recording it.
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    /// Records a command that has already been applied; clears the redo list.
+    pub fn record(&mut self, cmd: Command) {
+        if cmd.is_empty() {
+            return;
+        }
+        self.undone.clear();
+        self.done.push_back(cmd);
+        while self.done.len() > self.limit {
+            self.done.pop_front();
+        }
+    }
+
+    /// Applies `cmd` to `boxes` and records it.
+    pub fn execute(&mut self, cmd: Command, boxes: &mut Vec<BBox>) {
+        cmd.apply(boxes);
+        self.record(cmd);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Reverts the last command; false if there was nothing to undo.
+    pub fn undo(&mut self, boxes: &mut Vec<BBox>) -> bool {
+        self.pending = None;
+        let Some(cmd) = self.done.pop_back() else { return false };
+        cmd.revert(boxes);
+        self.undone.push(cmd);
+        true
+    }
+
+    /// Re-applies the last undone command; false if there was nothing to redo.
+    pub fn redo(&mut self, boxes: &mut Vec<BBox>) -> bool {
+        self.pending = None;
+        let Some(cmd) = self.undone.pop() else { return false };
+        cmd.apply(boxes);
+        self.done.push_back(cmd);
+        true
+    }
+
+    /// Labels of all commands, oldest first, including the undone ones.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.done.iter().chain(self.undone.iter().rev()).map(|c| c.label.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.done.len() + self.undone.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of commands currently applied; `labels()` past it can be redone.
+    pub fn position(&self) -> usize {
+        self.done.len()
+    }
+
+    /// Undoes or redoes until `position` commands are applied.
+    pub fn go_to(&mut self, position: usize, boxes: &mut Vec<BBox>) {
+        while self.position() > position && self.undo(boxes) {}
+        while self.position() < position && self.redo(boxes) {}
+    }
+}
@@ -0,0 +1,251 @@
+//! Keyboard shortcuts of the annotation window, read from a user-editable
+//! `keymap.toml` that maps action names to one key or a list of keys:
+//!
+//! ```toml
+//! next_image = ["D", "PageDown"]
+//! delete = "Delete"
+//! ```
+//!
+//! A key may only belong to one action.
+
+use crate::atomic;
+use anyhow::{Context, Result, bail};
+use egui::{InputState, Key, KeyboardShortcut, Modifiers};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const KEYMAP_FILE: &str = "keymap.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Undo,
+    Redo,
+    Copy,
+    Paste,
+    CopyPrevious,
+    SelectAll,
+    Delete,
+    PrevImage,
+    NextImage,
+    Save,
+    Draw,
+    NextBox,
+    PrevBox,
+    Shortcuts,
+    /// Picks the n-th class (0-based) for new boxes.
+    Class(usize),
+}
+
+impl Action {
+    pub const ALL: [Action; 24] = [
+        Action::Undo, Action::Redo, Action::Copy, Action::Paste, Action::CopyPrevious, Action::SelectAll,
+        Action::Delete, Action::PrevImage, Action::NextImage, Action::Save, Action::Draw, Action::NextBox,
+        Action::PrevBox, Action::Shortcuts, Action::Class(0), Action::Class(1), Action::Class(2), Action::Class(3),
+        Action::Class(4), Action::Class(5), Action::Class(6), Action::Class(7), Action::Class(8), Action::Class(9),
+    ];
+
+    /// Key of the action in keymap.toml.
+    pub fn name(self) -> String {
+        let name = match self {
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Copy => "copy",
+            Action::Paste => "paste",
+            Action::CopyPrevious => "copy_previous",
+            Action::SelectAll => "select_all",
+            Action::Delete => "delete",
+            Action::PrevImage => "prev_image",
+            Action::NextImage => "next_image",
+            Action::Save => "save",
+            Action::Draw => "draw",
+            Action::NextBox => "next_box",
+            Action::PrevBox => "prev_box",
+            Action::Shortcuts => "shortcuts",
+            Action::Class(n) => return format!("class_{}", n + 1),
+        };
+        name.to_owned()
+    }
+
+    pub fn description(self) -> String {
+        let text = match self {
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Copy => "Copy the selected boxes",
+            Action::Paste => "Paste boxes",
+            Action::CopyPrevious => "Copy all boxes of the previous image (unlabeled images only)",
+            Action::SelectAll => "Select all boxes",
+            Action::Delete => "Delete the selected boxes",
+            Action::PrevImage => "Previous image",
+            Action::NextImage => "Next image",
+            Action::Save => "Save",
+            Action::Draw => "Draw a new shape, even on top of other boxes",
+            Action::NextBox => "Select the next box",
+            Action::PrevBox => "Select the previous box",
+            Action::Shortcuts => "Show / hide this list",
+            Action::Class(n) => return format!("Use class {} for new boxes", n + 1),
+        };
+        text.to_owned()
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Undo => &["Ctrl+Z"],
+            Action::Redo => &["Ctrl+Y", "Ctrl+Shift+Z"],
+            Action::Copy => &["Ctrl+C"],
+            Action::Paste => &["Ctrl+V"],
+            Action::CopyPrevious => &["P"],
+            Action::SelectAll => &["Ctrl+A"],
+            Action::Delete => &["Delete"],
+            Action::PrevImage => &["A", "PageUp"],
+            Action::NextImage => &["D", "PageDown"],
+            Action::Save => &["Ctrl+S"],
+            Action::Draw => &["W"],
+            Action::NextBox => &["Tab"],
+            Action::PrevBox => &["Shift+Tab"],
+            Action::Shortcuts => &["F1"],
+            Action::Class(n) => &CLASS_KEYS[n % 10],
+        }
+    }
+}
+
+const CLASS_KEYS: [[&str; 1]; 10] = [["1"], ["2"], ["3"], ["4"], ["5"], ["6"], ["7"], ["8"], ["9"], ["0"]];
+
+const KEYS: [Key; 73] = [
+    Key::ArrowDown, Key::ArrowLeft, Key::ArrowRight, Key::ArrowUp, Key::Escape, Key::Tab, Key::Backspace,
+    Key::Enter, Key::Space, Key::Insert, Key::Delete, Key::Home, Key::End, Key::PageUp, Key::PageDown,
+    Key::Minus, Key::PlusEquals, Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6,
+    Key::Num7, Key::Num8, Key::Num9, Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+    Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V,
+    Key::W, Key::X, Key::Y, Key::Z, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8,
+    Key::F9, Key::F10, Key::F11, Key::F12, Key::F13, Key::F14, Key::F15, Key::F16, Key::F17, Key::F18,
+    Key::F19, Key::F20,
+];
+
+/// Parses `Ctrl+Shift+Z` style shortcuts; `Ctrl` is Cmd on macOS.
+pub fn parse_shortcut(text: &str) -> Result<KeyboardShortcut> {
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let Some(key_name) = parts.pop().filter(|k| !k.is_empty()) else { bail!("no key in '{}'", text) };
+    let mut modifiers = Modifiers::NONE;
+    for m in parts {
+        modifiers = modifiers | match m.to_ascii_lowercase().as_str() {
+            "ctrl" | "cmd" | "command" => Modifiers::COMMAND,
+            "shift" => Modifiers::SHIFT,
+            "alt" | "option" => Modifiers::ALT,
+            _ => bail!("unknown modifier '{}' in '{}'", m, text),
+        };
+    }
+    let key = KEYS.iter().copied().find(|k| k.name().eq_ignore_ascii_case(key_name))
+        .with_context(|| format!("unknown key '{}'", key_name))?;
+    Ok(KeyboardShortcut::new(modifiers, key))
+}
+
+/// The keymap.toml spelling of a shortcut.
+pub fn format_shortcut(s: &KeyboardShortcut) -> String {
+    let mut text = String::new();
+    if s.modifiers.command || s.modifiers.ctrl { text += "Ctrl+"; }
+    if s.modifiers.alt { text += "Alt+"; }
+    if s.modifiers.shift { text += "Shift+"; }
+    text + s.key.name()
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Binding {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<KeyboardShortcut>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL.iter().map(|a| {
+            (*a, a.default_keys().iter().map(|k| parse_shortcut(k).expect("default shortcut")).collect())
+        }).collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Reads keymap.toml text; actions it leaves out keep their default keys.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut keymap = Self::default();
+        let table: BTreeMap<String, Binding> = toml::from_str(text)?;
+        for (name, binding) in table {
+            let Some(slot) = keymap.bindings.iter_mut().find(|(a, _)| a.name() == name) else {
+                bail!("unknown action '{}'", name);
+            };
+            let keys = match binding {
+                Binding::One(k) => vec![k],
+                Binding::Many(ks) => ks,
+            };
+            slot.1 = keys.iter().map(|k| parse_shortcut(k)).collect::<Result<_>>().with_context(|| format!("in '{}'", name))?;
+        }
+        if let Some((shortcut, a, b)) = keymap.conflict() {
+            bail!("'{}' is bound to both '{}' and '{}'", format_shortcut(&shortcut), a.name(), b.name());
+        }
+        Ok(keymap)
+    }
+
+    // a shortcut bound to two actions, only the first of which would ever
+    // see the key press
+    fn conflict(&self) -> Option<(KeyboardShortcut, Action, Action)> {
+        for (i, (a, keys)) in self.bindings.iter().enumerate() {
+            for (b, other) in &self.bindings[i + 1..] {
+                if let Some(k) = keys.iter().find(|k| other.contains(k)) {
+                    return Some((*k, *a, *b));
+                }
+            }
+        }
+        None
+    }
+
+    /// Loads `path`, first writing the defaults there if it doesn't exist so
+    /// there is a file to edit.
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            atomic::write(path, Self::default().to_toml())?;
+            return Ok(Self::default());
+        }
+        Self::parse(&fs::read_to_string(path)?).with_context(|| format!("reading {}", path.display()))
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut out = String::from(
+            "# Keyboard shortcuts of img-annotator: one key or a list of keys per action.\n\
+             # Modifiers are Ctrl (Cmd on macOS), Alt and Shift, e.g. \"Ctrl+Shift+Z\".\n\
+             # Actions left out keep their default keys; delete the file to restore all defaults.\n\n",
+        );
+        for (action, keys) in &self.bindings {
+            let keys: Vec<String> = keys.iter().map(|k| format!("\"{}\"", format_shortcut(k))).collect();
+            out += &format!("# {}\n{} = [{}]\n", action.description(), action.name(), keys.join(", "));
+        }
+        out
+    }
+
+    pub fn shortcuts(&self, action: Action) -> &[KeyboardShortcut] {
+        self.bindings.iter().find(|(a, _)| *a == action).map_or(&[], |(_, k)| k)
+    }
+
+    /// Whether a shortcut of `action` was pressed this frame; consumes the key press.
+    pub fn pressed(&self, input: &mut InputState, action: Action) -> bool {
+        let mut hit = false;
+        for s in self.shortcuts(action) {
+            hit |= input.consume_shortcut(s);
+        }
+        hit
+    }
+
+    /// Whether `shortcut` is one of the keys of `action`.
+    pub fn binds(&self, action: Action, shortcut: &KeyboardShortcut) -> bool {
+        self.shortcuts(action).contains(shortcut)
+    }
+}
@@ -0,0 +1,31 @@
+//! Headless annotation model shared by the GUI binary and scripts.
+//!
+//! Everything in here works without a window: reading and writing YOLO
+//! and Pascal VOC label files, the class list, the pose skeleton, the
+//! image folder scan and the keymap.
+
+pub mod atomic;
+pub mod backup;
+pub mod class_edit;
+pub mod classes;
+pub mod conflict;
+pub mod coco;
+pub mod dataset;
+pub mod history;
+pub mod keymap;
+pub mod model;
+pub mod render;
+pub mod settings;
+pub mod skeleton;
+pub mod split;
+pub mod stats;
+pub mod ultralytics;
+pub mod validate;
+pub mod voc;
+pub mod watch;
+pub mod yolo;
+
+pub use classes::ClassRegistry;
+pub use dataset::{Dataset, ImageEntry, LabelFormat, Layout};
+pub use model::{BBox, ImageAnnotations, Keypoint};
+pub use skeleton::Skeleton;
@@ -0,0 +1,148 @@
+//! Decodes images on worker threads and keeps the textures of the most
+//! recently used ones, so that moving to a neighboring image doesn't block
+//! the window.
+
+use anyhow::Result;
+use eframe::egui::{self, ColorImage, TextureHandle};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+// textures kept beyond the ones last requested, for going back and forth
+const KEEP_RECENT: usize = 4;
+
+#[derive(Default)]
+struct Jobs {
+    // most urgent first
+    queue: VecDeque<PathBuf>,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    jobs: Mutex<Jobs>,
+    ready: Condvar,
+}
+
+pub struct ImageLoader {
+    shared: Arc<Shared>,
+    results: Receiver<(PathBuf, Result<ColorImage>)>,
+    // queued or being decoded
+    pending: HashSet<PathBuf>,
+    // least recently used first
+    cache: VecDeque<(PathBuf, TextureHandle)>,
+    capacity: usize,
+    failed: HashMap<PathBuf, String>,
+}
+
+impl ImageLoader {
+    /// Starts the workers; they wake `ctx` when an image is decoded.
+    pub fn new(ctx: &egui::Context) -> Self {
+        let shared = Arc::new(Shared::default());
+        let (tx, results) = mpsc::channel();
+        let workers = thread::available_parallelism().map_or(2, |n| n.get()).clamp(1, 4);
+        for _ in 0..workers {
+            let (shared, tx, ctx) = (shared.clone(), tx.clone(), ctx.clone());
+            thread::spawn(move || loop {
+                let path = {
+                    let mut jobs = shared.jobs.lock().unwrap();
+                    while jobs.queue.is_empty() && !jobs.closed {
+                        jobs = shared.ready.wait(jobs).unwrap();
+                    }
+                    if jobs.closed {
+                        return;
+                    }
+                    jobs.queue.pop_front().unwrap()
+                };
+                let image = decode(&path);
+                if tx.send((path, image)).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            });
+        }
+        Self { shared, results, pending: HashSet::new(), cache: VecDeque::new(), capacity: KEEP_RECENT, failed: HashMap::new() }
+    }
+
+    /// Queues the images of `wanted` that aren't loaded yet, most urgent
+    /// first, dropping earlier requests no worker has started on.
+    pub fn request(&mut self, wanted: &[PathBuf]) {
+        self.capacity = wanted.len() + KEEP_RECENT;
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        for p in jobs.queue.drain(..) {
+            self.pending.remove(&p);
+        }
+        for p in wanted {
+            if !self.pending.contains(p) && !self.failed.contains_key(p) && !self.cache.iter().any(|(c, _)| c == p) {
+                jobs.queue.push_back(p.clone());
+                self.pending.insert(p.clone());
+            }
+        }
+        drop(jobs);
+        self.shared.ready.notify_all();
+        self.evict();
+    }
+
+    /// Turns decoded images into textures; call once a frame.
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        while let Ok((path, image)) = self.results.try_recv() {
+            // dropped by clear() while it was being decoded
+            if !self.pending.remove(&path) {
+                continue;
+            }
+            match image {
+                Ok(image) => {
+                    let tex = ctx.load_texture(path.to_string_lossy(), image, egui::TextureOptions::NEAREST);
+                    self.cache.push_back((path, tex));
+                }
+                Err(e) => {
+                    self.failed.insert(path, format!("{:#}", e));
+                }
+            }
+        }
+        self.evict();
+    }
+
+    /// The texture of `path` if it is decoded, marking it as just used.
+    pub fn get(&mut self, path: &Path) -> Option<TextureHandle> {
+        let i = self.cache.iter().position(|(p, _)| p == path)?;
+        let entry = self.cache.remove(i)?;
+        let tex = entry.1.clone();
+        self.cache.push_back(entry);
+        Some(tex)
+    }
+
+    /// Why `path` couldn't be decoded.
+    pub fn error(&self, path: &Path) -> Option<&str> {
+        self.failed.get(path).map(String::as_str)
+    }
+
+    /// Forgets all textures and errors, e.g. after the files changed on disk.
+    pub fn clear(&mut self) {
+        self.shared.jobs.lock().unwrap().queue.clear();
+        self.pending.clear();
+        self.cache.clear();
+        self.failed.clear();
+    }
+
+    fn evict(&mut self) {
+        while self.cache.len() > self.capacity {
+            self.cache.pop_front();
+        }
+    }
+}
+
+impl Drop for ImageLoader {
+    fn drop(&mut self) {
+        self.shared.jobs.lock().unwrap().closed = true;
+        self.shared.ready.notify_all();
+    }
+}
+
+fn decode(path: &Path) -> Result<ColorImage> {
+    let rgba = image::io::Reader::open(path)?.decode()?.to_rgba8();
+    let size = [rgba.width() as usize, rgba.height() as usize];
+    Ok(ColorImage::from_rgba_unmultiplied(size, rgba.as_raw()))
+}
@@ -1,260 +1,1605 @@
+mod cli;
+mod loader;
+
 use anyhow::Result;
 use eframe::{egui};
 use egui::{Color32, Pos2, Rect, Sense, Stroke, Vec2, Key};
-use glob::glob;
-use image::GenericImageView;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use img_annotator::classes::{DEFAULT_CLASS, IdMapping};
+use img_annotator::backup::{self, Retention};
+use img_annotator::class_edit::{self, ClassEdit};
+use img_annotator::conflict;
+use img_annotator::history::History;
+use img_annotator::keymap::{self, Action, Keymap};
+use img_annotator::render;
+use img_annotator::settings::{self, ProjectConfig, Settings};
+use img_annotator::stats::{self, Bin, Stats};
+use img_annotator::validate::{self, Issue, Severity};
+use img_annotator::watch::Watcher;
+use img_annotator::{BBox, ClassRegistry, Dataset, ImageAnnotations, Keypoint, LabelFormat, Layout, Skeleton, coco, ultralytics};
+use loader::ImageLoader;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ResizeHandle { TL, TR, BL, BR, Top, Bottom, Left, Right }
+
+impl ResizeHandle {
+    // whether dragging the handle moves a vertical / horizontal edge
+    fn moves_x(self) -> bool { !matches!(self, ResizeHandle::Top | ResizeHandle::Bottom) }
+    fn moves_y(self) -> bool { !matches!(self, ResizeHandle::Left | ResizeHandle::Right) }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DragMode { None, Creating, Selecting, Moving, Resizing(ResizeHandle), Vertex(usize), Rotating, Keypoint(usize) }
+
+const APP_TITLE: &str = "Rust Image Annotator";
+
+const LEGACY_OFFSET_HINT: &str = "The first class is 'object': if these labels were made before class id mappings, set the offset to 1 under Class ids in label files";
+
+// keys that aren't in the keymap, listed in the shortcuts window
+const FIXED_SHORTCUTS: [(&str, &str); 7] = [
+    ("Arrows", "Nudge the selected boxes by one image pixel (pan the image when none is selected)"),
+    ("Shift+Arrows", "Nudge the selected boxes by 10 pixels"),
+    ("Shift+Click / Shift+Drag", "Add boxes to the selection"),
+    ("Alt+Drag a handle", "Resize around the center"),
+    ("Enter / Backspace / Escape", "Close the polygon / remove its last vertex / cancel it"),
+    ("Escape", "Stop placing keypoints or drawing"),
+    ("Right click a vertex", "Delete it"),
+];
 
-#[derive(Clone, Debug)]
-struct BBox {
-    class_name: String,
-    cx: f32, // center x (ratio 0..1)
-    cy: f32, // center y (ratio)
-    w: f32,  // width (ratio)
-    h: f32,  // height (ratio)
+// kept versions of the open image's label file and of the class file, each
+// with a short summary, for the versions window
+struct VersionList {
+    label_file: PathBuf,
+    labels: Vec<(backup::Version, String)>,
+    classes: Vec<(backup::Version, String)>,
 }
 
-struct ImageEntry {
-    path: PathBuf,
+// the open image's label file was changed by another program while the
+// boxes in the window differ from it
+struct Conflict {
+    theirs: Vec<BBox>,
+    only_ours: Vec<BBox>,
+    only_theirs: Vec<BBox>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum ResizeCorner { TL, TR, BL, BR }
+enum Resolution { Ours, Theirs, Merge }
 
+// an applied class edit with the per-class settings it changed
+struct ClassChange {
+    applied: class_edit::Applied,
+    colors: BTreeMap<String, [u8; 3]>,
+    hidden: BTreeSet<String>,
+    locked: BTreeSet<String>,
+}
+
+// how the align buttons line up the selected boxes
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum DragMode { None, Creating, Moving, Resizing(ResizeCorner) }
+enum Align { Left, Center, Right, Top, Middle, Bottom }
+
+// what dragging / clicking on empty image space creates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ShapeMode { Box, Rotated, Polygon }
 
 struct AppState {
-    images: Vec<ImageEntry>,
+    dataset: Dataset,
     cur_idx: usize,
     texture: Option<egui::TextureHandle>,
+    // decodes the open image and its neighbors in the background; made with the first context
+    loader: Option<ImageLoader>,
     texture_size: Vec2, // displayed size in UI
     original_size: (u32, u32),
     dragging: bool,
     drag_start: Pos2,
     drag_end: Pos2,
     boxes: Vec<BBox>,
-    classes: Vec<String>,
+    // the open image's boxes as its label file had them when last read or
+    // written; what both sides of a conflict started from
+    disk_boxes: Vec<BBox>,
+    // watches the dataset folders for changes by other programs
+    watcher: Option<Watcher>,
+    conflict: Option<Conflict>,
     cur_class_idx: usize,
-    load_dir: PathBuf,
     selected_box: Option<usize>,
+    // every selected box; selected_box is the one with handles and is always part of it
+    selection: BTreeSet<usize>,
+    // boxes copied with Ctrl+C, pasted into any image with Ctrl+V
+    clipboard: Vec<BBox>,
+    // Next copies the previous image's boxes into an image that has none
+    auto_propagate: bool,
+    // edits are written on Save and navigation only
+    manual_save: bool,
+    // asking whether to quit with unsaved changes
+    confirm_quit: bool,
+    // the close was confirmed; let it through
+    quitting: bool,
+    window_title: String,
+    // per-class colors replacing the palette, and classes not drawn / not selectable;
+    // kept in the project file
+    class_colors: BTreeMap<String, [u8; 3]>,
+    hidden_classes: BTreeSet<String>,
+    locked_classes: BTreeSet<String>,
+    fill_opacity: f32,
+    prefetch: usize,
+    // how many earlier versions of each file are kept
+    retention: Retention,
+    show_versions: bool,
+    // listed when the versions window is open; dropped after every save
+    version_list: Option<VersionList>,
     // persistent text field for adding classes (was previously recreated every frame)
     new_class: String,
     drag_mode: DragMode,
     last_pointer_pos: Option<Pos2>,
-    // history stack for undo
-    history: Vec<Vec<BBox>>,
-    history_limit: usize,
+    shape_mode: ShapeMode,
+    // vertices (ratios) of the polygon being drawn, closed with Enter or by clicking the first one
+    draft_polygon: Vec<(f32, f32)>,
+    // keypoint of the selected box the next click places; advances through the skeleton
+    placing_keypoint: Option<usize>,
+    // skeleton editor inputs: new keypoint name and the pair picked for edges / flip pairs
+    new_keypoint: String,
+    keypoint_pair: (usize, usize),
+    // undo / redo per image, kept while browsing other images
+    histories: HashMap<PathBuf, History>,
+    show_history: bool,
+    // arrow keys are moving the selection; the whole nudge is one undo step
+    nudging: bool,
+    keymap: Keymap,
+    // keymap.toml in the user's config folder
+    keymap_path: Option<PathBuf>,
+    show_shortcuts: bool,
+    // settings.toml in the user's config folder, and the settings and project
+    // file as last read or written
+    settings_path: Option<PathBuf>,
+    settings: Settings,
+    project: ProjectConfig,
+    // the next press on the image starts a new shape even on top of a box
+    draw_armed: bool,
     // UI-adjustable settings
     click_tolerance: f32, // pixels; how close a click near the box counts as clicking it
     min_box_pixels: f32,  // min width or height in screen pixels to accept new box
     zoom_level: f32,
     zoom_center: Option<Pos2>,
-    pan_offset: Vec2
+    pan_offset: Vec2,
+    // path typed into the COCO menu
+    coco_path: String,
+    // result of the last import/export, shown in the top bar
+    status: String,
+    // file and time of the last successful write, and why the last one failed
+    // if it did; shown in the status bar
+    last_save: Option<(PathBuf, Instant)>,
+    save_error: Option<String>,
+    // result of the last validation, listed in the problems panel
+    problems: Vec<Issue>,
+    show_problems: bool,
+    // last computed dataset statistics, shown in the statistics window
+    stats: Option<Stats>,
+    show_stats: bool,
+    show_classes: bool,
+    // class manager: the selected class, the edit being previewed and the
+    // applied ones, newest last
+    class_sel: usize,
+    class_rename: String,
+    class_merge_into: usize,
+    class_plan: Option<class_edit::Plan>,
+    class_changes: Vec<ClassChange>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
-            images: vec![],
+            dataset: Dataset::default(),
             cur_idx: 0,
             texture: None,
+            loader: None,
             texture_size: Vec2::ZERO,
             original_size: (0, 0),
             dragging: false,
             drag_start: Pos2::ZERO,
             drag_end: Pos2::ZERO,
             boxes: vec![],
-            classes: vec!["object".to_owned()],
+            disk_boxes: vec![],
+            watcher: None,
+            conflict: None,
             cur_class_idx: 0,
-            load_dir: PathBuf::new(),
             selected_box: None,
+            selection: BTreeSet::new(),
+            clipboard: vec![],
+            auto_propagate: false,
+            manual_save: false,
+            confirm_quit: false,
+            quitting: false,
+            window_title: String::new(),
+            class_colors: BTreeMap::new(),
+            hidden_classes: BTreeSet::new(),
+            locked_classes: BTreeSet::new(),
+            fill_opacity: Settings::default().fill_opacity,
+            prefetch: Settings::default().prefetch,
+            retention: Retention::default(),
+            show_versions: false,
+            version_list: None,
             new_class: String::new(),
             drag_mode: DragMode::None,
             last_pointer_pos: None,
-            history: vec![],
-            history_limit: 200,
+            shape_mode: ShapeMode::Box,
+            draft_polygon: vec![],
+            placing_keypoint: None,
+            new_keypoint: String::new(),
+            keypoint_pair: (0, 0),
+            histories: HashMap::new(),
+            show_history: false,
+            nudging: false,
+            keymap: Keymap::default(),
+            keymap_path: None,
+            show_shortcuts: false,
+            settings_path: None,
+            settings: Settings::default(),
+            project: ProjectConfig::default(),
+            draw_armed: false,
             click_tolerance: 8.0,
             min_box_pixels: 6.0,
             zoom_level: 1.0,
             zoom_center: None,
             pan_offset: Vec2::ZERO,
+            coco_path: String::new(),
+            status: String::new(),
+            last_save: None,
+            save_error: None,
+            problems: vec![],
+            show_problems: false,
+            stats: None,
+            show_stats: false,
+            show_classes: false,
+            class_sel: 0,
+            class_rename: String::new(),
+            class_merge_into: 0,
+            class_plan: None,
+            class_changes: vec![],
+        }
+    }
+}
+
+impl AppState {
+    // starts an undoable edit of the current image's boxes; every change up to
+    // commit_edit becomes one history entry
+    fn begin_edit(&mut self) {
+        let Some(entry) = self.dataset.images.get(self.cur_idx) else { return };
+        self.histories.entry(entry.path.clone()).or_default().begin(&self.boxes);
+    }
+
+    // records the changes since begin_edit, if there are any, and saves them
+    // unless saving is manual
+    fn commit_edit(&mut self, label: &str) {
+        let Some(entry) = self.dataset.images.get(self.cur_idx) else { return };
+        if self.histories.get_mut(&entry.path).is_some_and(|h| h.commit(label, &self.boxes)) {
+            self.autosave();
+        }
+    }
+
+    fn autosave(&mut self) {
+        if !self.manual_save {
+            self.save_current();
+        }
+    }
+
+    // whether the open image's boxes differ from its label file
+    fn is_dirty(&self) -> bool {
+        !conflict::same_boxes(&self.boxes, &self.disk_boxes)
+    }
+
+    fn reload_keymap(&mut self) {
+        let Some(path) = &self.keymap_path else { return };
+        match Keymap::load_or_create(path) {
+            Ok(k) => self.keymap = k,
+            Err(e) => self.status = format!("Keymap not loaded: {:#}", e),
+        }
+    }
+
+    fn load_settings(&mut self) {
+        let Some(path) = &self.settings_path else { return };
+        match Settings::load(path) {
+            Ok(s) => {
+                self.click_tolerance = s.click_tolerance;
+                self.min_box_pixels = s.min_box_pixels;
+                self.auto_propagate = s.auto_propagate;
+                self.manual_save = s.manual_save;
+                self.fill_opacity = s.fill_opacity;
+                self.prefetch = s.prefetch;
+                self.retention = Retention { max_versions: s.keep_versions, max_age_days: s.keep_days };
+                self.settings = s;
+            }
+            Err(e) => self.status = format!("Settings not loaded: {:#}", e),
+        }
+    }
+
+    // resumes the open dataset where it was left: split, image, class and
+    // zoom, plus tolerances set in its project file
+    fn restore_project(&mut self, resume_split: bool) {
+        let project = match ProjectConfig::load(&self.dataset.project_file_path()) {
+            Ok(p) => p,
+            Err(e) => {
+                self.status = format!("Project settings not loaded: {:#}", e);
+                return;
+            }
+        };
+        if resume_split && let Some(split) = &project.split {
+            let known = matches!(&self.dataset.layout, Layout::Ultralytics { splits, .. } if splits.contains(split));
+            if known && let Err(e) = self.dataset.set_split(split) {
+                self.status = format!("Failed to open split: {:#}", e);
+            }
+        }
+        if let Some(name) = &project.image
+            && let Some(i) = self.dataset.images.iter().position(|e| e.path.file_name().is_some_and(|f| f == name.as_str()))
+        {
+            self.cur_idx = i;
+        }
+        if let Some(id) = project.class.as_deref().and_then(|c| self.dataset.classes.id_of(c)) {
+            self.cur_class_idx = id;
+        }
+        if let Some(z) = project.zoom_level { self.zoom_level = z; }
+        if let Some(t) = project.click_tolerance { self.click_tolerance = t; }
+        if let Some(p) = project.min_box_pixels { self.min_box_pixels = p; }
+        self.class_colors = project.colors.clone();
+        self.hidden_classes = project.hidden.clone();
+        self.locked_classes = project.locked.clone();
+        self.project = project;
+    }
+
+    // writes the user settings and the project file if they changed
+    fn remember_settings(&mut self) {
+        let mut settings = Settings {
+            click_tolerance: self.click_tolerance,
+            min_box_pixels: self.min_box_pixels,
+            auto_propagate: self.auto_propagate,
+            manual_save: self.manual_save,
+            fill_opacity: self.fill_opacity,
+            prefetch: self.prefetch,
+            keep_versions: self.retention.max_versions,
+            keep_days: self.retention.max_age_days,
+            last_folder: self.settings.last_folder.clone(),
+        };
+        if !self.dataset.root.as_os_str().is_empty() {
+            let mut project = self.project.clone();
+            // tolerances the project file sets are kept there
+            if project.click_tolerance.is_some() {
+                project.click_tolerance = Some(self.click_tolerance);
+                settings.click_tolerance = self.settings.click_tolerance;
+            }
+            if project.min_box_pixels.is_some() {
+                project.min_box_pixels = Some(self.min_box_pixels);
+                settings.min_box_pixels = self.settings.min_box_pixels;
+            }
+            project.image = self.dataset.images.get(self.cur_idx)
+                .and_then(|e| e.path.file_name())
+                .map(|f| f.to_string_lossy().into_owned());
+            project.split = match &self.dataset.layout {
+                Layout::Ultralytics { split, .. } => Some(split.clone()),
+                Layout::Flat => None,
+            };
+            project.class = self.dataset.classes.name(self.cur_class_idx).map(str::to_owned);
+            project.zoom_level = Some(self.zoom_level);
+            project.colors = self.class_colors.clone();
+            project.hidden = self.hidden_classes.clone();
+            project.locked = self.locked_classes.clone();
+            project.obb = self.dataset.obb;
+            if project != self.project {
+                match project.save(&self.dataset.project_file_path()) {
+                    Ok(()) => self.project = project,
+                    Err(e) => self.status = format!("Project settings not saved: {:#}", e),
+                }
+            }
+            settings.last_folder = Some(self.dataset.root.clone());
+        }
+        if let Some(path) = &self.settings_path && settings != self.settings {
+            match settings.save(path) {
+                Ok(()) => self.settings = settings,
+                Err(e) => self.status = format!("Settings not saved: {:#}", e),
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: Action, ctx: &egui::Context) {
+        match action {
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Copy => self.copy_selection(),
+            Action::Paste => self.paste(),
+            Action::CopyPrevious => self.copy_from_previous(),
+            Action::SelectAll => {
+                self.selection = (0..self.boxes.len()).filter(|i| self.box_pickable(*i)).collect();
+                self.selected_box = self.selection.last().copied();
+            }
+            Action::Delete => self.delete_selection(),
+            Action::PrevImage => self.prev_image(ctx),
+            Action::NextImage => self.next_image(ctx),
+            Action::Save => { self.save_current(); }
+            Action::Draw => {
+                self.select(None);
+                self.placing_keypoint = None;
+                self.draw_armed = true;
+            }
+            Action::NextBox => self.cycle_box(true),
+            Action::PrevBox => self.cycle_box(false),
+            Action::Shortcuts => self.show_shortcuts = !self.show_shortcuts,
+            Action::Class(n) => {
+                if n < self.dataset.classes.len() { self.cur_class_idx = n; }
+            }
+        }
+    }
+
+    fn prev_image(&mut self, ctx: &egui::Context) {
+        // a failed save keeps the image open so the edits aren't lost
+        if self.dataset.images.is_empty() || !self.save_current() { return; }
+        if self.cur_idx == 0 { self.cur_idx = self.dataset.images.len() - 1; }
+        else { self.cur_idx -= 1; }
+        let _ = self.load_current_image_texture(ctx);
+    }
+
+    fn next_image(&mut self, ctx: &egui::Context) {
+        if self.dataset.images.is_empty() || !self.save_current() { return; }
+        self.cur_idx = (self.cur_idx + 1) % self.dataset.images.len();
+        let _ = self.load_current_image_texture(ctx);
+        if self.auto_propagate { self.copy_from_previous(); }
+    }
+
+    // selects the box after (or before) the selected one, wrapping around
+    fn cycle_box(&mut self, forward: bool) {
+        let n = self.boxes.len();
+        if n == 0 { return; }
+        let mut next = match self.selected_box {
+            Some(i) if forward => (i + 1) % n,
+            Some(i) => (i + n - 1) % n,
+            None if forward => 0,
+            None => n - 1,
+        };
+        // skip boxes of hidden and locked classes
+        for _ in 0..n {
+            if self.box_pickable(next) {
+                self.select(Some(next));
+                return;
+            }
+            next = if forward { (next + 1) % n } else { (next + n - 1) % n };
+        }
+    }
+
+    fn shortcuts_ui(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("shortcuts_grid").striped(true).show(ui, |ui| {
+            for action in Action::ALL {
+                let what = match action {
+                    Action::Class(n) => match self.dataset.classes.name(n) {
+                        Some(name) => format!("Use class '{}' for new boxes", name),
+                        None => continue,
+                    },
+                    _ => action.description(),
+                };
+                let keys: Vec<String> = self.keymap.shortcuts(action).iter().map(|s| ui.ctx().format_shortcut(s)).collect();
+                ui.label(keys.join(", "));
+                ui.label(what);
+                ui.end_row();
+            }
+            for (keys, what) in FIXED_SHORTCUTS {
+                ui.label(keys);
+                ui.label(what);
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        if let Some(path) = &self.keymap_path {
+            ui.label(format!("Edit {} to change the shortcuts.", path.display()));
+        }
+        if ui.button("Reload keymap").clicked() { self.reload_keymap(); }
+    }
+
+    fn select(&mut self, idx: Option<usize>) {
+        self.selected_box = idx;
+        self.selection = idx.into_iter().collect();
+    }
+
+    // shift-click: add the box to the selection or take it out again
+    fn toggle_selected(&mut self, idx: usize) {
+        if self.selection.remove(&idx) {
+            if self.selected_box == Some(idx) {
+                self.selected_box = self.selection.last().copied();
+            }
+        } else {
+            self.selection.insert(idx);
+            self.selected_box = Some(idx);
+        }
+    }
+
+    // topmost box under the pointer, preferring the selected one
+    fn box_at(&self, pos: Pos2, img_rect: &Rect) -> Option<usize> {
+        let tol = self.click_tolerance;
+        let hit = |i: &usize| self.box_pickable(*i) && clicked_in_a_box(&self.boxes[*i], img_rect, &pos, &tol);
+        self.selected_box.filter(|i| *i < self.boxes.len() && hit(i))
+            .or_else(|| (0..self.boxes.len()).rev().find(hit))
+    }
+
+    // override from the project file, else the palette color of the class's place in the list
+    fn class_color(&self, name: &str) -> Color32 {
+        let [r, g, b] = self.class_colors.get(name).copied()
+            .unwrap_or_else(|| render::class_color(self.dataset.classes.id_of(name).unwrap_or(0)));
+        Color32::from_rgb(r, g, b)
+    }
+
+    fn box_visible(&self, i: usize) -> bool {
+        self.boxes.get(i).is_some_and(|b| !self.hidden_classes.contains(&b.class_name))
+    }
+
+    // visible and not locked
+    fn box_pickable(&self, i: usize) -> bool {
+        self.box_visible(i) && !self.locked_classes.contains(&self.boxes[i].class_name)
+    }
+
+    // color, show and lock toggles and box count of every class
+    fn legend_ui(&mut self, ui: &mut egui::Ui) {
+        let mut toggled = false;
+        egui::Grid::new("legend_grid").show(ui, |ui| {
+            ui.label("");
+            ui.label("Class");
+            ui.label("Show");
+            ui.label("Lock");
+            ui.end_row();
+            for (id, name) in self.dataset.classes.iter().enumerate() {
+                let mut rgb = self.class_colors.get(name).copied().unwrap_or_else(|| render::class_color(id));
+                if ui.color_edit_button_srgb(&mut rgb).changed() {
+                    self.class_colors.insert(name.clone(), rgb);
+                }
+                let count = self.boxes.iter().filter(|b| &b.class_name == name).count();
+                ui.label(format!("{} ({})", name, count));
+                let mut shown = !self.hidden_classes.contains(name);
+                if ui.checkbox(&mut shown, "").changed() {
+                    if shown { self.hidden_classes.remove(name); } else { self.hidden_classes.insert(name.clone()); }
+                    toggled = true;
+                }
+                let mut locked = self.locked_classes.contains(name);
+                if ui.checkbox(&mut locked, "").changed() {
+                    if locked { self.locked_classes.insert(name.clone()); } else { self.locked_classes.remove(name); }
+                    toggled = true;
+                }
+                if self.class_colors.contains_key(name) && ui.small_button("Reset").on_hover_text("Back to the palette color").clicked() {
+                    self.class_colors.remove(name);
+                }
+                ui.end_row();
+            }
+        });
+        if toggled {
+            // hidden and locked boxes drop out of the selection
+            let keep: BTreeSet<usize> = self.selection.iter().copied().filter(|i| self.box_pickable(*i)).collect();
+            self.selected_box = self.selected_box.filter(|i| keep.contains(i)).or_else(|| keep.last().copied());
+            self.selection = keep;
+        }
+    }
+
+    // moves every selected box by the same offset, limited so that polygons
+    // stay inside the image and plain boxes keep their center inside it
+    fn move_selection(&mut self, dx: f32, dy: f32) {
+        let (mut x0, mut x1, mut y0, mut y1) = (f32::MIN, f32::MAX, f32::MIN, f32::MAX);
+        for b in self.selection.iter().filter_map(|i| self.boxes.get(*i)) {
+            let (l, r, t, bt) = if b.is_polygon() { (b.left(), b.right(), b.top(), b.bottom()) } else { (b.cx, b.cx, b.cy, b.cy) };
+            x0 = x0.max(-l.max(0.0));
+            x1 = x1.min((1.0 - r).max(0.0));
+            y0 = y0.max(-t.max(0.0));
+            y1 = y1.min((1.0 - bt).max(0.0));
+        }
+        let (dx, dy) = (dx.clamp(x0, x1), dy.clamp(y0, y1));
+        for i in &self.selection {
+            if let Some(b) = self.boxes.get_mut(*i) {
+                b.translate(dx, dy);
+            }
+        }
+    }
+
+    fn delete_selection(&mut self) {
+        if self.selection.is_empty() { return; }
+        self.begin_edit();
+        for i in self.selection.iter().rev() {
+            if *i < self.boxes.len() {
+                self.boxes.remove(*i);
+            }
+        }
+        self.commit_edit(if self.selection.len() > 1 { "Delete boxes" } else { "Delete box" });
+        self.select(None);
+    }
+
+    // copies end up on top of the originals and become the selection
+    fn duplicate_selection(&mut self) {
+        let copies: Vec<BBox> = self.selection.iter().filter_map(|i| self.boxes.get(*i)).cloned().collect();
+        if copies.is_empty() { return; }
+        let start = self.boxes.len();
+        self.begin_edit();
+        self.boxes.extend(copies);
+        self.commit_edit(if self.boxes.len() - start > 1 { "Duplicate boxes" } else { "Duplicate box" });
+        self.selection = (start..self.boxes.len()).collect();
+        self.selected_box = Some(self.boxes.len() - 1);
+    }
+
+    fn copy_selection(&mut self) {
+        let copied: Vec<BBox> = self.selection.iter().filter_map(|i| self.boxes.get(*i)).cloned().collect();
+        if copied.is_empty() { return; }
+        self.status = format!("Copied {} boxes", copied.len());
+        self.clipboard = copied;
+    }
+
+    // pasted boxes keep their ratio coordinates and become the selection
+    fn paste(&mut self) {
+        if self.clipboard.is_empty() || self.dataset.images.is_empty() { return; }
+        let start = self.boxes.len();
+        self.begin_edit();
+        self.boxes.extend(self.clipboard.iter().cloned());
+        self.commit_edit(if self.clipboard.len() > 1 { "Paste boxes" } else { "Paste box" });
+        self.selection = (start..self.boxes.len()).collect();
+        self.selected_box = Some(self.boxes.len() - 1);
+    }
+
+    // copies all boxes of the image before this one, into an image without labels only
+    fn copy_from_previous(&mut self) {
+        if self.cur_idx == 0 || self.cur_idx >= self.dataset.images.len() || !self.boxes.is_empty() { return; }
+        let prev = self.dataset.images[self.cur_idx - 1].path.clone();
+        match self.dataset.load_annotations(&prev) {
+            Ok(ann) if ann.boxes.is_empty() => self.status = "The previous image has no boxes".to_owned(),
+            Ok(ann) => {
+                self.status = format!("Copied {} boxes from the previous image", ann.boxes.len());
+                self.begin_edit();
+                self.boxes = ann.boxes;
+                self.commit_edit("Copy from previous image");
+            }
+            Err(e) => self.status = format!("Reading the previous image's labels failed: {:#}", e),
+        }
+    }
+
+    // lines the selected boxes up with the edge or center of their common bounds
+    fn align_selection(&mut self, align: Align) {
+        let sel: Vec<usize> = self.selection.iter().copied().filter(|i| *i < self.boxes.len()).collect();
+        if sel.len() < 2 { return; }
+        let (mut left, mut top, mut right, mut bottom) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for b in sel.iter().map(|i| &self.boxes[*i]) {
+            left = left.min(b.left());
+            top = top.min(b.top());
+            right = right.max(b.right());
+            bottom = bottom.max(b.bottom());
+        }
+        self.begin_edit();
+        for i in sel {
+            let b = &mut self.boxes[i];
+            let (dx, dy) = match align {
+                Align::Left => (left - b.left(), 0.0),
+                Align::Center => ((left + right) / 2.0 - b.cx, 0.0),
+                Align::Right => (right - b.right(), 0.0),
+                Align::Top => (0.0, top - b.top()),
+                Align::Middle => (0.0, (top + bottom) / 2.0 - b.cy),
+                Align::Bottom => (0.0, bottom - b.bottom()),
+            };
+            b.translate(dx, dy);
+        }
+        self.commit_edit("Align boxes");
+    }
+
+    fn undo(&mut self) {
+        self.travel(|h, boxes| h.undo(boxes));
+    }
+
+    fn redo(&mut self) {
+        self.travel(|h, boxes| h.redo(boxes));
+    }
+
+    fn travel(&mut self, step: impl FnOnce(&mut History, &mut Vec<BBox>) -> bool) {
+        let Some(entry) = self.dataset.images.get(self.cur_idx) else { return };
+        let Some(history) = self.histories.get_mut(&entry.path) else { return };
+        if step(history, &mut self.boxes) {
+            self.select(None);
+            self.placing_keypoint = None;
+            self.autosave();
+        }
+    }
+
+    fn history_ui(&mut self, ui: &mut egui::Ui) {
+        let path = self.dataset.images.get(self.cur_idx).map(|e| e.path.clone());
+        let history = path.as_ref().and_then(|p| self.histories.get(p));
+        let (can_undo, can_redo) = (history.is_some_and(|h| h.can_undo()), history.is_some_and(|h| h.can_redo()));
+        ui.horizontal(|ui| {
+            if ui.add_enabled(can_undo, egui::Button::new("Undo")).clicked() { self.undo(); }
+            if ui.add_enabled(can_redo, egui::Button::new("Redo")).clicked() { self.redo(); }
+        });
+        ui.separator();
+        let Some(history) = path.as_ref().and_then(|p| self.histories.get(p)) else {
+            ui.label("No edits to this image yet.");
+            return;
+        };
+        // clicking an entry undoes or redoes up to and including it
+        let pos = history.position();
+        let mut target = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            if ui.selectable_label(pos == 0, "Start").clicked() { target = Some(0); }
+            for (i, label) in history.labels().enumerate() {
+                let text = if i < pos { egui::RichText::new(label) } else { egui::RichText::new(label).weak() };
+                if ui.selectable_label(i + 1 == pos, text).clicked() { target = Some(i + 1); }
+            }
+        });
+        if let Some(t) = target {
+            self.travel(|h, boxes| {
+                let moved = h.position() != t;
+                h.go_to(t, boxes);
+                moved
+            });
+        }
+    }
+
+    fn load_current_image_texture(&mut self, ctx: &egui::Context) -> Result<()> {
+        self.texture = None;
+        self.select(None);
+        self.drag_mode = DragMode::None;
+        self.last_pointer_pos = None;
+        self.draft_polygon.clear();
+        self.placing_keypoint = None;
+        self.version_list = None;
+        self.conflict = None;
+        // every image change is a point to resume from
+        self.remember_settings();
+        if self.dataset.images.is_empty() {
+            return Ok(());
+        }
+        self.load_annotations_for_current();
+        self.request_images(ctx);
+        // the size is read from the header, the pixels are decoded in the background
+        match image::image_dimensions(&self.dataset.images[self.cur_idx].path) {
+            Ok(size) => self.original_size = size,
+            Err(e) => {
+                self.original_size = (0, 0);
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    // queues the open image, then `prefetch` neighbors on each side
+    fn request_images(&mut self, ctx: &egui::Context) {
+        if self.dataset.images.is_empty() { return; }
+        let n = self.dataset.images.len();
+        let mut wanted: Vec<PathBuf> = vec![];
+        for d in 0..=self.prefetch.min(n) {
+            for i in [(self.cur_idx + d) % n, (self.cur_idx + n - d % n) % n] {
+                let p = &self.dataset.images[i].path;
+                if !wanted.contains(p) { wanted.push(p.clone()); }
+            }
+        }
+        self.loader.get_or_insert_with(|| ImageLoader::new(ctx)).request(&wanted);
+    }
+
+    fn load_annotations_for_current(&mut self) {
+        self.boxes.clear();
+        self.disk_boxes.clear();
+        if self.dataset.images.is_empty() {
+            return;
+        }
+        let imgp = self.dataset.images[self.cur_idx].path.clone();
+        match self.dataset.load_annotations(&imgp) {
+            Ok(ann) => self.boxes = ann.boxes,
+            Err(e) => self.status = format!("Failed to load annotations for {}: {:#}", imgp.display(), e),
+        }
+        self.disk_boxes = self.boxes.clone();
+    }
+
+    fn save_annotations_for_current(&mut self) -> Result<()> {
+        if self.dataset.images.is_empty() { return Ok(()); }
+        let ann = ImageAnnotations {
+            image: self.dataset.images[self.cur_idx].path.clone(),
+            boxes: self.boxes.clone(),
+            size: (self.original_size.0 > 0).then_some(self.original_size),
+        };
+        self.dataset.save_annotations(&ann)
+    }
+
+    // saves the open image, noting the outcome for the status bar; false if
+    // the labels couldn't be written
+    fn save_current(&mut self) -> bool {
+        let Some(entry) = self.dataset.images.get(self.cur_idx) else { return true };
+        let path = self.dataset.annotation_path(&entry.path, self.dataset.format);
+        if self.conflict.is_some() {
+            self.save_error = Some(format!("{} was changed on disk; choose which version to keep first", path.display()));
+            return false;
+        }
+        let result = self.save_annotations_for_current();
+        let saved = self.note_save(path, result);
+        if saved {
+            self.disk_boxes = self.boxes.clone();
+        }
+        saved
+    }
+
+    // the open image's boxes as its label file has them now
+    fn read_current_from_disk(&self) -> Result<Vec<BBox>> {
+        let Some(entry) = self.dataset.images.get(self.cur_idx) else { return Ok(vec![]) };
+        let mut classes = self.dataset.classes.clone();
+        Ok(self.dataset.read_annotations(&entry.path, &mut classes)?.boxes)
+    }
+
+    // reacts to files changed by other programs: added or deleted images and a
+    // changed class file reload the folder, a changed label file of the open
+    // image is checked for a conflict
+    fn check_files(&mut self, ctx: &egui::Context) {
+        if self.dataset.root.as_os_str().is_empty() { return; }
+        let dirs = self.dataset.watched_dirs();
+        if self.watcher.as_ref().is_none_or(|w| w.dirs() != dirs) {
+            let repaint = ctx.clone();
+            self.watcher = Some(Watcher::new(dirs, Duration::from_secs(1), move || repaint.request_repaint()));
+            return;
+        }
+        let Some(changes) = self.watcher.as_ref().map(Watcher::poll).filter(|c| !c.is_empty()) else { return };
+        let is_image = |p: &&PathBuf| Dataset::is_image_file(p);
+        let dataset_files = [self.dataset.classes_file_path(), self.dataset.id_map_file_path(), self.dataset.skeleton_file_path()];
+        if changes.added.iter().chain(&changes.removed).any(|p| is_image(&p)) || changes.paths().any(|p| dataset_files.contains(p)) {
+            self.reload_folder(ctx);
+        }
+        if changes.modified.iter().any(|p| is_image(&p)) {
+            // pixels changed: decode again
+            if let Some(l) = &mut self.loader { l.clear(); }
+            self.texture = None;
+            self.request_images(ctx);
+        }
+        // undo steps of other images no longer fit their label files
+        let format = self.dataset.format;
+        let changed: Vec<&PathBuf> = changes.paths().collect();
+        let current = self.dataset.images.get(self.cur_idx).map(|e| e.path.clone());
+        self.histories.retain(|img, _| Some(img) == current.as_ref() || !changed.contains(&&self.dataset.annotation_path(img, format)));
+        if let Some(img) = current && changed.contains(&&self.dataset.annotation_path(&img, format)) {
+            self.check_current_labels();
+        }
+    }
+
+    // re-scans the folder, staying on the open image unless it was deleted
+    fn reload_folder(&mut self, ctx: &egui::Context) {
+        let current = self.dataset.images.get(self.cur_idx).map(|e| e.path.clone());
+        let classes = self.dataset.classes.clone();
+        if let Err(e) = self.dataset.reload() {
+            self.status = format!("Failed to reload folder: {:#}", e);
+            return;
+        }
+        if self.dataset.classes != classes {
+            self.status = "The class list was changed on disk and has been reloaded".to_owned();
+        }
+        match current.and_then(|c| self.dataset.images.iter().position(|e| e.path == c)) {
+            Some(i) => self.cur_idx = i,
+            None => {
+                self.cur_idx = self.cur_idx.min(self.dataset.images.len().saturating_sub(1));
+                self.histories.retain(|img, _| img.exists());
+                let _ = self.load_current_image_texture(ctx);
+            }
+        }
+    }
+
+    // another program wrote the open image's label file: take it if the boxes
+    // here haven't changed since, otherwise ask which version to keep
+    fn check_current_labels(&mut self) {
+        let Ok(theirs) = self.read_current_from_disk() else { return };
+        // our own write
+        if conflict::same_boxes(&theirs, &self.disk_boxes) { return; }
+        if conflict::same_boxes(&self.boxes, &self.disk_boxes) || conflict::same_boxes(&self.boxes, &theirs) {
+            self.begin_edit();
+            self.boxes = theirs.clone();
+            self.select(None);
+            self.disk_boxes = theirs;
+            if let Some(entry) = self.dataset.images.get(self.cur_idx) {
+                self.histories.entry(entry.path.clone()).or_default().commit("Changed on disk", &self.boxes);
+            }
+            return;
+        }
+        self.conflict = Some(Conflict {
+            only_ours: conflict::missing_from(&self.boxes, &theirs),
+            only_theirs: conflict::missing_from(&theirs, &self.boxes),
+            theirs,
+        });
+    }
+
+    fn resolve_conflict(&mut self, choice: Resolution) {
+        let Some(c) = self.conflict.take() else { return };
+        // the file now holds their version
+        let base = std::mem::replace(&mut self.disk_boxes, c.theirs.clone());
+        let (label, boxes) = match choice {
+            Resolution::Ours => {
+                self.save_current();
+                return;
+            }
+            Resolution::Theirs => ("Take changes from disk", c.theirs),
+            Resolution::Merge => ("Merge changes from disk", conflict::merge(&base, &self.boxes, &c.theirs)),
+        };
+        self.begin_edit();
+        self.boxes = boxes;
+        self.select(None);
+        self.commit_edit(label);
+    }
+
+    fn conflict_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(c) = &self.conflict else { return };
+        let file = self.dataset.images.get(self.cur_idx)
+            .map(|e| self.dataset.annotation_path(&e.path, self.dataset.format))
+            .unwrap_or_default();
+        ui.label(format!("{} was changed by another program.", file.display()));
+        let describe = |b: &BBox| format!("{}  ({:.3}, {:.3})  {:.3} x {:.3}", b.class_name, b.cx, b.cy, b.w, b.h);
+        ui.columns(2, |cols| {
+            cols[0].strong(format!("Only here ({})", c.only_ours.len()));
+            for b in &c.only_ours { cols[0].colored_label(Color32::from_rgb(120, 200, 120), describe(b)); }
+            cols[1].strong(format!("Only on disk ({})", c.only_theirs.len()));
+            for b in &c.only_theirs { cols[1].colored_label(Color32::from_rgb(230, 190, 60), describe(b)); }
+        });
+        ui.separator();
+        let mut choice = None;
+        ui.horizontal(|ui| {
+            if ui.button("Keep mine").on_hover_text("Overwrite the file with the boxes shown here").clicked() { choice = Some(Resolution::Ours); }
+            if ui.button("Take theirs").on_hover_text("Replace the boxes here with the file's").clicked() { choice = Some(Resolution::Theirs); }
+            if ui.button("Merge").on_hover_text("Keep the boxes here, drop the ones the other program deleted and add the ones it added").clicked() { choice = Some(Resolution::Merge); }
+        });
+        ui.label("The version not kept can still be restored from Versions.");
+        if let Some(choice) = choice { self.resolve_conflict(choice); }
+    }
+
+    // closes the window, first asking if the open image has unsaved changes
+    fn quit(&mut self, ctx: &egui::Context) {
+        if self.is_dirty() {
+            self.confirm_quit = true;
+            return;
+        }
+        self.quitting = true;
+        self.remember_settings();
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+
+    fn confirm_quit_ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let name = self.dataset.images.get(self.cur_idx)
+            .and_then(|e| e.path.file_name())
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        ui.label(format!("The labels of {} have unsaved changes.", name));
+        ui.horizontal(|ui| {
+            if ui.button("Save and quit").clicked() {
+                self.confirm_quit = false;
+                // a failed save stays open, with the error in the status bar
+                if self.save_current() { self.quit(ctx); }
+            }
+            if ui.button("Quit without saving").clicked() {
+                self.confirm_quit = false;
+                self.quitting = true;
+                self.remember_settings();
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            if ui.button("Cancel").clicked() {
+                self.confirm_quit = false;
+            }
+        });
+    }
+
+    // "image.jpg * - Rust Image Annotator" while the open image has unsaved changes
+    fn update_title(&mut self, ctx: &egui::Context) {
+        let mut title = APP_TITLE.to_owned();
+        if let Some(name) = self.dataset.images.get(self.cur_idx).and_then(|e| e.path.file_name()) {
+            let mark = if self.is_dirty() { " *" } else { "" };
+            title = format!("{}{} - {}", name.to_string_lossy(), mark, APP_TITLE);
+        }
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+
+    fn note_save(&mut self, path: PathBuf, result: Result<()>) -> bool {
+        match result {
+            Ok(()) => {
+                if let Some(w) = &self.watcher { w.acknowledge(&path); }
+                self.last_save = Some((path, Instant::now()));
+                self.save_error = None;
+                self.version_list = None;
+                true
+            }
+            Err(e) => {
+                self.save_error = Some(format!("{:#}", e));
+                false
+            }
+        }
+    }
+
+    fn status_bar_ui(&self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if self.is_dirty() {
+                ui.colored_label(Color32::from_rgb(230, 190, 60), "Unsaved changes");
+                ui.separator();
+            }
+            if let Some(e) = &self.save_error {
+                ui.colored_label(Color32::from_rgb(255, 90, 90), format!("Save failed: {}", e));
+            } else if let Some((path, at)) = &self.last_save {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                ui.label(format!("Saved {} {} ago", name, format_age(at.elapsed())));
+                // keep the age current
+                ui.ctx().request_repaint_after(Duration::from_secs(1));
+            } else {
+                ui.label("Nothing saved yet");
+            }
+        });
+    }
+
+    fn list_versions(&self, image: &Path, label_file: PathBuf) -> VersionList {
+        let root = &self.dataset.root;
+        let labels = backup::versions(root, &label_file).unwrap_or_default().into_iter().map(|v| {
+            let mut classes = self.dataset.classes.clone();
+            let summary = match self.dataset.read_label_file(image, &v.path, self.dataset.format, &mut classes) {
+                Ok(ann) => format!("{} boxes", ann.boxes.len()),
+                Err(_) => "unreadable".to_owned(),
+            };
+            (v, summary)
+        }).collect();
+        let classes = backup::versions(root, &self.dataset.classes_file_path()).unwrap_or_default().into_iter().map(|v| {
+            let read = match self.dataset.layout {
+                Layout::Flat => ClassRegistry::load(&v.path),
+                Layout::Ultralytics { .. } => ultralytics::read_classes(&v.path),
+            };
+            let summary = match read {
+                Ok(c) => format!("{} classes", c.len()),
+                Err(_) => "unreadable".to_owned(),
+            };
+            (v, summary)
+        }).collect();
+        VersionList { label_file, labels, classes }
+    }
+
+    // earlier versions of the open image's labels and of the class file, each
+    // with a button to go back to it
+    fn versions_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(image) = self.dataset.images.get(self.cur_idx).map(|e| e.path.clone()) else {
+            ui.label("No image open.");
+            return;
+        };
+        let label_file = self.dataset.annotation_path(&image, self.dataset.format);
+        if self.version_list.as_ref().is_none_or(|l| l.label_file != label_file) {
+            self.version_list = Some(self.list_versions(&image, label_file));
+        }
+        let Some(list) = &self.version_list else { return };
+        // (class file?, version) picked this frame
+        let mut revert: Option<(bool, backup::Version)> = None;
+        for (is_classes, title, versions) in [(false, &list.label_file, &list.labels), (true, &self.dataset.classes_file_path(), &list.classes)] {
+            ui.strong(title.file_name().unwrap_or_default().to_string_lossy());
+            if versions.is_empty() {
+                ui.label("No earlier versions kept.");
+            }
+            egui::Grid::new(("versions_grid", is_classes)).striped(true).show(ui, |ui| {
+                for (v, summary) in versions {
+                    ui.label(format!("{} ago", format_age(v.age())));
+                    ui.label(summary);
+                    if ui.button("Revert").clicked() {
+                        revert = Some((is_classes, v.clone()));
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+        }
+        ui.label(format!("Kept in {}", self.dataset.root.join(backup::HISTORY_DIR).display()));
+        match revert {
+            Some((false, v)) => self.revert_labels(&image, &v),
+            Some((true, v)) => self.revert_classes(&v),
+            None => {}
+        }
+    }
+
+    // takes the boxes of a kept version as one undoable edit; saving it keeps
+    // the current labels as a version in turn
+    fn revert_labels(&mut self, image: &Path, version: &backup::Version) {
+        let mut classes = self.dataset.classes.clone();
+        match self.dataset.read_label_file(image, &version.path, self.dataset.format, &mut classes) {
+            Ok(ann) => {
+                if classes != self.dataset.classes {
+                    self.dataset.classes = classes;
+                    let result = self.dataset.save_classes_file();
+                    self.note_save(self.dataset.classes_file_path(), result);
+                }
+                self.begin_edit();
+                self.boxes = ann.boxes;
+                self.select(None);
+                self.commit_edit("Revert to earlier version");
+            }
+            Err(e) => self.status = format!("Version not readable: {:#}", e),
+        }
+    }
+
+    // puts back a kept class file and re-reads the open image's labels with it
+    fn revert_classes(&mut self, version: &backup::Version) {
+        let path = self.dataset.classes_file_path();
+        let result = backup::restore(&self.dataset.root, &path, version, &self.retention)
+            .and_then(|()| self.dataset.load_classes_file());
+        if self.note_save(path, result) {
+            self.load_annotations_for_current();
+        }
+    }
+
+    // Changing the mapping changes how the label files on disk are read, so the
+    // current image is saved under the old mapping and re-read under the new one.
+    fn apply_id_mapping(&mut self, mapping: IdMapping) {
+        if !self.save_current() { return; }
+        self.dataset.classes.set_mapping(mapping);
+        let result = self.dataset.save_classes_file();
+        self.note_save(self.dataset.classes_file_path(), result);
+        self.load_annotations_for_current();
+    }
+
+    fn id_mapping_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Class ids in label files:");
+        let mut mapping = self.dataset.classes.mapping().clone();
+        let mut is_table = matches!(mapping, IdMapping::Table(_));
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut is_table, false, "Offset");
+            ui.radio_value(&mut is_table, true, "Table");
+        });
+        if is_table != matches!(mapping, IdMapping::Table(_)) {
+            mapping = if is_table {
+                // start from the ids the classes currently have
+                let classes = &self.dataset.classes;
+                IdMapping::Table(classes.iter().filter_map(|c| Some((classes.file_id(c)?, c.clone()))).collect())
+            } else {
+                IdMapping::Offset(0)
+            };
+        }
+        match &mut mapping {
+            IdMapping::Offset(k) => {
+                let max = self.dataset.classes.len().saturating_sub(1);
+                ui.add(egui::DragValue::new(k).clamp_range(0..=max).prefix("offset: "));
+                ui.label("id = position in class list - offset");
+            }
+            IdMapping::Table(t) => {
+                let mut rows: Vec<(usize, String)> = t.iter().map(|(id, n)| (*id, n.clone())).collect();
+                let mut edit = None;
+                for (i, (id, name)) in rows.iter().enumerate() {
+                    let mut v = *id;
+                    ui.horizontal(|ui| {
+                        if ui.add(egui::DragValue::new(&mut v)).changed() { edit = Some((i, v)); }
+                        ui.label(name.as_str());
+                    });
+                }
+                if let Some((i, v)) = edit {
+                    // taking an id that's already used swaps the two classes
+                    let old = rows[i].0;
+                    if let Some(j) = rows.iter().position(|r| r.0 == v) { rows[j].0 = old; }
+                    rows[i].0 = v;
+                    *t = rows.into_iter().collect();
+                }
+            }
+        }
+        if mapping != *self.dataset.classes.mapping() {
+            self.apply_id_mapping(mapping);
+        }
+    }
+
+    // The skeleton changes how pose lines are read, so like the id mapping it
+    // is stored right away, and the label files of all images are rewritten
+    // with its keypoint columns.
+    fn apply_skeleton(&mut self, skeleton: Option<Skeleton>, removed: Option<usize>) {
+        if !self.save_current() { return; }
+        match self.dataset.set_skeleton(skeleton, removed) {
+            Ok(written) => {
+                if let Some(w) = &self.watcher {
+                    for p in &written { w.acknowledge(p); }
+                }
+                self.note_save(self.dataset.skeleton_file_path(), Ok(()));
+                if !written.is_empty() {
+                    self.status = format!("Rewrote {} label files for the new keypoints", written.len());
+                }
+                // undo steps hold the old keypoint columns
+                self.histories.clear();
+                self.placing_keypoint = None;
+                self.load_annotations_for_current();
+            }
+            Err(e) => self.status = format!("Skeleton not changed: {:#}", e),
+        }
+    }
+
+    fn skeleton_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(mut sk) = self.dataset.skeleton.clone() else {
+            if ui.button("Add keypoint skeleton").clicked() { self.apply_skeleton(Some(Skeleton::default()), None); }
+            return;
+        };
+        if ui.button("Remove skeleton").on_hover_text("Drop the keypoints from all label files").clicked() {
+            self.apply_skeleton(None, None);
+            return;
+        }
+        let mut removed = None;
+        ui.horizontal(|ui| {
+            ui.label("Columns:");
+            ui.radio_value(&mut sk.dims, 3, "x y v");
+            ui.radio_value(&mut sk.dims, 2, "x y");
+        });
+        for (i, k) in sk.keypoints.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}: {}", i, k));
+                if ui.small_button("x").clicked() { removed = Some(i); }
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_keypoint);
+            if ui.button("Add keypoint").clicked() && !self.new_keypoint.trim().is_empty() {
+                sk.keypoints.push(self.new_keypoint.trim().to_owned());
+                self.new_keypoint.clear();
+            }
+        });
+        if let Some(k) = removed { sk.remove_keypoint(k); }
+        if sk.keypoints.len() >= 2 {
+            let names = sk.keypoints.clone();
+            let (mut a, mut b) = self.keypoint_pair;
+            a = a.min(names.len() - 1);
+            b = b.min(names.len() - 1);
+            ui.horizontal(|ui| {
+                for (id, v) in [("kp_pair_a", &mut a), ("kp_pair_b", &mut b)] {
+                    egui::ComboBox::from_id_source(id).selected_text(names[*v].as_str()).show_ui(ui, |ui| {
+                        for (i, n) in names.iter().enumerate() { ui.selectable_value(v, i, n.as_str()); }
+                    });
+                }
+            });
+            self.keypoint_pair = (a, b);
+            ui.horizontal(|ui| {
+                if ui.add_enabled(a != b && !sk.edges.contains(&(a, b)) && !sk.edges.contains(&(b, a)), egui::Button::new("Add edge")).clicked() {
+                    sk.edges.push((a, b));
+                }
+                let flipped = sk.flip_pairs.iter().any(|&(x, y)| [x, y].contains(&a) || [x, y].contains(&b));
+                if ui.add_enabled(a != b && !flipped, egui::Button::new("Add flip pair")).clicked() {
+                    sk.flip_pairs.push((a.min(b), a.max(b)));
+                }
+            });
+        }
+        let names = sk.keypoints.clone();
+        for (list, sep) in [(&mut sk.edges, "-"), (&mut sk.flip_pairs, "<->")] {
+            let mut drop = None;
+            for (i, (a, b)) in list.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} {} {}", names[*a], sep, names[*b]));
+                    if ui.small_button("x").clicked() { drop = Some(i); }
+                });
+            }
+            if let Some(i) = drop { list.remove(i); }
+        }
+        if Some(&sk) != self.dataset.skeleton.as_ref() {
+            self.apply_skeleton(Some(sk), removed);
+        }
+    }
+
+    // sets keypoint `k` of box `idx` at `pos`, visible
+    fn place_keypoint(&mut self, idx: usize, k: usize, pos: (f32, f32)) {
+        let count = self.dataset.skeleton.as_ref().map_or(0, |s| s.keypoints.len());
+        let Some(b) = self.boxes.get_mut(idx) else { return };
+        if k >= count { return; }
+        if b.keypoints.len() < count { b.keypoints.resize(count, Keypoint::default()); }
+        b.keypoints[k] = Keypoint { x: pos.0, y: pos.1, v: 2 };
+    }
+
+    fn run_validation(&mut self) {
+        // the open image's edits are checked too
+        if !self.save_current() { return; }
+        match validate::validate(&self.dataset) {
+            Ok(issues) => self.problems = issues,
+            Err(e) => self.status = format!("Validation failed: {:#}", e),
+        }
+    }
+
+    // open the image the issue is in and select the offending box
+    fn jump_to_issue(&mut self, issue: &Issue, ctx: &egui::Context) {
+        let Some(img) = &issue.image else { return };
+        // the image may be in another split
+        if let Layout::Ultralytics { splits, .. } = &self.dataset.layout
+            && !self.dataset.images.iter().any(|e| &e.path == img)
+            && let Some(split) = splits.iter().find(|s| img.parent() == Some(ultralytics::split_dir(&self.dataset.root, s).as_path())).cloned()
+        {
+            if !self.save_current() { return; }
+            if let Err(e) = self.dataset.set_split(&split) {
+                self.status = format!("Failed to open split: {:#}", e);
+                return;
+            }
+            self.cur_idx = self.dataset.images.iter().position(|e| &e.path == img).unwrap_or(0);
+            let _ = self.load_current_image_texture(ctx);
+        }
+        let Some(i) = self.dataset.images.iter().position(|e| &e.path == img) else { return };
+        if i != self.cur_idx {
+            if !self.save_current() { return; }
+            self.cur_idx = i;
+            let _ = self.load_current_image_texture(ctx);
+        }
+        self.select(issue.box_index.filter(|b| *b < self.boxes.len()));
+    }
+
+    fn problems_ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        ui.horizontal(|ui| {
+            ui.heading("Problems");
+            let errors = self.problems.iter().filter(|i| i.severity == Severity::Error).count();
+            ui.label(format!("{} errors, {} warnings", errors, self.problems.len() - errors));
+            if ui.button("Re-check").clicked() { self.run_validation(); }
+        });
+        let mut clicked = None;
+        egui::ScrollArea::vertical().auto_shrink([false, true]).show(ui, |ui| {
+            if self.problems.is_empty() { ui.label("No problems found."); }
+            for (i, issue) in self.problems.iter().enumerate() {
+                let color = match issue.severity {
+                    Severity::Error => Color32::from_rgb(255, 90, 90),
+                    Severity::Warning => Color32::from_rgb(230, 190, 60),
+                };
+                let file = issue.file.strip_prefix(&self.dataset.root).unwrap_or(&issue.file);
+                let text = match issue.line {
+                    Some(l) => format!("{}:{}  {}", file.display(), l, issue.message),
+                    None => format!("{}  {}", file.display(), issue.message),
+                };
+                let resp = ui.add_enabled(issue.image.is_some(), egui::SelectableLabel::new(false, egui::RichText::new(text).color(color)));
+                if resp.clicked() { clicked = Some(i); }
+            }
+        });
+        if let Some(i) = clicked {
+            let issue = self.problems[i].clone();
+            self.jump_to_issue(&issue, ctx);
+        }
+    }
+
+    fn refresh_stats(&mut self) {
+        if !self.save_current() { return; }
+        match stats::compute(&self.dataset) {
+            Ok(s) => self.stats = Some(s),
+            Err(e) => self.status = format!("Statistics failed: {:#}", e),
+        }
+    }
+
+    // dry run of `edit`, shown in the class manager until applied
+    fn preview_class_edit(&mut self, edit: ClassEdit) {
+        // the open image's labels take part
+        if !self.save_current() { return; }
+        match class_edit::plan(&self.dataset, &edit) {
+            Ok(plan) => self.class_plan = Some(plan),
+            Err(e) => {
+                self.class_plan = None;
+                self.status = format!("{}: {:#}", edit.describe(), e);
+            }
         }
     }
-}
 
-impl AppState {
-    fn push_history(&mut self) {
-        // push current boxes snapshot
-        self.history.push(self.boxes.clone());
-        if self.history.len() > self.history_limit {
-            self.history.remove(0);
+    fn apply_class_edit(&mut self) {
+        let Some(plan) = self.class_plan.take() else { return };
+        if !self.save_current() { return; }
+        let class = self.dataset.classes.name(self.cur_class_idx).map(str::to_owned);
+        match class_edit::apply(&mut self.dataset, &plan.edit) {
+            Ok(applied) => {
+                let edit = &applied.edit;
+                if let Some(w) = &self.watcher {
+                    for p in applied.paths() { w.acknowledge(p); }
+                }
+                let change = ClassChange {
+                    colors: self.class_colors.clone(),
+                    hidden: self.hidden_classes.clone(),
+                    locked: self.locked_classes.clone(),
+                    applied: applied.clone(),
+                };
+                // a renamed class keeps its color and toggles
+                let carry = |name: &String| match edit {
+                    ClassEdit::Rename { from, to } if from == name => Some(to.clone()),
+                    ClassEdit::Merge { from, .. } | ClassEdit::Delete { name: from } if from == name => None,
+                    _ => Some(name.clone()),
+                };
+                self.class_colors = std::mem::take(&mut self.class_colors).into_iter().filter_map(|(k, v)| Some((carry(&k)?, v))).collect();
+                self.hidden_classes = self.hidden_classes.iter().filter_map(carry).collect();
+                self.locked_classes = self.locked_classes.iter().filter_map(carry).collect();
+                self.cur_class_idx = class.and_then(|c| edit.map_class(&c)).and_then(|c| self.dataset.classes.id_of(&c)).unwrap_or(0);
+                self.status = format!("{}: rewrote {} label files", edit.describe(), plan.files.len());
+                self.class_changes.push(change);
+            }
+            Err(e) => self.status = format!("{} failed, nothing was changed: {:#}", plan.edit.describe(), e),
         }
+        self.after_class_change();
     }
 
-    fn undo(&mut self) {
-        if let Some(prev) = self.history.pop() {
-            self.boxes = prev;
-            self.selected_box = None;
-            let _ = self.save_annotations_for_current();
+    fn undo_class_edit(&mut self) {
+        if !self.save_current() { return; }
+        let Some(change) = self.class_changes.pop() else { return };
+        let class = self.dataset.classes.name(self.cur_class_idx).map(str::to_owned);
+        match class_edit::undo(&mut self.dataset, &change.applied) {
+            Ok(()) => {
+                if let Some(w) = &self.watcher {
+                    for p in change.applied.paths() { w.acknowledge(p); }
+                }
+                self.class_colors = change.colors;
+                self.hidden_classes = change.hidden;
+                self.locked_classes = change.locked;
+                self.cur_class_idx = class.and_then(|c| self.dataset.classes.id_of(&c)).unwrap_or(0);
+                self.status = format!("Undid {}", change.applied.edit.describe());
+            }
+            Err(e) => self.status = format!("Undo of {} failed: {:#}", change.applied.edit.describe(), e),
         }
+        self.class_plan = None;
+        self.after_class_change();
     }
 
-    fn classes_file_path(&self) -> PathBuf {
-        self.load_dir.join("_darknet.labels")
+    // the open image's boxes and all undo steps name classes that may be gone
+    fn after_class_change(&mut self) {
+        self.histories.clear();
+        self.select(None);
+        self.load_annotations_for_current();
+        self.version_list = None;
+        self.remember_settings();
     }
 
-    fn load_classes_file(&mut self) {
-        let path = self.classes_file_path();
-        if path.exists() {
-            if let Ok(file) = File::open(&path) {
-                let reader = BufReader::new(file);
-                self.classes.clear();
-                for line in reader.lines().flatten() {
-                    let s = line.trim().to_string();
-                    if !s.is_empty() {
-                        self.classes.push(s);
+    fn classes_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Changes here rewrite the label files of all images, in every split.");
+        let names = self.dataset.classes.names().to_vec();
+        if names.is_empty() { return; }
+        self.class_sel = self.class_sel.min(names.len() - 1);
+        let mut preview = None;
+        egui::ScrollArea::vertical().id_source("class_manager_list").max_height(240.0).show(ui, |ui| {
+            egui::Grid::new("class_manager_grid").striped(true).show(ui, |ui| {
+                ui.strong("id");
+                ui.strong("class");
+                ui.end_row();
+                for (i, name) in names.iter().enumerate() {
+                    ui.label(self.dataset.classes.file_id(name).map_or("-".to_owned(), |id| id.to_string()));
+                    if ui.selectable_label(i == self.class_sel, name).clicked() && i != self.class_sel {
+                        self.class_sel = i;
+                        self.class_rename = name.clone();
                     }
+                    if ui.add_enabled(i > 0, egui::Button::new("Up").small()).clicked() {
+                        preview = Some(ClassEdit::Move { name: name.clone(), to: i - 1 });
+                    }
+                    if ui.add_enabled(i + 1 < names.len(), egui::Button::new("Down").small()).clicked() {
+                        preview = Some(ClassEdit::Move { name: name.clone(), to: i + 1 });
+                    }
+                    ui.end_row();
                 }
-                if self.classes.is_empty() {
-                    self.classes.push("object".to_owned());
-                }
+            });
+        });
+        ui.separator();
+        let name = names[self.class_sel].clone();
+        ui.horizontal(|ui| {
+            ui.label("Rename to");
+            ui.text_edit_singleline(&mut self.class_rename);
+            if ui.button("Preview").clicked() {
+                preview = Some(ClassEdit::Rename { from: name.clone(), to: self.class_rename.trim().to_owned() });
+            }
+        });
+        if names.len() > 1 {
+            if self.class_merge_into >= names.len() || self.class_merge_into == self.class_sel {
+                self.class_merge_into = usize::from(self.class_sel == 0);
             }
+            ui.horizontal(|ui| {
+                ui.label("Merge into");
+                egui::ComboBox::from_id_source("class_merge_combo")
+                    .selected_text(names[self.class_merge_into].clone())
+                    .show_ui(ui, |ui| {
+                        for (i, n) in names.iter().enumerate().filter(|(i, _)| *i != self.class_sel) {
+                            ui.selectable_value(&mut self.class_merge_into, i, n);
+                        }
+                    });
+                if ui.button("Preview").clicked() {
+                    preview = Some(ClassEdit::Merge { from: name.clone(), into: names[self.class_merge_into].clone() });
+                }
+            });
         }
-    }
-
-    fn save_classes_file(&self) -> Result<()> {
-        let path = self.classes_file_path();
-        let mut file = File::create(&path)?;
-        for c in &self.classes {
-            writeln!(file, "{}", c)?;
+        if ui.button(format!("Delete '{}' and its boxes", name)).clicked() {
+            preview = Some(ClassEdit::Delete { name: name.clone() });
         }
-        Ok(())
-    }
+        if let Some(edit) = preview { self.preview_class_edit(edit); }
 
-    fn load_images_from_dir(dir: &Path) -> Result<Vec<ImageEntry>> {
-        let mut imgs = vec![];
-        let patterns = ["*.png", "*.jpg", "*.jpeg", "*.bmp", "*.webp", "*.tif"];
-        for pat in patterns.iter() {
-            let globpat = dir.join(pat).to_string_lossy().to_string();
-            for entry in glob(&globpat)? {
-                if let Ok(p) = entry {
-                    imgs.push(ImageEntry { path: p });
+        let (mut apply, mut cancel) = (false, false);
+        if let Some(plan) = &self.class_plan {
+            ui.separator();
+            ui.strong(plan.edit.describe());
+            ui.label(format!("Rewrites {} label files: {} boxes change, {} boxes are deleted.",
+                plan.files.len(), plan.changed_boxes(), plan.removed_boxes()));
+            egui::ScrollArea::vertical().id_source("class_plan_files").max_height(160.0).show(ui, |ui| {
+                for f in &plan.files {
+                    let path = f.path.strip_prefix(&self.dataset.root).unwrap_or(&f.path);
+                    ui.label(format!("{}  ({} changed, {} deleted)", path.display(), f.changed, f.removed));
                 }
-            }
+            });
+            ui.horizontal(|ui| {
+                apply = ui.button("Apply").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
         }
-        imgs.sort_by_key(|e| e.path.clone());
-        Ok(imgs)
-    }
+        if apply { self.apply_class_edit(); }
+        if cancel { self.class_plan = None; }
 
-    fn load_current_image_texture(&mut self, ctx: &egui::Context) -> Result<()> {
-        self.texture = None;
-        self.selected_box = None;
-        self.drag_mode = DragMode::None;
-        self.last_pointer_pos = None;
-        if self.images.is_empty() {
-            return Ok(());
+        if let Some(last) = self.class_changes.last() {
+            ui.separator();
+            if ui.button(format!("Undo: {}", last.applied.edit.describe())).on_hover_text("Put the class list and label files back as they were").clicked() {
+                self.undo_class_edit();
+            }
         }
-        let p = &self.images[self.cur_idx].path;
-        let dynimg = image::io::Reader::open(p)?.decode()?;
-        let (w, h) = dynimg.dimensions();
-        self.original_size = (w, h);
-        let rgba = dynimg.to_rgba8();
-        let size = [w as usize, h as usize];
-        let pixels: Vec<u8> = rgba.into_vec();
-        let image = egui::ColorImage::from_rgba_unmultiplied(size, &pixels);
-        let tex = ctx.load_texture(p.to_string_lossy(), image, egui::TextureOptions::NEAREST);
-        self.texture = Some(tex);
-        self.load_annotations_for_current();
-        Ok(())
     }
 
-    fn annotation_path_for_image(path: &Path) -> PathBuf {
-        let mut out = path.to_path_buf();
-        out.set_extension("txt");
-        out
+    fn stats_ui(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Refresh").clicked() { self.refresh_stats(); }
+        let Some(s) = &self.stats else { return };
+        ui.label(format!("{} images, {} without boxes, {} boxes", s.images, s.unlabeled_images, s.boxes));
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.collapsing("Classes", |ui| {
+                let max = s.classes.iter().map(|c| c.boxes).max().unwrap_or(0);
+                egui::Grid::new("stats_classes").striped(true).show(ui, |ui| {
+                    ui.strong("class");
+                    ui.strong("boxes");
+                    ui.strong("images");
+                    ui.end_row();
+                    for c in &s.classes {
+                        let id = c.id.map(|i| i.to_string()).unwrap_or_else(|| "?".into());
+                        ui.label(format!("{}:{}", id, c.name));
+                        bar(ui, c.boxes, max, c.boxes.to_string());
+                        ui.label(c.images.to_string());
+                        ui.end_row();
+                    }
+                });
+            });
+            ui.collapsing("Boxes per image", |ui| {
+                let max = s.boxes_per_image.iter().copied().max().unwrap_or(0);
+                egui::Grid::new("stats_per_image").show(ui, |ui| {
+                    for (n, count) in s.boxes_per_image.iter().enumerate() {
+                        ui.label(n.to_string());
+                        bar(ui, *count, max, format!("{} images", count));
+                        ui.end_row();
+                    }
+                });
+            });
+            for (title, d) in [("Box size (px, square root of area)", &s.box_size_px), ("Aspect ratio (width / height)", &s.aspect_ratio)] {
+                ui.collapsing(title, |ui| {
+                    ui.label(format!("min {:.1}, median {:.1}, mean {:.1}, max {:.1}", d.min, d.median, d.mean, d.max));
+                    bins_ui(ui, title, &d.bins);
+                });
+            }
+        });
     }
 
-    // Attempt to parse annotation files that may contain either class_id or class_name
-    fn load_annotations_for_current(&mut self) {
-        self.boxes.clear();
-        if self.images.is_empty() {
-            return;
+    fn format_menu_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Save labels as:");
+        let before = self.dataset.format;
+        for f in LabelFormat::ALL {
+            ui.radio_value(&mut self.dataset.format, f, f.label());
         }
-        let imgp = &self.images[self.cur_idx].path;
-        let annp = Self::annotation_path_for_image(imgp);
-        if !annp.exists() {
-            return;
+        if self.dataset.format != before {
+            // the open image is written in the new format right away; other
+            // images are read from their old files until they're saved
+            if !self.save_current() { self.dataset.format = before; }
         }
-        if let Ok(file) = File::open(annp) {
-            let reader = BufReader::new(file);
-            let addition: usize = if self.classes.get(0).is_some_and(|c| c == "object") { 1 } else { 0 };
-            for line in reader.lines().flatten() {
-                let line = line.trim();
-                if line.is_empty() { continue; }
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 5 {
-                    let token = parts[0];
-                    // parse numbers as id if possible
-                    let class_name = if let Ok(mut id) = token.parse::<usize>() {
-                        id += addition;
-                        if id < self.classes.len() {
-                            self.classes[id].clone()
-                        } else {
-                            // Unknown id -> create placeholder name and extend classes vector
-                            while self.classes.len() <= id { self.classes.push(format!("class_{}", self.classes.len())); }
-                            self.classes[id].clone()
-                        }
-                    } else {
-                        token.replace('_', " ")
-                    };
-
-                    if let (Ok(x), Ok(y), Ok(w), Ok(h)) = (
-                        parts[1].parse::<f32>(),
-                        parts[2].parse::<f32>(),
-                        parts[3].parse::<f32>(),
-                        parts[4].parse::<f32>(),
-                    ) {
-                        self.boxes.push(BBox { class_name: class_name.clone(), cx: x, cy: y, w, h });
-                        if !self.classes.iter().any(|c| c == &class_name) {
-                            self.classes.push(class_name);
-                        }
-                    }
-                }
-            }
+        ui.separator();
+        let (from, to) = (LabelFormat::ALL.into_iter().find(|f| *f != self.dataset.format).unwrap_or_default(), self.dataset.format);
+        if ui.button(format!("Convert all {} labels to {}", from.label(), to.label())).clicked() && self.save_current() {
+            self.status = match self.dataset.convert_all(from, to) {
+                Ok(n) => format!("Converted {} label files to {}", n, to.label()),
+                Err(e) => format!("Conversion failed: {:#}", e),
+            };
+            ui.close_menu();
         }
-        // save classes file so newly discovered classes persist
-        let _ = self.save_classes_file();
     }
 
-    // save annotations using class ids (index in self.classes)
-    fn save_annotations_for_current(&mut self) -> Result<()> {
-        if self.images.is_empty() { return Ok(()); }
-        let imgp = &self.images[self.cur_idx].path;
-        let annp = Self::annotation_path_for_image(imgp);
-        let mut file = File::create(&annp)?;
-        for b in &self.boxes {
-            // find class id or create it
-            let mut minus: usize = if self.classes.get(0).is_some_and(|c| c == "object") { 1 } else { 0 };
-            let cid = match self.classes.iter().position(|c| c == &b.class_name) {
-                Some(i) => i,
-                None => {
-                    let i = self.classes.len();
-                    self.classes.push(b.class_name.clone());
-                    // update classes file on disk
-                    let _ = self.save_classes_file();
-                    i
+    fn coco_menu_ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        if self.coco_path.is_empty() {
+            self.coco_path = self.dataset.root.join(coco::DEFAULT_FILE).to_string_lossy().to_string();
+        }
+        ui.label("COCO json file:");
+        ui.text_edit_singleline(&mut self.coco_path);
+        let path = PathBuf::from(&self.coco_path);
+        if ui.button("Export all images").clicked() && self.save_current() {
+            self.status = match coco::export_to_file(&self.dataset, &path) {
+                Ok(s) => format!("Exported {} images, {} boxes to {}", s.images, s.boxes, path.display()),
+                Err(e) => format!("COCO export failed: {:#}", e),
+            };
+            ui.close_menu();
+        }
+        if ui.button("Import (overwrites labels)").clicked() && self.save_current() {
+            self.status = match coco::import_file(&path, &mut self.dataset) {
+                Ok(s) => {
+                    if let Some(w) = &self.watcher {
+                        for p in &s.written { w.acknowledge(p); }
+                    }
+                    format!("Imported {} images, {} boxes from {}", s.images, s.boxes, path.display())
                 }
+                Err(e) => format!("COCO import failed: {:#}", e),
             };
-            if cid < minus {
-                // should not happen, but just in case
-                minus = 0;
+            // classes and labels changed on disk
+            if let Err(e) = self.dataset.reload() {
+                self.status = format!("Failed to reload folder: {:#}", e);
             }
-            writeln!(file, "{} {:.6} {:.6} {:.6} {:.6}", cid - minus, b.cx, b.cy, b.w, b.h)?;
+            self.histories.clear();
+            self.cur_idx = self.cur_idx.min(self.dataset.images.len().saturating_sub(1));
+            self.cur_class_idx = 0;
+            let _ = self.load_current_image_texture(ctx);
+            ui.close_menu();
         }
-        Ok(())
     }
 
     fn add_box_from_drag(&mut self, img_rect: Rect) {
@@ -274,25 +1619,149 @@ impl AppState {
         let pixel_w = w * img_rect.width();
         let pixel_h = h * img_rect.height();
         if w > 0.0 && h > 0.0 && pixel_w >= self.min_box_pixels && pixel_h >= self.min_box_pixels {
-            let class_name = if self.classes.is_empty() {
-                "object".to_owned()
-            } else {
-                self.classes.get(self.cur_class_idx).cloned().unwrap_or_else(|| self.classes[0].clone())
-            };
-            // record history before creating
-            self.push_history();
-            self.boxes.push(BBox { class_name, cx, cy, w, h });
+            let class_name = self.new_shape_class();
+            let b = BBox::new(class_name, cx, cy, w, h);
+            self.boxes.push(if self.shape_mode == ShapeMode::Rotated { b.to_rotated() } else { b });
+        }
+    }
+
+    // class given to newly drawn shapes
+    fn new_shape_class(&self) -> String {
+        self.dataset.classes.name(self.cur_class_idx)
+            .or_else(|| self.dataset.classes.name(0))
+            .unwrap_or(DEFAULT_CLASS)
+            .to_owned()
+    }
+
+    fn finish_polygon(&mut self) {
+        let points = std::mem::take(&mut self.draft_polygon);
+        if points.len() < 3 { return; }
+        let class_name = self.new_shape_class();
+        self.begin_edit();
+        self.boxes.push(BBox::from_polygon(class_name, points));
+        self.commit_edit("Create polygon");
+        self.select(Some(self.boxes.len() - 1));
+    }
+}
+
+// horizontal bar scaled against `max`
+fn bar(ui: &mut egui::Ui, value: usize, max: usize, text: String) {
+    let frac = if max == 0 { 0.0 } else { value as f32 / max as f32 };
+    ui.add(egui::ProgressBar::new(frac).desired_width(200.0).text(text));
+}
+
+fn bins_ui(ui: &mut egui::Ui, id: &str, bins: &[Bin]) {
+    let max = bins.iter().map(|b| b.count).max().unwrap_or(0);
+    egui::Grid::new(id).show(ui, |ui| {
+        for b in bins {
+            ui.label(&b.label);
+            bar(ui, b.count, max, b.count.to_string());
+            ui.end_row();
+        }
+    });
+}
+
+fn to_screen(img_rect: &Rect, (x, y): (f32, f32)) -> Pos2 {
+    Pos2::new(img_rect.left() + x * img_rect.width(), img_rect.top() + y * img_rect.height())
+}
+
+fn to_ratio(img_rect: &Rect, pos: Pos2) -> (f32, f32) {
+    (((pos.x - img_rect.left()) / img_rect.width()).clamp(0.0, 1.0), ((pos.y - img_rect.top()) / img_rect.height()).clamp(0.0, 1.0))
+}
+
+fn dist_to_segment(p: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let ab = b - a;
+    let t = if ab.length_sq() > 0.0 { ((p - a).dot(ab) / ab.length_sq()).clamp(0.0, 1.0) } else { 0.0 };
+    p.distance(a + ab * t)
+}
+
+// even-odd rule, or within `tol` of an edge
+fn point_in_polygon(pts: &[Pos2], pos: Pos2, tol: f32) -> bool {
+    let n = pts.len();
+    let mut inside = false;
+    for i in 0..n {
+        let (a, b) = (pts[i], pts[(i + 1) % n]);
+        if dist_to_segment(pos, a, b) <= tol { return true; }
+        if (a.y > pos.y) != (b.y > pos.y) && pos.x < a.x + (pos.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
         }
     }
+    inside
+}
+
+/// How long ago something happened, in its largest whole unit.
+fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        s @ 0..=59 => format!("{} s", s),
+        s @ 60..=3599 => format!("{} min", s / 60),
+        s @ 3600..=86399 => format!("{} h", s / 3600),
+        s => format!("{} days", s / 86400),
+    }
+}
+
+// whether the polygon turns the same way at every vertex; only those are filled
+fn is_convex(pts: &[Pos2]) -> bool {
+    let n = pts.len();
+    let mut sign = 0.0f32;
+    for i in 0..n {
+        let (a, b, c) = (pts[i], pts[(i + 1) % n], pts[(i + 2) % n]);
+        let cross = (b - a).x * (c - b).y - (b - a).y * (c - b).x;
+        if cross != 0.0 {
+            if sign != 0.0 && cross.signum() != sign { return false; }
+            sign = cross.signum();
+        }
+    }
+    true
+}
+
+// handle above the middle of the first edge of a rotated box, in screen space
+fn rotation_handle(pts: &[Pos2]) -> Option<Pos2> {
+    let center = pts.iter().fold(Vec2::ZERO, |acc, p| acc + p.to_vec2()) / pts.len() as f32;
+    let mid = pts[0].lerp(pts[1], 0.5);
+    let out = mid.to_vec2() - center;
+    (out.length() > 0.0).then(|| mid + out.normalized() * 25.0)
+}
+
+// Turns a rotated box so its rotation handle points at `pos`. Rotation happens
+// in screen space, which is the image's pixel space scaled, so the box stays a
+// rectangle in pixels. Rotations that would move a corner off the image are ignored.
+fn rotate_towards(b: &mut BBox, pos: Pos2, img_rect: &Rect) {
+    let pts: Vec<Pos2> = b.polygon.iter().map(|p| to_screen(img_rect, *p)).collect();
+    let center = (pts.iter().fold(Vec2::ZERO, |acc, p| acc + p.to_vec2()) / pts.len() as f32).to_pos2();
+    let (from, to) = (pts[0].lerp(pts[1], 0.5) - center, pos - center);
+    if from.length() == 0.0 || to.length() == 0.0 { return; }
+    let delta = to.angle() - from.angle();
+    let rot = egui::emath::Rot2::from_angle(delta);
+    let turned: Vec<Pos2> = pts.iter().map(|p| center + rot * (*p - center)).collect();
+    if turned.iter().all(|p| img_rect.expand(0.5).contains(*p)) {
+        b.polygon = turned.iter().map(|p| to_ratio(img_rect, *p)).collect();
+        b.fit_to_polygon();
+    }
+}
 
-    // fn screen_to_ratio(&self, pos: Pos2, img_rect: Rect) -> (f32, f32) {
-    //     let x = ((pos.x - img_rect.left()) / img_rect.width()).clamp(0.0, 1.0);
-    //     let y = ((pos.y - img_rect.top()) / img_rect.height()).clamp(0.0, 1.0);
-    //     (x, y)
-    // }
+// Moves corner `k` of a rotated box to `pos`, keeping the opposite corner and
+// the box's orientation.
+fn resize_rotated(b: &mut BBox, k: usize, pos: Pos2, img_rect: &Rect) {
+    let pts: Vec<Pos2> = b.polygon.iter().map(|p| to_screen(img_rect, *p)).collect();
+    let o = pts[(k + 2) % 4];
+    let (e1, e2) = (pts[(k + 1) % 4] - o, pts[(k + 3) % 4] - o);
+    if e1.length() == 0.0 || e2.length() == 0.0 { return; }
+    let (u1, u2) = (e1.normalized(), e2.normalized());
+    let d = pos.clamp(img_rect.min, img_rect.max) - o;
+    let (a, c) = (u1 * d.dot(u1), u2 * d.dot(u2));
+    let moved = [(k, o + a + c), ((k + 1) % 4, o + a), ((k + 3) % 4, o + c)];
+    if moved.iter().all(|(_, p)| img_rect.expand(0.5).contains(*p)) {
+        for (i, p) in moved {
+            b.polygon[i] = to_ratio(img_rect, p);
+        }
+    }
 }
 
 fn clicked_in_a_box(b: &BBox, img_rect: &Rect, pos: &Pos2, tol: &f32) -> bool {
+    if b.is_polygon() {
+        let pts: Vec<Pos2> = b.polygon.iter().map(|p| to_screen(img_rect, *p)).collect();
+        return point_in_polygon(&pts, *pos, *tol);
+    }
     let left = img_rect.left() + (b.cx - b.w/2.0) * img_rect.width();
     let top = img_rect.top() + (b.cy - b.h/2.0) * img_rect.height();
     let right = left + b.w * img_rect.width();
@@ -306,52 +1775,138 @@ fn clicked_in_a_box(b: &BBox, img_rect: &Rect, pos: &Pos2, tol: &f32) -> bool {
 
 impl eframe::App for AppState {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // handle Ctrl+Z undo
-        let ctrl_z_pressed = ctx.input(|input| input.modifiers.ctrl && input.key_pressed(Key::Z));
-        if ctrl_z_pressed {
-            self.undo();
+        if ctx.input(|i| i.viewport().close_requested()) {
+            if self.is_dirty() && !self.quitting {
+                // ask first; confirm_quit_ui closes the window again
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                self.confirm_quit = true;
+            } else {
+                self.remember_settings();
+            }
+        }
+
+        // keymap shortcuts; text fields keep their keys
+        if self.drag_mode == DragMode::None && !self.nudging && !ctx.wants_keyboard_input() {
+            let keymap = &self.keymap;
+            let actions: Vec<Action> = ctx.input_mut(|i| {
+                let mut actions: Vec<Action> = Action::ALL.into_iter().filter(|a| keymap.pressed(i, *a)).collect();
+                // the window may turn Ctrl+C / Ctrl+V into copy / paste events instead of key presses
+                for (action, key) in [(Action::Copy, Key::C), (Action::Paste, Key::V)] {
+                    let event = i.events.iter().any(|e| match action {
+                        Action::Copy => matches!(e, egui::Event::Copy),
+                        _ => matches!(e, egui::Event::Paste(_)),
+                    });
+                    if event && keymap.binds(action, &egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, key)) && !actions.contains(&action) {
+                        actions.push(action);
+                    }
+                }
+                actions
+            });
+            for action in actions {
+                self.run_action(action, ctx);
+            }
+        }
+        if (self.placing_keypoint.is_some() || self.draw_armed) && !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(Key::Escape)) {
+            self.placing_keypoint = None;
+            self.draw_armed = false;
+        }
+        if !self.draft_polygon.is_empty() && !ctx.wants_keyboard_input() {
+            let (enter, escape, backspace) = ctx.input(|i| (i.key_pressed(Key::Enter), i.key_pressed(Key::Escape), i.key_pressed(Key::Backspace)));
+            if enter { self.finish_polygon(); }
+            if escape { self.draft_polygon.clear(); }
+            if backspace { self.draft_polygon.pop(); }
         }
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 if ui.button("Prev").clicked() {
-                    if !self.images.is_empty() {
-                        let _ = self.save_annotations_for_current();
-                        if self.cur_idx == 0 { self.cur_idx = self.images.len() - 1; }
-                        else { self.cur_idx -= 1; }
-                        let _ = self.load_current_image_texture(ctx);
-                    }
+                    self.prev_image(ctx);
                 }
                 if ui.button("Next").clicked() {
-                    if !self.images.is_empty() {
-                        let _ = self.save_annotations_for_current();
-                        self.cur_idx = (self.cur_idx + 1) % self.images.len();
-                        let _ = self.load_current_image_texture(ctx);
-                    }
+                    self.next_image(ctx);
+                }
+                let can_propagate = self.cur_idx > 0 && self.boxes.is_empty();
+                if ui.add_enabled(can_propagate, egui::Button::new("Copy previous")).on_hover_text("Copy all boxes of the previous image into this unlabeled one (P)").clicked() {
+                    self.copy_from_previous();
                 }
+                ui.checkbox(&mut self.auto_propagate, "on Next").on_hover_text("Copy the previous image's boxes whenever Next opens an unlabeled image");
 
                 if ui.button("Save").clicked() {
-                    let _ = self.save_annotations_for_current();
+                    self.save_current();
                 }
+                ui.checkbox(&mut self.manual_save, "manually").on_hover_text("Write labels only on Save and when moving to another image, not after every edit");
 
-                ui.label(format!("Image {}/{}", self.cur_idx + 1, self.images.len().max(1)));
+                ui.label(format!("Image {}/{}", self.cur_idx + 1, self.dataset.images.len().max(1)));
 
                 ui.separator();
 
-                if ui.button("Reload folder").clicked() {
-                    if let Ok(list) = Self::load_images_from_dir(&self.load_dir) {
-                        self.images = list;
-                        self.cur_idx = 0;
-                        // reload classes and first image
-                        self.load_classes_file();
-                        let _ = self.load_current_image_texture(ctx);
-                    }
+                ui.menu_button(format!("Labels: {}", self.dataset.format.label()), |ui| self.format_menu_ui(ui));
+                ui.menu_button("COCO", |ui| self.coco_menu_ui(ui, ctx));
+
+                let problems_label = if self.show_problems { format!("Problems ({})", self.problems.len()) } else { "Problems".to_owned() };
+                if ui.selectable_label(self.show_problems, problems_label).clicked() {
+                    self.show_problems = !self.show_problems;
+                    if self.show_problems { self.run_validation(); }
+                }
+
+                if ui.selectable_label(self.show_history, "History").clicked() {
+                    self.show_history = !self.show_history;
+                }
+
+                if ui.selectable_label(self.show_versions, "Versions").on_hover_text("Revert to an earlier version of this image's labels").clicked() {
+                    self.show_versions = !self.show_versions;
+                }
+
+                if ui.selectable_label(self.show_shortcuts, "Shortcuts").clicked() {
+                    self.show_shortcuts = !self.show_shortcuts;
+                }
+
+                if ui.selectable_label(self.show_stats, "Statistics").clicked() {
+                    self.show_stats = !self.show_stats;
+                    if self.show_stats { self.refresh_stats(); }
                 }
 
-                if ui.button("Quit").clicked() { std::process::exit(0); }
+                if ui.button("Quit").clicked() {
+                    self.quit(ctx);
+                }
+
+                if !self.status.is_empty() {
+                    ui.separator();
+                    ui.label(&self.status);
+                }
             });
         });
 
+        // added first so it stays below the problems panel
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| self.status_bar_ui(ui));
+
+        if self.show_problems {
+            egui::TopBottomPanel::bottom("problems_panel").resizable(true).show(ctx, |ui| self.problems_ui(ui, ctx));
+        }
+
+        let mut show_shortcuts = self.show_shortcuts;
+        egui::Window::new("Keyboard shortcuts").open(&mut show_shortcuts).show(ctx, |ui| self.shortcuts_ui(ui));
+        self.show_shortcuts = show_shortcuts;
+
+        let mut show_versions = self.show_versions;
+        egui::Window::new("Label versions").open(&mut show_versions).show(ctx, |ui| self.versions_ui(ui));
+        self.show_versions = show_versions;
+
+        let mut show_classes = self.show_classes;
+        egui::Window::new("Manage classes").open(&mut show_classes).default_width(380.0).show(ctx, |ui| self.classes_ui(ui));
+        self.show_classes = show_classes;
+
+        let mut show_stats = self.show_stats;
+        egui::Window::new("Statistics").open(&mut show_stats).default_width(420.0).show(ctx, |ui| self.stats_ui(ui));
+        self.show_stats = show_stats;
+
+        if self.show_history {
+            egui::SidePanel::right("history_panel").show(ctx, |ui| {
+                ui.heading("History");
+                self.history_ui(ui);
+            });
+        }
+
         egui::SidePanel::left("left_panel").show(ctx, |ui| {
             if ui.button("Back to Default View").clicked() {
                 self.zoom_level = 1.0;
@@ -360,16 +1915,17 @@ impl eframe::App for AppState {
             }
             ui.vertical(|ui| {
                 ui.heading("Classes");
-                if !self.classes.is_empty() {
-                    let mut idx = self.cur_class_idx.min(self.classes.len()-1);
+                if !self.dataset.classes.is_empty() {
+                    let mut idx = self.cur_class_idx.min(self.dataset.classes.len()-1);
                     egui::ComboBox::from_id_source("class_combo")
-                        .selected_text(self.classes[idx].clone())
+                        .selected_text(self.dataset.classes.names()[idx].clone())
                         .show_ui(ui, |ui| {
-                            for (i, c) in self.classes.iter().enumerate() {
+                            for (i, c) in self.dataset.classes.iter().enumerate() {
                                 if ui.selectable_label(i==idx, c).clicked() { idx = i; }
                             }
                         });
                     self.cur_class_idx = idx;
+                    self.legend_ui(ui);
                 }
 
                 ui.separator();
@@ -380,45 +1936,127 @@ impl eframe::App for AppState {
                     if ui.button("Add").clicked() {
                         if !self.new_class.trim().is_empty() {
                             let name = self.new_class.trim().to_owned();
-                            if !self.classes.iter().any(|c| c == &name) {
-                                self.classes.push(name.clone());
+                            let (id, added) = self.dataset.classes.ensure(&name);
+                            if added {
                                 // persist classes
-                                let _ = self.save_classes_file();
+                                let result = self.dataset.save_classes_file();
+                                self.note_save(self.dataset.classes_file_path(), result);
                             }
-                            self.cur_class_idx = self.classes.iter().position(|c| c == &name).unwrap_or(0);
+                            self.cur_class_idx = id;
                             self.new_class.clear();
                         }
                     }
                 });
+                if ui.button("Manage classes").on_hover_text("Rename, reorder, merge or delete classes in all label files").clicked() {
+                    self.show_classes = true;
+                }
+
+                // four-corner lines are polygons unless the dataset says OBB,
+                // so rotated boxes are only drawn there; pose datasets hold
+                // plain boxes only
+                let pose = self.dataset.kpt_shape().is_some_and(|k| k.count > 0);
+                let mut obb = self.dataset.obb;
+                let toggled = ui.add_enabled(!pose, egui::Checkbox::new(&mut obb, "OBB labels"))
+                    .on_hover_text("Read and write four-corner label lines as rotated boxes").changed();
+                if toggled && self.save_current() {
+                    self.dataset.obb = obb;
+                    self.load_annotations_for_current();
+                    self.remember_settings();
+                }
+                let rotated = self.dataset.obb && !pose;
+                if (pose && self.shape_mode == ShapeMode::Polygon) || (!rotated && self.shape_mode == ShapeMode::Rotated) {
+                    self.shape_mode = ShapeMode::Box;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("New shapes:");
+                    ui.radio_value(&mut self.shape_mode, ShapeMode::Box, "Box");
+                    ui.add_enabled_ui(rotated, |ui| ui.radio_value(&mut self.shape_mode, ShapeMode::Rotated, "Rotated box"));
+                    ui.add_enabled_ui(!pose, |ui| ui.radio_value(&mut self.shape_mode, ShapeMode::Polygon, "Polygon"));
+                });
+
+                ui.separator();
+                self.id_mapping_ui(ui);
+
+                ui.separator();
+                ui.collapsing("Keypoint skeleton", |ui| self.skeleton_ui(ui));
 
                 ui.separator();
                 ui.label("Settings:");
                 ui.add(egui::Slider::new(&mut self.click_tolerance, 1.0..=30.0).text("click tolerance (px)"));
                 ui.add(egui::Slider::new(&mut self.min_box_pixels, 1.0..=40.0).text("min box pixels"));
                 ui.add(egui::Slider::new(&mut self.zoom_level, 0.1..=5.0).text("zoom level"));
+                ui.add(egui::Slider::new(&mut self.fill_opacity, 0.0..=1.0).text("fill opacity"));
+                ui.add(egui::Slider::new(&mut self.prefetch, 0..=8).text("prefetch images"));
+                let kept = ui.add(egui::Slider::new(&mut self.retention.max_versions, 0..=500).text("versions kept per file (0 = all)")).changed()
+                    | ui.add(egui::Slider::new(&mut self.retention.max_age_days, 0..=365).text("days versions are kept (0 = forever)")).changed();
+                if kept { self.dataset.backup = Some(self.retention); }
+
+                if let Layout::Ultralytics { splits, split } = &self.dataset.layout {
+                    ui.separator();
+                    let mut chosen = split.clone();
+                    ui.horizontal(|ui| {
+                        ui.label("Split:");
+                        egui::ComboBox::from_id_source("split_combo")
+                            .selected_text(if chosen.is_empty() { "(images)" } else { chosen.as_str() })
+                            .show_ui(ui, |ui| {
+                                for s in splits {
+                                    ui.selectable_value(&mut chosen, s.clone(), if s.is_empty() { "(images)" } else { s.as_str() });
+                                }
+                            });
+                    });
+                    if chosen != *split && self.save_current() {
+                        match self.dataset.set_split(&chosen) {
+                            Ok(()) => {
+                                self.cur_idx = 0;
+                                let _ = self.load_current_image_texture(ctx);
+                            }
+                            Err(e) => self.status = format!("Failed to open split: {:#}", e),
+                        }
+                    }
+                }
 
                 ui.separator();
                 ui.heading("Images in folder:");
                 // Collect clicked index outside the loop to avoid borrow issues
                 let mut clicked_idx: Option<usize> = None;
-                for (i, e) in self.images.iter().enumerate() {
-                    let fname = e.path.file_name().unwrap().to_string_lossy();
-                    if ui.selectable_label(i == self.cur_idx, fname.as_ref()).clicked() {
+                let dirty = self.is_dirty();
+                for (i, e) in self.dataset.images.iter().enumerate() {
+                    let mut fname = e.path.file_name().unwrap().to_string_lossy().into_owned();
+                    if i == self.cur_idx && dirty { fname.push_str(" *"); }
+                    if ui.selectable_label(i == self.cur_idx, fname).clicked() {
                         clicked_idx = Some(i);
                     }
                 }
-                if let Some(i) = clicked_idx {
-                    let _ = self.save_annotations_for_current();
+                if let Some(i) = clicked_idx && self.save_current() {
                     self.cur_idx = i;
                     let _ = self.load_current_image_texture(ctx);
                 }
             })
         });
 
+        self.update_title(ctx);
+        if self.confirm_quit {
+            egui::Window::new("Unsaved changes").collapsible(false).resizable(false).anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| self.confirm_quit_ui(ui, ctx));
+        }
+
+        self.check_files(ctx);
+        if self.conflict.is_some() {
+            egui::Window::new("Labels changed on disk").collapsible(false).anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| self.conflict_ui(ui));
+        }
+
+        if let Some(loader) = &mut self.loader {
+            loader.poll(ctx);
+            if self.texture.is_none() && let Some(e) = self.dataset.images.get(self.cur_idx) {
+                self.texture = loader.get(&e.path);
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.label(if self.images.is_empty() { "No images loaded. Launch with: cargo run -- /path/to/images" } else { "Draw bounding boxes by clicking and dragging over the image. Click a box to select it, drag to move, drag corners to resize. Ctrl+Z to undo." });
+            ui.label(if self.dataset.images.is_empty() { "No images loaded. Launch with: cargo run -- /path/to/images" } else { "Draw bounding boxes by clicking and dragging over the image. Click a box to select it, drag to move, drag corners to resize. In polygon mode click to add vertices and Enter to close; drag a vertex to move it, its edge midpoint to insert one, right-click to delete it. Ctrl+Z to undo." });
 
-            if self.images.is_empty() { return; }
+            if self.dataset.images.is_empty() { return; }
 
             if let Some(tex) = &self.texture {
                 let available = ui.available_size();
@@ -465,17 +2103,38 @@ impl eframe::App for AppState {
 
                 let pan_step = 30.0; // pixels per key press
                 let input = ctx.input(|i| i.clone());
-                if input.key_pressed(Key::ArrowLeft) {
-                    self.pan_offset.x += pan_step;
-                }
-                if input.key_pressed(Key::ArrowRight) {
-                    self.pan_offset.x -= pan_step;
-                }
-                if input.key_pressed(Key::ArrowUp) {
-                    self.pan_offset.y += pan_step;
+                let arrows = [(Key::ArrowLeft, -1.0, 0.0), (Key::ArrowRight, 1.0, 0.0), (Key::ArrowUp, 0.0, -1.0), (Key::ArrowDown, 0.0, 1.0)];
+                if !self.selection.is_empty() && self.drag_mode == DragMode::None && !ctx.wants_keyboard_input() {
+                    // arrow keys nudge the selected boxes by one image pixel, ten with Shift
+                    let step = if input.modifiers.shift { 10.0 } else { 1.0 };
+                    let (mut dx, mut dy) = (0.0, 0.0);
+                    for (key, x, y) in arrows {
+                        if input.key_pressed(key) { dx += x * step; dy += y * step; }
+                    }
+                    if (dx != 0.0 || dy != 0.0) && self.original_size.0 > 0 && self.original_size.1 > 0 {
+                        if !self.nudging {
+                            self.begin_edit();
+                            self.nudging = true;
+                        }
+                        self.move_selection(dx / self.original_size.0 as f32, dy / self.original_size.1 as f32);
+                    }
+                } else {
+                    if input.key_pressed(Key::ArrowLeft) {
+                        self.pan_offset.x += pan_step;
+                    }
+                    if input.key_pressed(Key::ArrowRight) {
+                        self.pan_offset.x -= pan_step;
+                    }
+                    if input.key_pressed(Key::ArrowUp) {
+                        self.pan_offset.y += pan_step;
+                    }
+                    if input.key_pressed(Key::ArrowDown) {
+                        self.pan_offset.y -= pan_step;
+                    }
                 }
-                if input.key_pressed(Key::ArrowDown) {
-                    self.pan_offset.y -= pan_step;
+                if self.nudging && !arrows.iter().any(|(k, _, _)| input.keys_down.contains(k)) {
+                    self.nudging = false;
+                    self.commit_edit(if self.selection.len() > 1 { "Nudge boxes" } else { "Nudge box" });
                 }
 
                 let pointer = ui.input(|i| i.pointer.clone());
@@ -497,67 +2156,134 @@ impl eframe::App for AppState {
                 if pointer.primary_clicked() {
                     if let Some(pos) = pointer.interact_pos() {
                         if img_rect.contains(pos) {
-                            // Check if click is inside a box (with tolerance)
-                            // prioritize current selected box if click is also inside it
-                            let mut found = None;
-                            let tol = self.click_tolerance;
-                            if let Some(sel) = self.selected_box {
-                                if sel < self.boxes.len() {
-                                    let b = &self.boxes[sel];
-                                    if clicked_in_a_box(b, &img_rect, &pos, &tol) {
-                                        found = Some(sel);
-                                    }
+                            let shift = ctx.input(|i| i.modifiers.shift);
+                            let handle = self.click_tolerance.max(6.0); // use tolerance as handle size but at least 6px
+                            // vertex or edge-midpoint handle of the selected polygon; corner or
+                            // rotation handle of a selected rotated box
+                            let mut vertex = None;
+                            let mut rotate = false;
+                            if let Some(b) = self.selected_box.and_then(|i| self.boxes.get(i)) && b.is_polygon() {
+                                let pts: Vec<Pos2> = b.polygon.iter().map(|p| to_screen(&img_rect, *p)).collect();
+                                if b.rotated && rotation_handle(&pts).is_some_and(|h| h.distance(pos) <= handle) {
+                                    rotate = true;
+                                } else if let Some(v) = pts.iter().position(|p| p.distance(pos) <= handle) {
+                                    vertex = Some((v, false));
+                                } else if !b.rotated && let Some(e) = (0..pts.len()).find(|e| pts[*e].lerp(pts[(e + 1) % pts.len()], 0.5).distance(pos) <= handle) {
+                                    vertex = Some((e + 1, true));
                                 }
                             }
-                            if found.is_none() {
-                                for (i, b) in self.boxes.iter().enumerate().rev() {
-                                    if clicked_in_a_box(b, &img_rect, &pos, &tol) {
-                                        found = Some(i);
-                                        break;
-                                    }
+                            // labeled keypoint of the selected box under the pointer
+                            let keypoint = self.selected_box.and_then(|i| self.boxes.get(i)).and_then(|b| {
+                                b.keypoints.iter().position(|k| k.v > 0 && to_screen(&img_rect, (k.x, k.y)).distance(pos) <= handle)
+                            });
+                            if let (Some(k), Some(sel)) = (self.placing_keypoint, self.selected_box) {
+                                self.begin_edit();
+                                self.place_keypoint(sel, k, to_ratio(&img_rect, pos));
+                                self.commit_edit("Place keypoint");
+                                let count = self.dataset.skeleton.as_ref().map_or(0, |s| s.keypoints.len());
+                                self.placing_keypoint = (k + 1 < count).then_some(k + 1);
+                            } else if let Some(k) = keypoint {
+                                // dragging moves the keypoint, a plain click toggles its visibility
+                                self.begin_edit();
+                                self.drag_start = pos;
+                                self.drag_mode = DragMode::Keypoint(k);
+                            } else if !self.draft_polygon.is_empty() {
+                                // while drawing a polygon every click adds a vertex; clicking the first one closes it
+                                let first = to_screen(&img_rect, self.draft_polygon[0]);
+                                if self.draft_polygon.len() >= 3 && first.distance(pos) <= handle {
+                                    self.finish_polygon();
+                                } else {
+                                    self.draft_polygon.push(to_ratio(&img_rect, pos));
                                 }
-                            }
-                            self.selected_box = found;
-
-                            // If user clicked on a box, decide move or resize; otherwise start creating
-                            if let Some(i) = found {
-                                // record history once when action starts
-                                self.push_history();
-
-                                // determine corner proximity
-                                let b = &self.boxes[i];
-                                let left = img_rect.left() + (b.cx - b.w/2.0) * img_rect.width();
-                                let top = img_rect.top() + (b.cy - b.h/2.0) * img_rect.height();
-                                let right = left + b.w * img_rect.width();
-                                let bottom = top + b.h * img_rect.height();
-                                let handle = self.click_tolerance.max(6.0); // use tolerance as handle size but at least 6px
-                                let near_left = (pos.x - left).abs() <= handle;
-                                let near_right = (pos.x - right).abs() <= handle;
-                                let near_top = (pos.y - top).abs() <= handle;
-                                let near_bottom = (pos.y - bottom).abs() <= handle;
-                                self.last_pointer_pos = Some(pos);
-                                if near_left && near_top { self.drag_mode = DragMode::Resizing(ResizeCorner::TL); }
-                                else if near_right && near_top { self.drag_mode = DragMode::Resizing(ResizeCorner::TR); }
-                                else if near_left && near_bottom { self.drag_mode = DragMode::Resizing(ResizeCorner::BL); }
-                                else if near_right && near_bottom { self.drag_mode = DragMode::Resizing(ResizeCorner::BR); }
-                                else { self.drag_mode = DragMode::Moving; }
+                            } else if shift {
+                                // shift-click adds to / removes from the selection, shift-drag selects a rectangle
+                                if let Some(i) = self.box_at(pos, &img_rect) {
+                                    self.toggle_selected(i);
+                                } else {
+                                    self.drag_mode = DragMode::Selecting;
+                                    self.drag_start = pos;
+                                    self.drag_end = pos;
+                                }
+                            } else if rotate {
+                                self.begin_edit();
+                                self.drag_mode = DragMode::Rotating;
+                            } else if let (Some((v, insert)), Some(sel)) = (vertex, self.selected_box) {
+                                self.begin_edit();
+                                if insert {
+                                    self.boxes[sel].polygon.insert(v, to_ratio(&img_rect, pos));
+                                }
+                                self.drag_mode = DragMode::Vertex(v);
                             } else {
-                                self.drag_mode = DragMode::Creating;
-                                if let Some(p) = pointer.interact_pos() {
-                                    self.dragging = true;
-                                    self.drag_start = p;
-                                    self.drag_end = p;
-                                    // record history for creation start
-                                    self.push_history();
+                                // after the draw shortcut the press always starts a new shape
+                                let found = if self.draw_armed { None } else { self.box_at(pos, &img_rect) };
+                                self.draw_armed = false;
+                                // pressing a box of a group keeps the group so it can be moved together
+                                if let Some(i) = found && self.selection.len() > 1 && self.selection.contains(&i) {
+                                    self.selected_box = Some(i);
+                                } else {
+                                    self.select(found);
+                                }
+
+                                // If user clicked on a box, decide move or resize; otherwise start creating
+                                if let Some(i) = found {
+                                    // the whole move / resize is one undo step, and none if the box didn't change
+                                    self.begin_edit();
+
+                                    // determine corner proximity
+                                    let b = &self.boxes[i];
+                                    let left = img_rect.left() + (b.cx - b.w/2.0) * img_rect.width();
+                                    let top = img_rect.top() + (b.cy - b.h/2.0) * img_rect.height();
+                                    let right = left + b.w * img_rect.width();
+                                    let bottom = top + b.h * img_rect.height();
+                                    let near_left = (pos.x - left).abs() <= handle;
+                                    let near_right = (pos.x - right).abs() <= handle;
+                                    let near_top = (pos.y - top).abs() <= handle;
+                                    let near_bottom = (pos.y - bottom).abs() <= handle;
+                                    self.last_pointer_pos = Some(pos);
+                                    // polygons are reshaped through their vertices, groups only move
+                                    if b.is_polygon() || self.selection.len() > 1 { self.drag_mode = DragMode::Moving; }
+                                    else if near_left && near_top { self.drag_mode = DragMode::Resizing(ResizeHandle::TL); }
+                                    else if near_right && near_top { self.drag_mode = DragMode::Resizing(ResizeHandle::TR); }
+                                    else if near_left && near_bottom { self.drag_mode = DragMode::Resizing(ResizeHandle::BL); }
+                                    else if near_right && near_bottom { self.drag_mode = DragMode::Resizing(ResizeHandle::BR); }
+                                    // edges change one dimension
+                                    else if near_top { self.drag_mode = DragMode::Resizing(ResizeHandle::Top); }
+                                    else if near_bottom { self.drag_mode = DragMode::Resizing(ResizeHandle::Bottom); }
+                                    else if near_left { self.drag_mode = DragMode::Resizing(ResizeHandle::Left); }
+                                    else if near_right { self.drag_mode = DragMode::Resizing(ResizeHandle::Right); }
+                                    else { self.drag_mode = DragMode::Moving; }
+                                } else if self.shape_mode == ShapeMode::Polygon {
+                                    self.draft_polygon.push(to_ratio(&img_rect, pos));
+                                } else {
+                                    self.drag_mode = DragMode::Creating;
+                                    if let Some(p) = pointer.interact_pos() {
+                                        self.dragging = true;
+                                        self.drag_start = p;
+                                        self.drag_end = p;
+                                        self.begin_edit();
+                                    }
                                 }
                             }
                         }
                     }
                 }
 
+                // right-click a vertex of the selected polygon to delete it
+                if pointer.secondary_clicked() && let Some(pos) = pointer.interact_pos() && let Some(sel) = self.selected_box {
+                    let handle = self.click_tolerance.max(6.0);
+                    let hit = self.boxes.get(sel).filter(|b| b.polygon.len() > 3 && !b.rotated)
+                        .and_then(|b| b.polygon.iter().position(|p| to_screen(&img_rect, *p).distance(pos) <= handle));
+                    if let Some(v) = hit {
+                        self.begin_edit();
+                        self.boxes[sel].polygon.remove(v);
+                        self.boxes[sel].fit_to_polygon();
+                        self.commit_edit("Delete vertex");
+                    }
+                }
+
                 // handle drag updates
                 if self.drag_mode != DragMode::None {
-                    if self.drag_mode == DragMode::Creating {
+                    if self.drag_mode == DragMode::Creating || self.drag_mode == DragMode::Selecting {
                         if let Some(pos) = pointer.interact_pos() { self.drag_end = pos; }
                     } else if self.drag_mode == DragMode::Moving {
                         if pointer.primary_down() {
@@ -565,19 +2291,36 @@ impl eframe::App for AppState {
                                 if let Some(last) = self.last_pointer_pos {
                                     let dx = (pos.x - last.x) / img_rect.width();
                                     let dy = (pos.y - last.y) / img_rect.height();
-                                    if let Some(idx) = self.selected_box {
-                                        if let Some(b) = self.boxes.get_mut(idx) {
-                                            b.cx = (b.cx + dx).clamp(0.0, 1.0);
-                                            b.cy = (b.cy + dy).clamp(0.0, 1.0);
-                                        }
-                                    }
+                                    self.move_selection(dx, dy);
                                     self.last_pointer_pos = Some(pos);
                                 } else {
                                     self.last_pointer_pos = pointer.interact_pos();
                                 }
                             }
                         }
+                    } else if let DragMode::Vertex(v) = self.drag_mode {
+                        if pointer.primary_down() && let Some(pos) = pointer.interact_pos()
+                            && let Some(b) = self.selected_box.and_then(|i| self.boxes.get_mut(i)) && v < b.polygon.len() {
+                            if b.rotated {
+                                resize_rotated(b, v, pos, &img_rect);
+                            } else {
+                                b.polygon[v] = to_ratio(&img_rect, pos);
+                            }
+                            b.fit_to_polygon();
+                        }
+                    } else if let DragMode::Keypoint(k) = self.drag_mode {
+                        if pointer.primary_down() && let Some(pos) = pointer.interact_pos() && pos.distance(self.drag_start) > 2.0
+                            && let Some(idx) = self.selected_box {
+                            self.place_keypoint(idx, k, to_ratio(&img_rect, pos));
+                        }
+                    } else if self.drag_mode == DragMode::Rotating {
+                        if pointer.primary_down() && let Some(pos) = pointer.interact_pos()
+                            && let Some(b) = self.selected_box.and_then(|i| self.boxes.get_mut(i)) {
+                            rotate_towards(b, pos, &img_rect);
+                        }
                     } else if let DragMode::Resizing(corner) = self.drag_mode {
+                        // with Alt the opposite edge mirrors the dragged one, resizing around the center
+                        let from_center = ctx.input(|i| i.modifiers.alt);
                         if pointer.primary_down() {
                             if let Some(pos) = pointer.interact_pos() {
                                 // compute opposite corner fixed, and new coords
@@ -599,12 +2342,24 @@ impl eframe::App for AppState {
                                             let y = ((pos.y - img_top) / img_height).clamp(0.0, 1.0);
                                             (x, y)
                                         };
-                                        let (new_left, new_top, new_right, new_bottom) = match corner {
-                                            ResizeCorner::TL => (rx, ry, right, bottom),
-                                            ResizeCorner::TR => (left, ry, rx, bottom),
-                                            ResizeCorner::BL => (rx, top, right, ry),
-                                            ResizeCorner::BR => (left, top, rx, ry),
+                                        let (mut new_left, mut new_top, mut new_right, mut new_bottom) = match corner {
+                                            ResizeHandle::TL => (rx, ry, right, bottom),
+                                            ResizeHandle::TR => (left, ry, rx, bottom),
+                                            ResizeHandle::BL => (rx, top, right, ry),
+                                            ResizeHandle::BR => (left, top, rx, ry),
+                                            ResizeHandle::Top => (left, ry, right, bottom),
+                                            ResizeHandle::Bottom => (left, top, right, ry),
+                                            ResizeHandle::Left => (rx, top, right, bottom),
+                                            ResizeHandle::Right => (left, top, rx, bottom),
                                         };
+                                        if from_center && corner.moves_x() {
+                                            let half = (rx - b.cx).abs().min(b.cx).min(1.0 - b.cx);
+                                            (new_left, new_right) = (b.cx - half, b.cx + half);
+                                        }
+                                        if from_center && corner.moves_y() {
+                                            let half = (ry - b.cy).abs().min(b.cy).min(1.0 - b.cy);
+                                            (new_top, new_bottom) = (b.cy - half, b.cy + half);
+                                        }
                                         // normalize
                                         let nl = new_left.min(new_right);
                                         let nr = new_left.max(new_right);
@@ -625,14 +2380,38 @@ impl eframe::App for AppState {
 
                 // handle release
                 if pointer.primary_released() && self.drag_mode != DragMode::None {
+                    if let DragMode::Keypoint(k) = self.drag_mode
+                        && pointer.interact_pos().is_none_or(|p| p.distance(self.drag_start) <= 2.0)
+                        && let Some(kp) = self.selected_box.and_then(|i| self.boxes.get_mut(i)).and_then(|b| b.keypoints.get_mut(k)) {
+                        kp.v = if kp.v == 2 { 1 } else { 2 };
+                    }
                     if self.drag_mode == DragMode::Creating {
                         self.dragging = false;
                         self.add_box_from_drag(img_rect);
-                        let _ = self.save_annotations_for_current();
-                    } else {
-                        // moving or resizing ended, save
-                        let _ = self.save_annotations_for_current();
                     }
+                    if self.drag_mode == DragMode::Selecting {
+                        // boxes entirely inside the band join the selection
+                        let band = Rect::from_two_pos(self.drag_start, self.drag_end);
+                        let inside: Vec<usize> = self.boxes.iter().enumerate()
+                            .filter(|(i, _)| self.box_pickable(*i))
+                            .filter(|(_, b)| band.contains_rect(Rect::from_min_max(to_screen(&img_rect, (b.left(), b.top())), to_screen(&img_rect, (b.right(), b.bottom())))))
+                            .map(|(i, _)| i)
+                            .collect();
+                        for i in inside {
+                            self.selection.insert(i);
+                            self.selected_box.get_or_insert(i);
+                        }
+                    }
+                    let label = match self.drag_mode {
+                        DragMode::Creating => "Create box",
+                        DragMode::Moving if self.selection.len() > 1 => "Move boxes",
+                        DragMode::Resizing(_) => "Resize box",
+                        DragMode::Vertex(_) => "Edit polygon",
+                        DragMode::Rotating => "Rotate box",
+                        DragMode::Keypoint(_) => "Edit keypoint",
+                        _ => "Move box",
+                    };
+                    self.commit_edit(label);
                     self.drag_mode = DragMode::None;
                     self.last_pointer_pos = None;
                 }
@@ -640,12 +2419,43 @@ impl eframe::App for AppState {
                 // draw boxes
                 let painter = ui.painter();
                 for (i, b) in self.boxes.iter().enumerate() {
+                    if !self.box_visible(i) { continue; }
+                    let color = self.class_color(&b.class_name);
+                    // the rest of a multi-selection is highlighted without handles
+                    let stroke = if self.selection.contains(&i) { Stroke::new(3.0, Color32::from_rgb(255, 50, 50)) } else { Stroke::new(2.0, color) };
+                    let fill = Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), (self.fill_opacity * 255.0).round() as u8);
                     let left = img_rect.left() + (b.cx - b.w / 2.0) * img_rect.width();
                     let top = img_rect.top() + (b.cy - b.h / 2.0) * img_rect.height();
                     let right = left + b.w * img_rect.width();
                     let bottom = top + b.h * img_rect.height();
                     let r = Rect::from_min_max(Pos2::new(left, top), Pos2::new(right, bottom));
-                    if Some(i) == self.selected_box {
+                    if b.is_polygon() {
+                        let pts: Vec<Pos2> = b.polygon.iter().map(|p| to_screen(&img_rect, *p)).collect();
+                        // egui only fills convex shapes
+                        if fill.a() > 0 && is_convex(&pts) {
+                            painter.add(egui::Shape::convex_polygon(pts.clone(), fill, Stroke::NONE));
+                        }
+                        if Some(i) == self.selected_box && b.rotated {
+                            painter.add(egui::Shape::closed_line(pts.clone(), Stroke::new(3.0, Color32::from_rgb(255, 50, 50))));
+                            for p in &pts {
+                                painter.rect_filled(Rect::from_center_size(*p, Vec2::splat(12.0)), 0.0, Color32::WHITE);
+                            }
+                            if let Some(h) = rotation_handle(&pts) {
+                                painter.line_segment([pts[0].lerp(pts[1], 0.5), h], Stroke::new(2.0, Color32::WHITE));
+                                painter.circle_filled(h, 6.0, Color32::from_rgb(255, 200, 50));
+                            }
+                        } else if Some(i) == self.selected_box {
+                            painter.add(egui::Shape::closed_line(pts.clone(), Stroke::new(3.0, Color32::from_rgb(255, 50, 50))));
+                            // vertex handles, and smaller ones at edge midpoints for inserting vertices
+                            for (k, p) in pts.iter().enumerate() {
+                                painter.circle_filled(*p, 5.0, Color32::WHITE);
+                                painter.circle_filled(p.lerp(pts[(k + 1) % pts.len()], 0.5), 3.0, Color32::LIGHT_GRAY);
+                            }
+                        } else {
+                            painter.add(egui::Shape::closed_line(pts, stroke));
+                        }
+                    } else if Some(i) == self.selected_box {
+                        painter.rect_filled(r, 0.0, fill);
                         painter.rect_stroke(r, 0.0, Stroke::new(3.0, Color32::from_rgb(255, 50, 50)));
                         // draw corner handles
                         let hs = 6.0;
@@ -653,12 +2463,41 @@ impl eframe::App for AppState {
                         painter.rect_filled(Rect::from_min_max(Pos2::new(right-hs, top-hs), Pos2::new(right+hs, top+hs)), 0.0, Color32::WHITE);
                         painter.rect_filled(Rect::from_min_max(Pos2::new(left-hs, bottom-hs), Pos2::new(left+hs, bottom+hs)), 0.0, Color32::WHITE);
                         painter.rect_filled(Rect::from_min_max(Pos2::new(right-hs, bottom-hs), Pos2::new(right+hs, bottom+hs)), 0.0, Color32::WHITE);
+                        // smaller edge handles change one dimension
+                        for p in [r.center_top(), r.center_bottom(), r.left_center(), r.right_center()] {
+                            painter.rect_filled(Rect::from_center_size(p, Vec2::splat(8.0)), 0.0, Color32::LIGHT_GRAY);
+                        }
                     } else {
-                        painter.rect_stroke(r, 0.0, Stroke::new(2.0, Color32::from_rgb(200, 100, 50)));
+                        painter.rect_filled(r, 0.0, fill);
+                        painter.rect_stroke(r, 0.0, stroke);
+                    }
+                    // show class name and the id it is written with
+                    let label = match self.dataset.classes.file_id(&b.class_name) {
+                        Some(id) => format!("{}:{}", id, &b.class_name),
+                        None => format!("?:{}", &b.class_name),
+                    };
+                    painter.text(Pos2::new(left + 2.0, top + 2.0), egui::Align2::LEFT_TOP, label, egui::TextStyle::Body.resolve(ui.style()), Color32::WHITE);
+                }
+
+                // keypoints and skeleton edges; hidden keypoints are drawn hollow
+                let edges = self.dataset.skeleton.as_ref().map(|s| s.edges.clone()).unwrap_or_default();
+                for (i, b) in self.boxes.iter().enumerate() {
+                    if !self.box_visible(i) { continue; }
+                    let kp = |k: usize| b.keypoints.get(k).filter(|p| p.v > 0).map(|p| to_screen(&img_rect, (p.x, p.y)));
+                    for &(a, c) in &edges {
+                        if let (Some(p), Some(q)) = (kp(a), kp(c)) {
+                            painter.line_segment([p, q], Stroke::new(2.0, Color32::from_rgb(100, 200, 255)));
+                        }
+                    }
+                    let r = if Some(i) == self.selected_box { 5.0 } else { 3.5 };
+                    for (k, p) in b.keypoints.iter().enumerate() {
+                        let Some(pos) = kp(k) else { continue };
+                        if p.v == 2 {
+                            painter.circle_filled(pos, r, Color32::from_rgb(255, 230, 80));
+                        } else {
+                            painter.circle_stroke(pos, r, Stroke::new(2.0, Color32::from_rgb(255, 230, 80)));
+                        }
                     }
-                    // show class name and id
-                    let class_id = self.classes.iter().position(|c| c==&b.class_name).unwrap_or(0);
-                    painter.text(Pos2::new(left + 2.0, top + 2.0), egui::Align2::LEFT_TOP, format!("{}:{}", class_id, &b.class_name), egui::TextStyle::Body.resolve(&ui.style()), Color32::WHITE);
                 }
 
                 if self.dragging && self.drag_mode == DragMode::Creating {
@@ -670,66 +2509,108 @@ impl eframe::App for AppState {
                     painter.rect_stroke(r, 0.0, Stroke::new(2.0, Color32::from_rgb(100, 200, 200)));
                 }
 
+                if self.drag_mode == DragMode::Selecting {
+                    let band = Rect::from_two_pos(self.drag_start, self.drag_end);
+                    painter.rect(band, 0.0, Color32::from_rgba_unmultiplied(100, 200, 200, 30), Stroke::new(1.0, Color32::from_rgb(100, 200, 200)));
+                }
+
+                if !self.draft_polygon.is_empty() {
+                    let mut pts: Vec<Pos2> = self.draft_polygon.iter().map(|p| to_screen(&img_rect, *p)).collect();
+                    for p in &pts { painter.circle_filled(*p, 4.0, Color32::from_rgb(100, 200, 200)); }
+                    if let Some(hover) = pointer.hover_pos() { pts.push(hover); }
+                    painter.add(egui::Shape::line(pts, Stroke::new(2.0, Color32::from_rgb(100, 200, 200))));
+                }
+
                 let tools_pos = Pos2::new(img_rect.right() - 10.0, img_rect.top() + 10.0);
                 egui::Area::new("tools_area").fixed_pos(tools_pos).show(ctx, |ui| {
                     ui.vertical(|ui| {
-                        if ui.button("Delete Selected Box").clicked() {
-                            if let Some(idx) = self.selected_box {
-                                if idx < self.boxes.len() {
-                                    self.push_history();
-                                    self.boxes.remove(idx);
-                                    self.selected_box = None;
-                                    let _ = self.save_annotations_for_current();
-                                }
-                            }
+                        let boxes = if self.selection.len() > 1 { format!("{} Boxes", self.selection.len()) } else { "Box".to_owned() };
+                        if ui.button(format!("Delete Selected {}", boxes)).clicked() {
+                            self.delete_selection();
                         }
 
-                        if ui.button("Duplicate Selected Box").clicked() {
-                            if let Some(idx) = self.selected_box {
-                                self.push_history();
-                                if let Some(b) = self.boxes.get(idx) {
-                                    self.boxes.push(b.clone());
-                                    let _ = self.save_annotations_for_current();
+                        if ui.button(format!("Duplicate Selected {}", boxes)).clicked() {
+                            self.duplicate_selection();
+                        }
+
+                        if self.selection.len() > 1 {
+                            ui.horizontal(|ui| {
+                                ui.label("Align:");
+                                for (align, text) in [(Align::Left, "Left"), (Align::Center, "Center"), (Align::Right, "Right"), (Align::Top, "Top"), (Align::Middle, "Middle"), (Align::Bottom, "Bottom")] {
+                                    if ui.small_button(text).clicked() { self.align_selection(align); }
                                 }
-                            }
+                            });
+                        }
+
+                        let plain = self.dataset.obb && self.dataset.kpt_shape().is_none_or(|k| k.count == 0) && self.selected_box.and_then(|i| self.boxes.get(i)).is_some_and(|b| !b.is_polygon());
+                        if ui.add_enabled(plain, egui::Button::new("Make Selected Box Rotated")).clicked() && let Some(idx) = self.selected_box {
+                            self.begin_edit();
+                            self.boxes[idx] = self.boxes[idx].to_rotated();
+                            self.commit_edit("Make box rotated");
                         }
 
                         ui.separator();
                         ui.label("Selected box controls:");
-                        if let Some(idx) = self.selected_box {
-                            // Move push_history before any borrow of self.boxes
-                            self.push_history();
-                            let mut sel = self.classes.iter().position(|c| {
-                                if let Some(b) = self.boxes.get(idx) {
-                                    c == &b.class_name
-                                } else {
-                                    false
+                        if let Some(idx) = self.selected_box && !self.dataset.classes.is_empty() {
+                            if let Some(b) = self.boxes.get(idx) {
+                                if self.selection.len() > 1 {
+                                    ui.label(format!("{} boxes selected (shift-click to add or remove)", self.selection.len()));
                                 }
-                            }).unwrap_or(0);
-                            if let Some(b) = self.boxes.get_mut(idx) {
+                                // the widgets edit a copy of the box with handles; what changed on it is applied to
+                                // every selected box, and only a real change becomes an undo step
+                                let mut edited = b.clone();
                                 // choose class from existing classes (no need to re-type previously used names)
                                 egui::ComboBox::from_id_source("selected_class_combo")
-                                    .selected_text(self.classes[sel].clone())
+                                    .selected_text(b.class_name.as_str())
                                     .show_ui(ui, |ui| {
-                                        for (i, c) in self.classes.iter().enumerate() {
-                                            if ui.selectable_label(i==sel, c).clicked() { sel = i; }
+                                        for c in self.dataset.classes.iter() {
+                                            if ui.selectable_label(c == &b.class_name, c).clicked() { edited.class_name = c.clone(); }
                                         }
                                     });
-                                let mut need_save = false;
-                                if b.class_name != self.classes[sel] {
-                                    let new_class_name = self.classes[sel].clone();
-                                    b.class_name = new_class_name;
-                                    need_save = true;
-                                }
                                 // allow quick reassign to current default class as well
-                                if ui.button("Assign current left-class to selected").clicked() {
-                                    let new_class_name = self.classes[self.cur_class_idx].clone();
-                                    b.class_name = new_class_name;
-                                    need_save = true;
+                                if ui.button("Assign current left-class to selected").clicked() && let Some(name) = self.dataset.classes.name(self.cur_class_idx) {
+                                    edited.class_name = name.to_owned();
+                                }
+                                if self.dataset.format == LabelFormat::Voc {
+                                    ui.checkbox(&mut edited.difficult, "difficult");
+                                    ui.checkbox(&mut edited.truncated, "truncated");
+                                }
+                                if edited != *b {
+                                    let class = (edited.class_name != b.class_name).then_some(edited.class_name);
+                                    let difficult = (edited.difficult != b.difficult).then_some(edited.difficult);
+                                    let truncated = (edited.truncated != b.truncated).then_some(edited.truncated);
+                                    self.begin_edit();
+                                    for i in &self.selection {
+                                        let Some(t) = self.boxes.get_mut(*i) else { continue };
+                                        if let Some(c) = &class { t.class_name = c.clone(); }
+                                        if let Some(d) = difficult { t.difficult = d; }
+                                        if let Some(v) = truncated { t.truncated = v; }
+                                    }
+                                    self.commit_edit(if class.is_some() { "Change class" } else { "Edit flags" });
+                                }
+                            }
+                            if let Some(sk) = &self.dataset.skeleton && !sk.keypoints.is_empty() && self.boxes.get(idx).is_some_and(|b| !b.is_polygon()) {
+                                ui.separator();
+                                ui.label("Keypoints (click one to toggle hidden):");
+                                let mut clear = None;
+                                for (k, name) in sk.keypoints.iter().enumerate() {
+                                    let v = self.boxes[idx].keypoints.get(k).map_or(0, |p| p.v);
+                                    ui.horizontal(|ui| {
+                                        let state = ["-", "hidden", "visible"][v.min(2) as usize];
+                                        if ui.selectable_label(self.placing_keypoint == Some(k), format!("{} ({})", name, state)).clicked() {
+                                            self.placing_keypoint = Some(k);
+                                        }
+                                        if v > 0 && ui.small_button("clear").clicked() { clear = Some(k); }
+                                    });
                                 }
-                                // Save after mutable borrow ends
-                                if need_save {
-                                    let _ = self.save_annotations_for_current();
+                                if ui.button("Place all keypoints").clicked() { self.placing_keypoint = Some(0); }
+                                if let Some(k) = self.placing_keypoint && let Some(name) = sk.keypoints.get(k) {
+                                    ui.label(format!("Click the image to place '{}' (Esc to stop)", name));
+                                }
+                                if let Some(k) = clear {
+                                    self.begin_edit();
+                                    self.boxes[idx].keypoints[k].v = 0;
+                                    self.commit_edit("Clear keypoint");
                                 }
                             }
                         } else {
@@ -738,36 +2619,91 @@ impl eframe::App for AppState {
                     });
                 });
 
+            } else if let Some(entry) = self.dataset.images.get(self.cur_idx) {
+                let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+                match self.loader.as_ref().and_then(|l| l.error(&entry.path)) {
+                    Some(e) => { ui.colored_label(Color32::from_rgb(255, 90, 90), format!("Failed to load {}: {}", name, e)); }
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("Loading {}…", name));
+                        });
+                    }
+                }
             }
         });
     }
 }
 
 
-fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    let mut app = AppState::default();
-    if args.len() >= 2 {
-        let dir = PathBuf::from(&args[1]);
+// per-user config folder: %APPDATA% on Windows, ~/Library/Application Support
+// on macOS, $XDG_CONFIG_HOME or ~/.config elsewhere
+fn config_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|h| h.join("Library").join("Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).or_else(|| home().map(|h| h.join(".config")))
+    };
+    Some(base?.join("img-annotator"))
+}
+
+fn run_gui(dir: Option<PathBuf>) -> ExitCode {
+    let mut app = AppState {
+        settings_path: config_dir().map(|d| d.join(settings::SETTINGS_FILE)),
+        ..Default::default()
+    };
+    app.load_settings();
+    // without a folder argument, reopen the last one
+    let dir = dir.or_else(|| app.settings.last_folder.clone().filter(|d| d.is_dir()));
+    if let Some(dir) = dir {
         if dir.is_dir() {
-            app.load_dir = dir.clone();
-            // load classes first (persisted file)
-            app.load_classes_file();
-            match AppState::load_images_from_dir(&dir) {
-                Ok(list) => { app.images = list; }
+            // loads the persisted classes file first, then scans the images
+            match Dataset::open(&dir) {
+                Ok(ds) => {
+                    // a split folder given on the command line wins over the saved split
+                    let resume_split = ds.root == dir;
+                    app.dataset = Dataset { backup: Some(app.retention), ..ds };
+                    app.restore_project(resume_split);
+                    if app.dataset.may_need_legacy_offset() {
+                        app.status = LEGACY_OFFSET_HINT.to_owned();
+                    }
+                }
                 Err(e) => { eprintln!("Failed to read images: {}", e); }
             }
         } else { eprintln!("Provided path is not a directory: {}", dir.display()); }
     } else { eprintln!("Usage: cargo run -- /path/to/images"); }
 
+    app.keymap_path = config_dir().map(|d| d.join(keymap::KEYMAP_FILE));
+    app.reload_keymap();
+
     let native_options = eframe::NativeOptions::default();
     // set visuals during creation
-    let _ = eframe::run_native("Rust Image Annotator", native_options, Box::new(move |cc| {
+    let res = eframe::run_native(APP_TITLE, native_options, Box::new(move |cc| {
         cc.egui_ctx.set_visuals(egui::Visuals::dark());
         // ensure we load the first image texture now that we have a ctx
         let _ = app.load_current_image_texture(&cc.egui_ctx);
         Box::new(app)
     }));
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => { eprintln!("Failed to start the GUI: {}", e); ExitCode::FAILURE }
+    }
+}
 
-    Ok(())
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match cli::parse(&args) {
+        Ok(cli::Command::Annotate(dir)) => run_gui(dir),
+        Ok(cmd) => cli::run(cmd).unwrap_or_else(|e| {
+            eprintln!("Error: {:#}", e);
+            ExitCode::FAILURE
+        }),
+        Err(e) => {
+            eprintln!("Error: {:#}\n\n{}", e, cli::USAGE);
+            ExitCode::from(2)
+        }
+    }
 }
@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+
+/// A pose keypoint, in ratios. `v` follows YOLO / COCO: 0 not labeled,
+/// 1 labeled but hidden, 2 visible.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Keypoint {
+    pub x: f32,
+    pub y: f32,
+    pub v: u8,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BBox {
+    pub class_name: String,
+    pub cx: f32, // center x (ratio 0..1)
+    pub cy: f32, // center y (ratio)
+    pub w: f32,  // width (ratio)
+    pub h: f32,  // height (ratio)
+    // Pascal VOC flags; not stored in YOLO files
+    pub difficult: bool,
+    pub truncated: bool,
+    /// Vertices (ratios) of a segmentation polygon; empty for a plain box.
+    /// For a polygon `cx`/`cy`/`w`/`h` are its bounding box.
+    pub polygon: Vec<(f32, f32)>,
+    /// `polygon` holds the four corners of a rotated box (YOLO-OBB).
+    pub rotated: bool,
+    /// Pose keypoints in skeleton order; empty when the box has none.
+    pub keypoints: Vec<Keypoint>,
+}
+
+impl BBox {
+    pub fn new(class_name: impl Into<String>, cx: f32, cy: f32, w: f32, h: f32) -> Self {
+        Self { class_name: class_name.into(), cx, cy, w, h, ..Default::default() }
+    }
+
+    pub fn from_polygon(class_name: impl Into<String>, polygon: Vec<(f32, f32)>) -> Self {
+        let mut b = Self { class_name: class_name.into(), polygon, ..Default::default() };
+        b.fit_to_polygon();
+        b
+    }
+
+    /// A rotated box from the four corners of a YOLO-OBB line, in order.
+    pub fn from_corners(class_name: impl Into<String>, corners: Vec<(f32, f32)>) -> Self {
+        let mut b = Self::from_polygon(class_name, corners);
+        b.rotated = true;
+        b
+    }
+
+    /// The box as a rotated box with angle 0.
+    pub fn to_rotated(&self) -> Self {
+        let (l, t, r, bt) = (self.left(), self.top(), self.right(), self.bottom());
+        let mut b = Self::from_polygon(self.class_name.clone(), vec![(l, t), (r, t), (r, bt), (l, bt)]);
+        b.difficult = self.difficult;
+        b.truncated = self.truncated;
+        b.rotated = true;
+        b
+    }
+
+    pub fn is_polygon(&self) -> bool {
+        !self.polygon.is_empty()
+    }
+
+    /// Recomputes the bounding box after the polygon changed.
+    pub fn fit_to_polygon(&mut self) {
+        if self.polygon.is_empty() {
+            return;
+        }
+        let (mut x0, mut y0, mut x1, mut y1) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for &(x, y) in &self.polygon {
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x);
+            y1 = y1.max(y);
+        }
+        self.cx = (x0 + x1) / 2.0;
+        self.cy = (y0 + y1) / 2.0;
+        self.w = x1 - x0;
+        self.h = y1 - y0;
+    }
+
+    /// Moves the box together with its polygon and labeled keypoints.
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.cx += dx;
+        self.cy += dy;
+        for p in &mut self.polygon {
+            p.0 += dx;
+            p.1 += dy;
+        }
+        for k in self.keypoints.iter_mut().filter(|k| k.v > 0) {
+            k.x += dx;
+            k.y += dy;
+        }
+    }
+
+    /// Side lengths in pixels of an image of `size`: the two edges from the
+    /// first corner for a rotated box, width and height otherwise.
+    pub fn pixel_sides(&self, size: (u32, u32)) -> (f64, f64) {
+        let (w, h) = (size.0 as f64, size.1 as f64);
+        if self.rotated && self.polygon.len() == 4 {
+            let len = |a: (f32, f32), b: (f32, f32)| ((b.0 - a.0) as f64 * w).hypot((b.1 - a.1) as f64 * h);
+            (len(self.polygon[0], self.polygon[1]), len(self.polygon[1], self.polygon[2]))
+        } else {
+            (self.w as f64 * w, self.h as f64 * h)
+        }
+    }
+
+    pub fn left(&self) -> f32 { self.cx - self.w / 2.0 }
+    pub fn right(&self) -> f32 { self.cx + self.w / 2.0 }
+    pub fn top(&self) -> f32 { self.cy - self.h / 2.0 }
+    pub fn bottom(&self) -> f32 { self.cy + self.h / 2.0 }
+}
+
+/// The boxes of a single image, as stored in its label file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageAnnotations {
+    pub image: PathBuf,
+    pub boxes: Vec<BBox>,
+    /// Pixel size of the image when known (decoded image or VOC `<size>`);
+    /// formats with pixel coordinates read it from the image file otherwise.
+    pub size: Option<(u32, u32)>,
+}
+
+impl ImageAnnotations {
+    pub fn new(image: impl Into<PathBuf>) -> Self {
+        Self { image: image.into(), ..Default::default() }
+    }
+
+    /// `size`, or the dimensions read from the image file header.
+    pub fn pixel_size(&self) -> anyhow::Result<(u32, u32)> {
+        match self.size {
+            Some(s) => Ok(s),
+            None => Ok(image::image_dimensions(&self.image)?),
+        }
+    }
+}
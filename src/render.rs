@@ -0,0 +1,128 @@
+//! Draws the boxes onto copies of the images, for quick visual checks
+//! without the GUI.
+
+use crate::classes::ClassRegistry;
+use crate::dataset::Dataset;
+use crate::model::BBox;
+use crate::skeleton::Skeleton;
+use anyhow::{Context, Result, bail};
+use image::{Rgb, RgbImage};
+use std::fs;
+use std::path::Path;
+
+/// Distinct colors cycled through by class id.
+pub const PALETTE: [[u8; 3]; 10] = [
+    [230, 25, 75], [60, 180, 75], [255, 225, 25], [0, 130, 200], [245, 130, 48],
+    [145, 30, 180], [70, 240, 240], [240, 50, 230], [210, 245, 60], [250, 190, 212],
+];
+
+pub fn class_color(id: usize) -> [u8; 3] {
+    PALETTE[id % PALETTE.len()]
+}
+
+fn draw_rect(img: &mut RgbImage, x0: i64, y0: i64, x1: i64, y1: i64, thickness: i64, color: Rgb<u8>) {
+    let (w, h) = (img.width() as i64, img.height() as i64);
+    let mut put = |x: i64, y: i64| {
+        if x >= 0 && y >= 0 && x < w && y < h {
+            img.put_pixel(x as u32, y as u32, color);
+        }
+    };
+    for t in 0..thickness {
+        for x in x0..=x1 {
+            put(x, y0 + t);
+            put(x, y1 - t);
+        }
+        for y in y0..=y1 {
+            put(x0 + t, y);
+            put(x1 - t, y);
+        }
+    }
+}
+
+// Bresenham line with a square pen
+fn draw_line(img: &mut RgbImage, from: (i64, i64), to: (i64, i64), thickness: i64, color: Rgb<u8>) {
+    let (w, h) = (img.width() as i64, img.height() as i64);
+    let (mut x, mut y) = from;
+    let (dx, dy) = ((to.0 - x).abs(), -(to.1 - y).abs());
+    let (sx, sy) = (if x < to.0 { 1 } else { -1 }, if y < to.1 { 1 } else { -1 });
+    let mut err = dx + dy;
+    loop {
+        for ox in 0..thickness {
+            for oy in 0..thickness {
+                let (px, py) = (x + ox - thickness / 2, y + oy - thickness / 2);
+                if px >= 0 && py >= 0 && px < w && py < h {
+                    img.put_pixel(px as u32, py as u32, color);
+                }
+            }
+        }
+        if (x, y) == to {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Outlines each box (or polygon) in its class color, with its keypoints and
+/// the skeleton edges between labeled ones.
+pub fn draw_boxes(img: &mut RgbImage, boxes: &[BBox], classes: &ClassRegistry, skeleton: Option<&Skeleton>, thickness: u32) {
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    for b in boxes {
+        let color = Rgb(class_color(classes.id_of(&b.class_name).unwrap_or(0)));
+        let kp = |i: usize| b.keypoints.get(i).filter(|k| k.v > 0).map(|k| ((k.x * w).round() as i64, (k.y * h).round() as i64));
+        for &(a, c) in skeleton.map(|s| s.edges.as_slice()).unwrap_or_default() {
+            if let (Some(p), Some(q)) = (kp(a), kp(c)) {
+                draw_line(img, p, q, (thickness as i64 / 2).max(1), color);
+            }
+        }
+        for i in 0..b.keypoints.len() {
+            if let Some(p) = kp(i) {
+                draw_line(img, p, p, thickness as i64 * 3, Rgb([255, 255, 255]));
+            }
+        }
+        if b.is_polygon() {
+            let pts: Vec<(i64, i64)> = b.polygon.iter().map(|(x, y)| ((x * w).round() as i64, (y * h).round() as i64)).collect();
+            for (i, p) in pts.iter().enumerate() {
+                draw_line(img, *p, pts[(i + 1) % pts.len()], thickness as i64, color);
+            }
+            continue;
+        }
+        draw_rect(
+            img,
+            (b.left() * w).round() as i64,
+            (b.top() * h).round() as i64,
+            (b.right() * w).round() as i64 - 1,
+            (b.bottom() * h).round() as i64 - 1,
+            thickness as i64,
+            color,
+        );
+    }
+}
+
+/// Writes every image of `ds` with its boxes drawn into `out`, keeping file
+/// names. `out` can't be the image folder itself. Returns the number of
+/// images written.
+pub fn render(ds: &Dataset, out: &Path) -> Result<usize> {
+    fs::create_dir_all(out)?;
+    let image_dir = ds.image_dir();
+    if fs::canonicalize(out)? == fs::canonicalize(&image_dir)? {
+        bail!("{} is the image folder; rendering there would overwrite the images", out.display());
+    }
+    let mut classes = ds.classes.clone();
+    for entry in &ds.images {
+        let ann = ds.read_annotations(&entry.path, &mut classes)?;
+        let mut img = image::open(&entry.path).with_context(|| format!("decoding {}", entry.path.display()))?.to_rgb8();
+        let thickness = (img.width().max(img.height()) / 400).max(2);
+        draw_boxes(&mut img, &ann.boxes, &classes, ds.skeleton.as_ref(), thickness);
+        let dst = out.join(entry.path.file_name().context("image without a file name")?);
+        img.save(&dst).with_context(|| format!("writing {}", dst.display()))?;
+    }
+    Ok(ds.images.len())
+}
@@ -0,0 +1,124 @@
+//! Settings kept between launches: the user's preferences in `settings.toml`
+//! in their config folder, and where work on a dataset left off in
+//! `_darknet.project` next to its class file.
+//!
+//! Both are TOML; missing keys take their defaults, so the files can be
+//! trimmed by hand.
+
+use crate::atomic;
+use crate::backup::Retention;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+pub const PROJECT_FILE: &str = "_darknet.project";
+
+/// Per-user settings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Screen pixels around a box that still count as clicking it.
+    pub click_tolerance: f32,
+    /// Smallest width or height, in screen pixels, of a new box.
+    pub min_box_pixels: f32,
+    /// Copy the previous image's boxes when Next opens an unlabeled image.
+    pub auto_propagate: bool,
+    /// Write labels only on Save and when leaving an image, not after every edit.
+    pub manual_save: bool,
+    /// Opacity (0 to 1) boxes are filled with in their class color.
+    pub fill_opacity: f32,
+    /// Images decoded ahead on each side of the open one.
+    pub prefetch: usize,
+    /// Earlier versions kept per label or class file; 0 keeps all.
+    pub keep_versions: usize,
+    /// Days earlier versions are kept; 0 keeps them forever.
+    pub keep_days: u64,
+    /// Folder opened when the annotator is started without one.
+    pub last_folder: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            click_tolerance: 8.0,
+            min_box_pixels: 6.0,
+            auto_propagate: false,
+            manual_save: false,
+            fill_opacity: 0.15,
+            prefetch: 2,
+            keep_versions: Retention::default().max_versions,
+            keep_days: Retention::default().max_age_days,
+            last_folder: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads `path`; defaults when it doesn't exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        read_toml(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_toml(path, self)
+    }
+}
+
+/// Per-dataset state. The tolerances are only set by editing the file and
+/// then take precedence over the user's own settings for this dataset.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    /// File name of the image that was open.
+    pub image: Option<String>,
+    /// Split that was open, for an Ultralytics layout.
+    pub split: Option<String>,
+    /// Name of the class new boxes were given.
+    pub class: Option<String>,
+    pub zoom_level: Option<f32>,
+    pub click_tolerance: Option<f32>,
+    pub min_box_pixels: Option<f32>,
+    /// Classes whose boxes are not drawn.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub hidden: BTreeSet<String>,
+    /// Classes whose boxes are drawn but can't be selected.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub locked: BTreeSet<String>,
+    /// RGB colors replacing the palette color of a class.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub colors: BTreeMap<String, [u8; 3]>,
+    /// Four-corner label lines are rotated boxes (YOLO-OBB) rather than
+    /// polygons; the two are written alike.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub obb: bool,
+}
+
+impl ProjectConfig {
+    /// Reads `path`; empty when it doesn't exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        read_toml(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_toml(path, self)
+    }
+}
+
+fn read_toml<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let text = fs::read_to_string(path)?;
+    toml::from_str(&text).with_context(|| format!("reading {}", path.display()))
+}
+
+fn write_toml<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    atomic::write(path, toml::to_string(value)?)
+}
@@ -0,0 +1,171 @@
+//! Keypoint skeleton of a pose dataset: keypoint names, the edges drawn
+//! between them and the left/right pairs swapped when an image is flipped.
+//!
+//! Stored with the Ultralytics data.yaml keys (`kpt_shape`, `flip_idx`, plus
+//! `kpt_names` and `skeleton`), in data.yaml itself or in `_darknet.skeleton`
+//! for a flat folder.
+
+use crate::atomic;
+use crate::yolo::KptShape;
+use anyhow::{Context, Result, bail};
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::Path;
+
+pub const SKELETON_FILE: &str = "_darknet.skeleton";
+// the keys `write_yaml` sets
+const YAML_KEYS: [&str; 4] = ["kpt_shape", "flip_idx", "kpt_names", "skeleton"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skeleton {
+    pub keypoints: Vec<String>,
+    /// Values per keypoint in label files: 2 (`x y`) or 3 (`x y visibility`).
+    pub dims: usize,
+    pub edges: Vec<(usize, usize)>,
+    pub flip_pairs: Vec<(usize, usize)>,
+}
+
+impl Default for Skeleton {
+    fn default() -> Self {
+        Self { keypoints: vec![], dims: 3, edges: vec![], flip_pairs: vec![] }
+    }
+}
+
+impl Skeleton {
+    pub fn kpt_shape(&self) -> KptShape {
+        KptShape { count: self.keypoints.len(), dims: self.dims }
+    }
+
+    /// Ultralytics `flip_idx`: the index each keypoint swaps with.
+    pub fn flip_idx(&self) -> Vec<usize> {
+        let mut idx: Vec<usize> = (0..self.keypoints.len()).collect();
+        for &(a, b) in &self.flip_pairs {
+            if a < idx.len() && b < idx.len() {
+                idx[a] = b;
+                idx[b] = a;
+            }
+        }
+        idx
+    }
+
+    /// Drops keypoint `k`, with the edges and flip pairs using it.
+    pub fn remove_keypoint(&mut self, k: usize) {
+        self.keypoints.remove(k);
+        let fix = |pairs: &mut Vec<(usize, usize)>| {
+            pairs.retain(|&(a, b)| a != k && b != k);
+            for (a, b) in pairs.iter_mut() {
+                if *a > k { *a -= 1; }
+                if *b > k { *b -= 1; }
+            }
+        };
+        fix(&mut self.edges);
+        fix(&mut self.flip_pairs);
+    }
+
+    /// Reads the skeleton keys of a yaml mapping, `None` without `kpt_shape`.
+    pub fn from_yaml(doc: &Mapping) -> Result<Option<Self>> {
+        let Some(shape) = doc.get("kpt_shape") else { return Ok(None) };
+        let nums = |v: &Value| -> Option<Vec<usize>> {
+            v.as_sequence()?.iter().map(|n| n.as_u64().map(|n| n as usize)).collect()
+        };
+        let Some([count, dims]) = nums(shape).and_then(|v| <[usize; 2]>::try_from(v).ok()) else {
+            bail!("kpt_shape must be [keypoints, dims]");
+        };
+        if dims != 2 && dims != 3 {
+            bail!("kpt_shape dims must be 2 or 3, not {}", dims);
+        }
+        let names = match doc.get("kpt_names") {
+            // newer Ultralytics files have one list per class; the first is used for all
+            Some(Value::Mapping(m)) => m.values().next().cloned(),
+            other => other.cloned(),
+        };
+        let mut keypoints: Vec<String> = match names {
+            Some(Value::Sequence(seq)) => seq.iter().map(|v| v.as_str().map(str::to_owned).context("kpt_names must be strings")).collect::<Result<_>>()?,
+            _ => vec![],
+        };
+        keypoints.resize_with(count, String::new);
+        for (i, k) in keypoints.iter_mut().enumerate() {
+            if k.is_empty() {
+                *k = format!("kp{}", i);
+            }
+        }
+        let in_range = |i: usize| if i < count { Ok(i) } else { bail!("keypoint index {} out of range", i) };
+        let mut edges = vec![];
+        if let Some(Value::Sequence(seq)) = doc.get("skeleton") {
+            for e in seq {
+                let Some([a, b]) = nums(e).and_then(|v| <[usize; 2]>::try_from(v).ok()) else {
+                    bail!("skeleton edges must be [from, to] pairs");
+                };
+                edges.push((in_range(a)?, in_range(b)?));
+            }
+        }
+        let mut flip_pairs = vec![];
+        if let Some(v) = doc.get("flip_idx") {
+            let idx = nums(v).context("flip_idx must be a list of indices")?;
+            for (a, b) in idx.into_iter().enumerate() {
+                if a < in_range(b)? {
+                    flip_pairs.push((a, b));
+                }
+            }
+        }
+        Ok(Some(Self { keypoints, dims, edges, flip_pairs }))
+    }
+
+    pub fn write_yaml(&self, doc: &mut Mapping) {
+        let seq = |v: Vec<usize>| Value::Sequence(v.into_iter().map(|n| (n as u64).into()).collect());
+        doc.insert("kpt_shape".into(), seq(vec![self.keypoints.len(), self.dims]));
+        doc.insert("flip_idx".into(), seq(self.flip_idx()));
+        doc.insert("kpt_names".into(), Value::Sequence(self.keypoints.iter().map(|k| k.as_str().into()).collect()));
+        doc.insert("skeleton".into(), Value::Sequence(self.edges.iter().map(|&(a, b)| seq(vec![a, b])).collect()));
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        match serde_yaml::from_str::<Value>(&fs::read_to_string(path)?).with_context(|| format!("parsing {}", path.display()))? {
+            Value::Mapping(m) => Self::from_yaml(&m).with_context(|| format!("reading the skeleton in {}", path.display())),
+            Value::Null => Ok(None),
+            _ => bail!("{} is not a mapping", path.display()),
+        }
+    }
+
+    /// Writes the skeleton keys into the yaml file at `path`, keeping its
+    /// other keys.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut doc = read_mapping(path)?;
+        self.write_yaml(&mut doc);
+        atomic::write(path, serde_yaml::to_string(&doc)?)?;
+        Ok(())
+    }
+
+    /// Drops the skeleton keys from the yaml file at `path`, keeping its
+    /// other keys; a file left empty is deleted.
+    pub fn clear(path: &Path) -> Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+        let mut doc = read_mapping(path)?;
+        for key in YAML_KEYS {
+            doc.remove(key);
+        }
+        if doc.is_empty() {
+            fs::remove_file(path)?;
+        } else {
+            atomic::write(path, serde_yaml::to_string(&doc)?)?;
+        }
+        Ok(())
+    }
+}
+
+// the yaml mapping at `path`, empty when there is no file yet
+fn read_mapping(path: &Path) -> Result<Mapping> {
+    if !path.exists() {
+        return Ok(Mapping::new());
+    }
+    match serde_yaml::from_str::<Value>(&fs::read_to_string(path)?)? {
+        Value::Mapping(m) => Ok(m),
+        Value::Null => Ok(Mapping::new()),
+        _ => bail!("{} is not a mapping", path.display()),
+    }
+}
@@ -0,0 +1,94 @@
+//! Splits an image folder into an Ultralytics `images/<split>` +
+//! `labels/<split>` dataset.
+
+use crate::dataset::{Dataset, LabelFormat, Layout};
+use crate::settings::ProjectConfig;
+use crate::ultralytics;
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SplitOptions {
+    /// Fraction of images that go to `val`.
+    pub val: f64,
+    /// Fraction of images that go to `test`; no `test` split when 0.
+    pub test: f64,
+    /// Seed of the shuffle, so the same folder always splits the same way.
+    pub seed: u64,
+}
+
+impl Default for SplitOptions {
+    fn default() -> Self {
+        Self { val: 0.2, test: 0.0, seed: 0 }
+    }
+}
+
+// splitmix64; good enough for shuffling and keeps the split reproducible
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Copies every image of `ds` into `out/images/<split>` and writes its labels
+/// as YOLO files to `out/labels/<split>`, plus a `data.yaml` with the class
+/// names and the pose skeleton, if any. Returns the number of images per split.
+pub fn split(ds: &Dataset, out: &Path, opts: &SplitOptions) -> Result<Vec<(String, usize)>> {
+    if !(0.0..1.0).contains(&opts.val) || !(0.0..1.0).contains(&opts.test) || opts.val + opts.test >= 1.0 {
+        bail!("val ({}) and test ({}) must be fractions that leave images for train", opts.val, opts.test);
+    }
+    if out.join(ultralytics::IMAGES_DIR).exists() || out.join(ultralytics::DATA_YAML).exists() {
+        bail!("{} already contains a dataset", out.display());
+    }
+
+    let n = ds.images.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    let mut state = opts.seed;
+    for i in (1..n).rev() {
+        let j = (next_random(&mut state) % (i as u64 + 1)) as usize;
+        order.swap(i, j);
+    }
+    let n_val = (n as f64 * opts.val).round() as usize;
+    let n_test = (n as f64 * opts.test).round() as usize;
+    let mut splits = vec![("train".to_owned(), n - n_val - n_test), ("val".to_owned(), n_val)];
+    if opts.test > 0.0 {
+        splits.push(("test".to_owned(), n_test));
+    }
+    for (name, _) in &splits {
+        fs::create_dir_all(ultralytics::split_dir(out, name))?;
+    }
+
+    let mut classes = ds.classes.clone();
+    let mut target = Dataset {
+        root: out.to_path_buf(),
+        layout: Layout::Ultralytics { splits: splits.iter().map(|s| s.0.clone()).collect(), split: String::new() },
+        format: LabelFormat::Yolo,
+        // keypoint columns are only written with a skeleton
+        skeleton: ds.skeleton.clone(),
+        obb: ds.obb,
+        ..Default::default()
+    };
+    let mut next = order.into_iter();
+    for (name, count) in &splits {
+        for idx in next.by_ref().take(*count) {
+            let src = &ds.images[idx].path;
+            let file_name = src.file_name().context("image without a file name")?;
+            let dst = ultralytics::split_dir(out, name).join(file_name);
+            fs::copy(src, &dst).with_context(|| format!("copying {}", src.display()))?;
+            let mut ann = ds.read_annotations(src, &mut classes)?;
+            ann.image = dst;
+            target.classes = classes.clone();
+            target.save_annotations(&ann)?;
+        }
+    }
+    target.classes = classes;
+    target.save_classes_file()?;
+    target.save_skeleton()?;
+    if target.obb {
+        ProjectConfig { obb: true, ..Default::default() }.save(&target.project_file_path())?;
+    }
+    Ok(splits)
+}
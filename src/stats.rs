@@ -0,0 +1,168 @@
+//! Dataset statistics: class balance, boxes per image and box size / aspect
+//! ratio distributions (in pixels of each image).
+
+use crate::dataset::Dataset;
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+
+/// Upper edges of the box size bins (square root of the area, in pixels).
+pub const SIZE_BIN_EDGES: [f64; 8] = [8.0, 16.0, 32.0, 64.0, 128.0, 256.0, 512.0, 1024.0];
+/// Upper edges of the aspect ratio (width / height) bins.
+pub const ASPECT_BIN_EDGES: [f64; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ClassStats {
+    pub name: String,
+    /// Id written to label files, if the class has one.
+    pub id: Option<usize>,
+    pub boxes: usize,
+    /// Images with at least one box of this class.
+    pub images: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Bin {
+    pub label: String,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Distribution {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub bins: Vec<Bin>,
+}
+
+impl Distribution {
+    fn from_samples(mut samples: Vec<f64>, edges: &[f64], fmt: fn(f64) -> String) -> Self {
+        let mut bins: Vec<Bin> = vec![];
+        let mut lower = None;
+        for e in edges {
+            let label = match lower {
+                None => format!("< {}", fmt(*e)),
+                Some(l) => format!("{} - {}", fmt(l), fmt(*e)),
+            };
+            bins.push(Bin { label, count: 0 });
+            lower = Some(*e);
+        }
+        bins.push(Bin { label: format!(">= {}", fmt(*edges.last().unwrap_or(&0.0))), count: 0 });
+        for v in &samples {
+            let i = edges.iter().position(|e| v < e).unwrap_or(edges.len());
+            bins[i].count += 1;
+        }
+        if samples.is_empty() {
+            return Self { bins, ..Default::default() };
+        }
+        samples.sort_by(f64::total_cmp);
+        let n = samples.len();
+        let median = if n % 2 == 1 { samples[n / 2] } else { (samples[n / 2 - 1] + samples[n / 2]) / 2.0 };
+        Self {
+            count: n,
+            min: samples[0],
+            max: samples[n - 1],
+            mean: samples.iter().sum::<f64>() / n as f64,
+            median,
+            bins,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub images: usize,
+    pub unlabeled_images: usize,
+    pub boxes: usize,
+    /// In class list order.
+    pub classes: Vec<ClassStats>,
+    /// `boxes_per_image[n]` is the number of images with exactly `n` boxes.
+    pub boxes_per_image: Vec<usize>,
+    /// Square root of the box area, in pixels.
+    pub box_size_px: Distribution,
+    /// Box width / height, in pixels.
+    pub aspect_ratio: Distribution,
+}
+
+/// Statistics of every image of the dataset, in all splits.
+pub fn compute(ds: &Dataset) -> Result<Stats> {
+    let mut classes = ds.classes.clone();
+    let images = ds.all_images()?;
+    let mut stats = Stats { images: images.len(), ..Default::default() };
+    let mut per_class: Vec<ClassStats> = vec![];
+    let (mut sizes, mut aspects) = (vec![], vec![]);
+    for path in &images {
+        let ann = ds.read_annotations(path, &mut classes)?;
+        if ann.boxes.is_empty() {
+            stats.unlabeled_images += 1;
+        }
+        stats.boxes += ann.boxes.len();
+        if stats.boxes_per_image.len() <= ann.boxes.len() {
+            stats.boxes_per_image.resize(ann.boxes.len() + 1, 0);
+        }
+        stats.boxes_per_image[ann.boxes.len()] += 1;
+        // placeholder classes may have been added while reading
+        per_class.resize_with(classes.len(), Default::default);
+        let mut seen = vec![false; classes.len()];
+        let size = if ann.boxes.is_empty() { None } else { ann.pixel_size().ok() };
+        for b in &ann.boxes {
+            if let Some(size) = size {
+                let (bw, bh) = b.pixel_sides(size);
+                sizes.push((bw * bh).max(0.0).sqrt());
+                if bh > 0.0 {
+                    aspects.push(bw / bh);
+                }
+            }
+            let Some(id) = classes.id_of(&b.class_name) else { continue };
+            per_class[id].boxes += 1;
+            if !seen[id] {
+                seen[id] = true;
+                per_class[id].images += 1;
+            }
+        }
+    }
+    per_class.resize_with(classes.len(), Default::default);
+    for (c, name) in per_class.iter_mut().zip(classes.iter()) {
+        c.name = name.clone();
+        c.id = classes.file_id(name);
+    }
+    stats.classes = per_class;
+    stats.box_size_px = Distribution::from_samples(sizes, &SIZE_BIN_EDGES, |v| format!("{}", v));
+    stats.aspect_ratio = Distribution::from_samples(aspects, &ASPECT_BIN_EDGES, |v| format!("{}", v));
+    Ok(stats)
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_owned() }
+}
+
+/// Writes the statistics as `metric,key,value` rows.
+pub fn write_csv(stats: &Stats, mut w: impl Write) -> Result<()> {
+    writeln!(w, "metric,key,value")?;
+    writeln!(w, "images,,{}", stats.images)?;
+    writeln!(w, "unlabeled_images,,{}", stats.unlabeled_images)?;
+    writeln!(w, "boxes,,{}", stats.boxes)?;
+    for c in &stats.classes {
+        writeln!(w, "class_boxes,{},{}", csv_field(&c.name), c.boxes)?;
+        writeln!(w, "class_images,{},{}", csv_field(&c.name), c.images)?;
+    }
+    for (n, count) in stats.boxes_per_image.iter().enumerate() {
+        writeln!(w, "boxes_per_image,{},{}", n, count)?;
+    }
+    for (metric, d) in [("box_size_px", &stats.box_size_px), ("aspect_ratio", &stats.aspect_ratio)] {
+        for (k, v) in [("min", d.min), ("max", d.max), ("mean", d.mean), ("median", d.median)] {
+            writeln!(w, "{}_{},,{}", metric, k, v)?;
+        }
+        for b in &d.bins {
+            writeln!(w, "{},{},{}", metric, csv_field(&b.label), b.count)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_json(stats: &Stats, w: impl Write) -> Result<()> {
+    serde_json::to_writer_pretty(w, stats)?;
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! Ultralytics dataset layout: `images/<split>` and `labels/<split>` side by
+//! side under a root folder, with class names in `data.yaml`.
+
+use crate::atomic;
+use crate::classes::{ClassRegistry, IdMapping};
+use anyhow::{Context, Result, bail};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub const DATA_YAML: &str = "data.yaml";
+pub const IMAGES_DIR: &str = "images";
+pub const LABELS_DIR: &str = "labels";
+/// Usual split names, listed first and in this order.
+pub const SPLITS: [&str; 3] = ["train", "val", "test"];
+
+/// Whether `dir` holds an `images` folder along with a `labels` folder or a
+/// data.yaml; a plain image folder may well have an `images` sub-folder.
+fn is_root(dir: &Path) -> bool {
+    dir.join(IMAGES_DIR).is_dir() && (dir.join(LABELS_DIR).is_dir() || dir.join(DATA_YAML).exists())
+}
+
+/// Finds the dataset root for `dir`, which may be the root itself or a folder
+/// below its `images` directory. Returns the root and, in the latter case,
+/// the split `dir` belongs to. A data.yaml next to the images themselves is
+/// no root; the folder is opened as a plain one.
+pub fn find_root(dir: &Path) -> Option<(PathBuf, Option<String>)> {
+    if is_root(dir) {
+        return Some((dir.to_path_buf(), None));
+    }
+    for anc in dir.ancestors() {
+        if anc.file_name().is_some_and(|n| n == IMAGES_DIR) {
+            let root = anc.parent()?;
+            if is_root(root) {
+                let split = dir.strip_prefix(anc).ok()
+                    .and_then(|rest| rest.components().next())
+                    .map(|c| c.as_os_str().to_string_lossy().to_string());
+                return Some((root.to_path_buf(), split));
+            }
+        }
+    }
+    None
+}
+
+/// Sub-folders of `root/images`, `train`/`val`/`test` first. A single empty
+/// name stands for images kept directly in `root/images`.
+pub fn splits(root: &Path) -> Vec<String> {
+    let mut found: Vec<String> = fs::read_dir(root.join(IMAGES_DIR))
+        .map(|rd| {
+            rd.flatten()
+                .filter(|e| e.path().is_dir())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    found.sort_by_key(|s| (SPLITS.iter().position(|k| k == s).unwrap_or(SPLITS.len()), s.clone()));
+    if found.is_empty() {
+        found.push(String::new());
+    }
+    found
+}
+
+pub fn split_dir(root: &Path, split: &str) -> PathBuf {
+    let images = root.join(IMAGES_DIR);
+    if split.is_empty() { images } else { images.join(split) }
+}
+
+pub fn labels_dir(root: &Path, split: &str) -> PathBuf {
+    let labels = root.join(LABELS_DIR);
+    if split.is_empty() { labels } else { labels.join(split) }
+}
+
+/// Label path for an image: the last `images` component of the path becomes
+/// `labels`.
+pub fn label_path_for_image(image: &Path) -> PathBuf {
+    let comps: Vec<Component> = image.components().collect();
+    match comps.iter().rposition(|c| c.as_os_str() == IMAGES_DIR) {
+        Some(i) => comps.iter().enumerate()
+            .map(|(j, c)| if j == i { Path::new(LABELS_DIR) } else { Path::new(c.as_os_str()) })
+            .collect(),
+        None => image.to_path_buf(),
+    }
+}
+
+/// Reads `names` from a data.yaml, either a list or an `{id: name}` map.
+/// A map with gaps in its ids becomes a table id mapping.
+pub fn read_classes(path: &Path) -> Result<ClassRegistry> {
+    let text = fs::read_to_string(path)?;
+    let doc: Value = serde_yaml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+    let to_name = |v: &Value| match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+    match doc.get("names") {
+        None | Some(Value::Null) => Ok(ClassRegistry::default()),
+        Some(Value::Sequence(seq)) => {
+            let names = seq.iter().map(|v| to_name(v).context("class names must be strings")).collect::<Result<_>>()?;
+            Ok(ClassRegistry::new(names))
+        }
+        Some(Value::Mapping(map)) => {
+            let mut table = BTreeMap::new();
+            for (k, v) in map {
+                let id = k.as_u64().with_context(|| format!("class id {:?} is not a number", k))? as usize;
+                table.insert(id, to_name(v).context("class names must be strings")?);
+            }
+            let mut classes = ClassRegistry::new(table.values().cloned().collect());
+            if !table.keys().copied().eq(0..table.len()) {
+                classes.set_mapping(IdMapping::Table(table));
+            }
+            Ok(classes)
+        }
+        Some(other) => bail!("unsupported 'names' in {}: {:?}", path.display(), other),
+    }
+}
+
+/// Updates `names` and `nc` in the data.yaml at `root`, keeping its other
+/// keys. A new file also gets `path` and one entry per split.
+pub fn write_classes(root: &Path, classes: &ClassRegistry) -> Result<()> {
+    let path = root.join(DATA_YAML);
+    let mut doc = if path.exists() {
+        match serde_yaml::from_str::<Value>(&fs::read_to_string(&path)?)? {
+            Value::Mapping(m) => m,
+            _ => bail!("{} is not a mapping", path.display()),
+        }
+    } else {
+        let mut m = Mapping::new();
+        m.insert("path".into(), ".".into());
+        for split in splits(root) {
+            let dir = if split.is_empty() { IMAGES_DIR.to_owned() } else { format!("{}/{}", IMAGES_DIR, split) };
+            let key = if split.is_empty() { "train".to_owned() } else { split };
+            m.insert(key.into(), dir.into());
+        }
+        m
+    };
+    let names = match classes.mapping() {
+        m if m.is_identity() => Value::Sequence(classes.iter().map(|c| c.as_str().into()).collect()),
+        _ => {
+            let mut ids: Vec<(usize, &String)> = classes.iter().filter_map(|c| Some((classes.file_id(c)?, c))).collect();
+            ids.sort();
+            Value::Mapping(ids.into_iter().map(|(id, c)| ((id as u64).into(), c.as_str().into())).collect())
+        }
+    };
+    let nc = match &names {
+        Value::Sequence(s) => s.len(),
+        Value::Mapping(m) => m.len(),
+        _ => 0,
+    };
+    doc.insert("nc".into(), (nc as u64).into());
+    doc.insert("names".into(), names);
+    atomic::write(&path, serde_yaml::to_string(&doc)?)?;
+    Ok(())
+}
@@ -0,0 +1,243 @@
+//! Checks label files for problems the loader would otherwise skip or
+//! paper over silently.
+
+use crate::classes::ClassRegistry;
+use crate::dataset::{Dataset, LabelFormat, Layout};
+use crate::model::BBox;
+use crate::{ultralytics, voc, yolo};
+use anyhow::Result;
+use glob::glob;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+// label-looking files that aren't per-image labels
+const NOT_LABEL_FILES: [&str; 1] = ["classes.txt"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// The label file the problem is in.
+    pub file: PathBuf,
+    /// 1-based line number, for line-oriented formats.
+    pub line: Option<usize>,
+    /// The image the label file belongs to; `None` for orphan label files.
+    pub image: Option<PathBuf>,
+    /// Index of the box as loaded (malformed lines don't get one).
+    pub box_index: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sev = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.line {
+            Some(l) => write!(f, "{}:{}: {}: {}", self.file.display(), l, sev, self.message),
+            None => write!(f, "{}: {}: {}", self.file.display(), sev, self.message),
+        }
+    }
+}
+
+// collects the issues of one label file
+struct FileCheck<'a> {
+    issues: &'a mut Vec<Issue>,
+    file: PathBuf,
+    image: PathBuf,
+}
+
+impl FileCheck<'_> {
+    fn push(&mut self, severity: Severity, line: Option<usize>, box_index: Option<usize>, message: String) {
+        self.issues.push(Issue {
+            severity,
+            file: self.file.clone(),
+            line,
+            image: Some(self.image.clone()),
+            box_index,
+            message,
+        });
+    }
+
+    // geometry checks shared by all formats; `seen` holds earlier boxes for
+    // duplicate detection
+    fn check_box(&mut self, b: &BBox, line: Option<usize>, idx: usize, size: Option<(u32, u32)>, seen: &mut Vec<(BBox, Option<usize>)>) {
+        let at = Some(idx);
+        if b.w <= 0.0 || b.h <= 0.0 {
+            self.push(Severity::Error, line, at, format!("zero-area box ({} x {})", b.w, b.h));
+        }
+        let out_of_range: Vec<String> = if b.is_polygon() {
+            b.polygon.iter().enumerate()
+                .filter(|(_, (x, y))| !(0.0..=1.0).contains(x) || !(0.0..=1.0).contains(y))
+                .map(|(i, _)| format!("vertex {}", i + 1))
+                .collect()
+        } else {
+            [b.cx, b.cy, b.w, b.h].iter().zip(yolo::FIELD_NAMES)
+                .filter(|(v, _)| !(0.0..=1.0).contains(*v))
+                .map(|(_, n)| n.to_owned())
+                .collect()
+        };
+        if !out_of_range.is_empty() {
+            self.push(Severity::Error, line, at, format!("{} outside 0..1", out_of_range.join(", ")));
+        } else {
+            // 1e-6 absorbs the rounding of the 6 decimals written to label files
+            let over = [-b.left(), b.right() - 1.0, -b.top(), b.bottom() - 1.0];
+            if over.iter().any(|o| *o > 1e-6) {
+                let msg = match size {
+                    Some((w, h)) => {
+                        let px = [over[0] * w as f32, over[1] * w as f32, over[2] * h as f32, over[3] * h as f32];
+                        format!("box extends past the image by up to {:.1} px", px.iter().cloned().fold(0.0, f32::max))
+                    }
+                    None => "box extends past the image".to_owned(),
+                };
+                self.push(Severity::Warning, line, at, msg);
+            }
+        }
+        let off_image: Vec<String> = b.keypoints.iter().enumerate()
+            .filter(|(_, k)| k.v > 0 && (!(0.0..=1.0).contains(&k.x) || !(0.0..=1.0).contains(&k.y)))
+            .map(|(i, _)| format!("keypoint {}", i + 1))
+            .collect();
+        if !off_image.is_empty() {
+            self.push(Severity::Error, line, at, format!("{} outside 0..1", off_image.join(", ")));
+        }
+        let same = |a: &BBox| a.class_name == b.class_name
+            && (a.cx - b.cx).abs() < 1e-6 && (a.cy - b.cy).abs() < 1e-6
+            && (a.w - b.w).abs() < 1e-6 && (a.h - b.h).abs() < 1e-6
+            && a.polygon.len() == b.polygon.len()
+            && a.polygon.iter().zip(&b.polygon).all(|(p, q)| (p.0 - q.0).abs() < 1e-6 && (p.1 - q.1).abs() < 1e-6);
+        if let Some((_, first)) = seen.iter().find(|(a, _)| same(a)) {
+            let msg = match first {
+                Some(l) => format!("duplicate of the box on line {}", l),
+                None => "duplicate of an earlier box".to_owned(),
+            };
+            self.push(Severity::Warning, line, at, msg);
+        }
+        seen.push((b.clone(), line));
+    }
+}
+
+fn check_yolo(check: &mut FileCheck, text: &str, classes: &ClassRegistry, kpt: Option<yolo::KptShape>, obb: bool, size: Option<(u32, u32)>) {
+    let mut seen = vec![];
+    let mut idx = 0;
+    for (n, line) in text.lines().enumerate() {
+        let ln = Some(n + 1);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let rec = match yolo::parse_line_checked(line, kpt) {
+            Ok(rec) => rec,
+            Err(e) => {
+                check.push(Severity::Error, ln, None, e.to_string());
+                continue;
+            }
+        };
+        let fields = line.split_whitespace().count();
+        if fields > 5 && rec.points.is_empty() && rec.keypoints.is_empty() {
+            check.push(Severity::Warning, ln, Some(idx), format!("{} extra fields ignored", fields - 5));
+        }
+        let class_name = match &rec.class {
+            yolo::ClassToken::Id(id) => match classes.lookup_file_id(*id) {
+                Some(name) => name.to_owned(),
+                None => {
+                    check.push(Severity::Error, ln, Some(idx), format!("unknown class id {}", id));
+                    format!("class_{}", id)
+                }
+            },
+            yolo::ClassToken::Name(name) => {
+                let msg = if classes.id_of(name).is_some() {
+                    format!("class given by name '{}' instead of id", name)
+                } else {
+                    format!("unknown class name '{}'", name)
+                };
+                check.push(Severity::Warning, ln, Some(idx), msg);
+                name.clone()
+            }
+        };
+        let b = rec.to_bbox(class_name, obb);
+        check.check_box(&b, ln, idx, size, &mut seen);
+        idx += 1;
+    }
+}
+
+fn check_voc(check: &mut FileCheck, text: &str, classes: &ClassRegistry, size: Option<(u32, u32)>) {
+    let doc = match voc::parse(text) {
+        Ok(doc) => doc,
+        Err(e) => {
+            check.push(Severity::Error, None, None, format!("{:#}", e));
+            return;
+        }
+    };
+    if let (Some(s), Some(img)) = (doc.size(), size) && s != img {
+        check.push(Severity::Warning, None, None, format!("<size> is {}x{} but the image is {}x{}", s.0, s.1, img.0, img.1));
+    }
+    let Some(size) = size.or(doc.size()) else {
+        check.push(Severity::Error, None, None, "no image size to convert the boxes with".to_owned());
+        return;
+    };
+    let mut seen = vec![];
+    for (idx, b) in doc.to_boxes(size).iter().enumerate() {
+        if classes.id_of(&b.class_name).is_none() {
+            check.push(Severity::Warning, None, Some(idx), format!("class '{}' is not in the class list", b.class_name));
+        }
+        check.check_box(b, None, idx, Some(size), &mut seen);
+    }
+}
+
+// folders label files live in
+fn label_dirs(ds: &Dataset) -> Vec<PathBuf> {
+    match &ds.layout {
+        Layout::Flat => vec![ds.root.clone()],
+        Layout::Ultralytics { splits, .. } => splits.iter().map(|s| ultralytics::labels_dir(&ds.root, s)).collect(),
+    }
+}
+
+/// Validates the label files of every image in the dataset, in all splits,
+/// and reports label files that have no image.
+pub fn validate(ds: &Dataset) -> Result<Vec<Issue>> {
+    let mut issues = vec![];
+    let mut label_files = HashSet::new();
+    for image in ds.all_images()? {
+        let size = image::image_dimensions(&image).ok();
+        for format in LabelFormat::ALL {
+            let annp = ds.annotation_path(&image, format);
+            if !annp.exists() {
+                continue;
+            }
+            label_files.insert(annp.clone());
+            let text = fs::read_to_string(&annp)?;
+            let mut check = FileCheck { issues: &mut issues, file: annp, image: image.clone() };
+            match format {
+                LabelFormat::Yolo => check_yolo(&mut check, &text, &ds.classes, ds.kpt_shape(), ds.obb, size),
+                LabelFormat::Voc => check_voc(&mut check, &text, &ds.classes, size),
+            }
+        }
+    }
+    for dir in label_dirs(ds) {
+        for format in LabelFormat::ALL {
+            let pat = dir.join(format!("*.{}", format.extension())).to_string_lossy().to_string();
+            for p in glob(&pat)?.flatten() {
+                let name = p.file_name().unwrap_or_default().to_string_lossy().to_string();
+                if label_files.contains(&p) || NOT_LABEL_FILES.contains(&name.as_str()) {
+                    continue;
+                }
+                issues.push(Issue {
+                    severity: Severity::Warning,
+                    file: p,
+                    line: None,
+                    image: None,
+                    box_index: None,
+                    message: "label file without an image".to_owned(),
+                });
+            }
+        }
+    }
+    Ok(issues)
+}
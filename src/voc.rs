@@ -0,0 +1,155 @@
+//! Pascal VOC XML label files, one `<image_name>.xml` per image with box
+//! corners in pixels.
+
+use crate::model::BBox;
+use anyhow::{Context, Result, bail};
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VocObject {
+    pub name: String,
+    pub difficult: bool,
+    pub truncated: bool,
+    pub xmin: f64,
+    pub ymin: f64,
+    pub xmax: f64,
+    pub ymax: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VocAnnotation {
+    pub folder: String,
+    pub filename: String,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub objects: Vec<VocObject>,
+}
+
+impl VocAnnotation {
+    /// Converts ratio boxes to pixel corners for an image of `size`.
+    pub fn from_boxes(filename: &str, size: (u32, u32), boxes: &[BBox]) -> Self {
+        let (w, h) = (size.0 as f64, size.1 as f64);
+        let objects = boxes.iter().map(|b| VocObject {
+            name: b.class_name.clone(),
+            difficult: b.difficult,
+            truncated: b.truncated,
+            xmin: (b.left() as f64 * w).round().clamp(0.0, w),
+            ymin: (b.top() as f64 * h).round().clamp(0.0, h),
+            xmax: (b.right() as f64 * w).round().clamp(0.0, w),
+            ymax: (b.bottom() as f64 * h).round().clamp(0.0, h),
+        }).collect();
+        Self { filename: filename.to_owned(), width: size.0, height: size.1, depth: 3, objects, ..Default::default() }
+    }
+
+    /// Converts the pixel corners back to ratios. `size` overrides the
+    /// `<size>` element, which some files leave at 0.
+    pub fn to_boxes(&self, size: (u32, u32)) -> Vec<BBox> {
+        let (w, h) = (size.0 as f64, size.1 as f64);
+        self.objects.iter().map(|o| {
+            let mut b = BBox::new(
+                o.name.clone(),
+                ((o.xmin + o.xmax) / 2.0 / w) as f32,
+                ((o.ymin + o.ymax) / 2.0 / h) as f32,
+                ((o.xmax - o.xmin) / w) as f32,
+                ((o.ymax - o.ymin) / h) as f32,
+            );
+            b.difficult = o.difficult;
+            b.truncated = o.truncated;
+            b
+        }).collect()
+    }
+
+    pub fn size(&self) -> Option<(u32, u32)> {
+        (self.width > 0 && self.height > 0).then_some((self.width, self.height))
+    }
+}
+
+fn parse_flag(s: &str) -> bool {
+    matches!(s.trim(), "1" | "true")
+}
+
+fn parse_num<T: std::str::FromStr>(tag: &str, s: &str) -> Result<T> {
+    s.trim().parse::<T>().ok().with_context(|| format!("<{}> is not a number: '{}'", tag, s))
+}
+
+pub fn parse(xml: &str) -> Result<VocAnnotation> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut ann = VocAnnotation::default();
+    // open element names from the root down
+    let mut stack: Vec<String> = vec![];
+    let mut obj: Option<VocObject> = None;
+    loop {
+        match reader.read_event().context("malformed VOC xml")? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                if name == "object" { obj = Some(VocObject::default()); }
+                stack.push(name);
+            }
+            Event::End(e) => {
+                if e.name().as_ref() == b"object" && let Some(o) = obj.take() {
+                    ann.objects.push(o);
+                }
+                stack.pop();
+            }
+            Event::Text(t) => {
+                // person layout <part>s have their own <name> and <bndbox>
+                if stack.iter().any(|s| s == "part") { continue; }
+                let text = t.unescape()?.to_string();
+                let up = |n: usize| stack.len().checked_sub(n + 1).map(|i| stack[i].as_str()).unwrap_or("");
+                let tag = up(0);
+                match (up(2), up(1), tag, obj.as_mut()) {
+                    ("object", "bndbox", "xmin", Some(o)) => o.xmin = parse_num(tag, &text)?,
+                    ("object", "bndbox", "ymin", Some(o)) => o.ymin = parse_num(tag, &text)?,
+                    ("object", "bndbox", "xmax", Some(o)) => o.xmax = parse_num(tag, &text)?,
+                    ("object", "bndbox", "ymax", Some(o)) => o.ymax = parse_num(tag, &text)?,
+                    (_, "object", "name", Some(o)) => o.name = text,
+                    (_, "object", "difficult", Some(o)) => o.difficult = parse_flag(&text),
+                    (_, "object", "truncated", Some(o)) => o.truncated = parse_flag(&text),
+                    (_, "size", "width", _) => ann.width = parse_num(tag, &text)?,
+                    (_, "size", "height", _) => ann.height = parse_num(tag, &text)?,
+                    (_, "size", "depth", _) => ann.depth = parse_num(tag, &text)?,
+                    (_, "annotation", "filename", _) => ann.filename = text,
+                    (_, "annotation", "folder", _) => ann.folder = text,
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if let Some(o) = ann.objects.iter().find(|o| o.name.is_empty()) {
+        bail!("object at ({}, {}) has no <name>", o.xmin, o.ymin);
+    }
+    Ok(ann)
+}
+
+pub fn to_xml(ann: &VocAnnotation) -> String {
+    let mut s = String::new();
+    s.push_str("<annotation>\n");
+    s.push_str(&format!("\t<folder>{}</folder>\n", escape(&ann.folder)));
+    s.push_str(&format!("\t<filename>{}</filename>\n", escape(&ann.filename)));
+    s.push_str("\t<size>\n");
+    s.push_str(&format!("\t\t<width>{}</width>\n\t\t<height>{}</height>\n\t\t<depth>{}</depth>\n", ann.width, ann.height, ann.depth));
+    s.push_str("\t</size>\n");
+    s.push_str("\t<segmented>0</segmented>\n");
+    for o in &ann.objects {
+        s.push_str("\t<object>\n");
+        s.push_str(&format!("\t\t<name>{}</name>\n", escape(&o.name)));
+        s.push_str("\t\t<pose>Unspecified</pose>\n");
+        s.push_str(&format!("\t\t<truncated>{}</truncated>\n", o.truncated as u8));
+        s.push_str(&format!("\t\t<difficult>{}</difficult>\n", o.difficult as u8));
+        s.push_str("\t\t<bndbox>\n");
+        s.push_str(&format!(
+            "\t\t\t<xmin>{}</xmin>\n\t\t\t<ymin>{}</ymin>\n\t\t\t<xmax>{}</xmax>\n\t\t\t<ymax>{}</ymax>\n",
+            o.xmin, o.ymin, o.xmax, o.ymax
+        ));
+        s.push_str("\t\t</bndbox>\n");
+        s.push_str("\t</object>\n");
+    }
+    s.push_str("</annotation>\n");
+    s
+}
@@ -0,0 +1,136 @@
+//! Notices files that other programs add, delete or change, by scanning
+//! folders on a background thread and comparing sizes and modification times.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Changes {
+    pub added: BTreeSet<PathBuf>,
+    pub removed: BTreeSet<PathBuf>,
+    pub modified: BTreeSet<PathBuf>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// Every changed path.
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.added.iter().chain(&self.removed).chain(&self.modified)
+    }
+
+    fn merge(&mut self, other: Changes) {
+        self.added.extend(other.added);
+        self.removed.extend(other.removed);
+        self.modified.extend(other.modified);
+    }
+}
+
+type Snapshot = HashMap<PathBuf, (SystemTime, u64)>;
+
+// files directly in `dirs`, leaving out hidden ones such as temporary files
+// and the version history
+fn scan(dirs: &[PathBuf]) -> Snapshot {
+    let mut out = Snapshot::new();
+    for dir in dirs {
+        let Ok(rd) = fs::read_dir(dir) else { continue };
+        for entry in rd.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else { continue };
+            if meta.is_file() {
+                out.insert(entry.path(), (meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), meta.len()));
+            }
+        }
+    }
+    out
+}
+
+fn diff(old: &Snapshot, new: &Snapshot) -> Changes {
+    let mut changes = Changes::default();
+    for (path, state) in new {
+        match old.get(path) {
+            None => { changes.added.insert(path.clone()); }
+            Some(s) if s != state => { changes.modified.insert(path.clone()); }
+            Some(_) => {}
+        }
+    }
+    changes.removed = old.keys().filter(|p| !new.contains_key(*p)).cloned().collect();
+    changes
+}
+
+pub struct Watcher {
+    dirs: Vec<PathBuf>,
+    // files as last seen, shared with the scanning thread
+    known: Arc<Mutex<Snapshot>>,
+    changes: Receiver<Changes>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Watcher {
+    /// Scans `dirs` every `interval`, calling `notify` when something changed.
+    pub fn new(dirs: Vec<PathBuf>, interval: Duration, notify: impl Fn() + Send + 'static) -> Self {
+        let known = Arc::new(Mutex::new(scan(&dirs)));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, changes) = mpsc::channel();
+        let (thread_dirs, thread_known, thread_stop) = (dirs.clone(), known.clone(), stop.clone());
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if thread_stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let now = scan(&thread_dirs);
+            let found = {
+                let mut known = thread_known.lock().unwrap();
+                let found = diff(&known, &now);
+                *known = now;
+                found
+            };
+            if !found.is_empty() {
+                if tx.send(found).is_err() {
+                    return;
+                }
+                notify();
+            }
+        });
+        Self { dirs, known, changes, stop }
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Everything that changed since the last call.
+    pub fn poll(&self) -> Changes {
+        let mut all = Changes::default();
+        while let Ok(c) = self.changes.try_recv() {
+            all.merge(c);
+        }
+        all
+    }
+
+    /// Takes `path` as it is now as known, so that a write of our own isn't
+    /// reported as a change.
+    pub fn acknowledge(&self, path: &Path) {
+        let mut known = self.known.lock().unwrap();
+        match fs::metadata(path) {
+            Ok(meta) => { known.insert(path.to_path_buf(), (meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), meta.len())); }
+            Err(_) => { known.remove(path); }
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,212 @@
+//! Reader and writer for YOLO label files, ratios of the image size: detection
+//! lines `<class_id> <cx> <cy> <width> <height>` and segmentation lines
+//! `<class_id> <x1> <y1> <x2> <y2> ...` with three or more vertices. OBB lines
+//! have the four corners of a rotated box in the segmentation form; they are
+//! read as rotated boxes in an OBB dataset and as polygons elsewhere. Pose
+//! lines are detection lines followed by the keypoints, read when the dataset
+//! has a skeleton.
+
+use crate::classes::ClassRegistry;
+use crate::model::{BBox, Keypoint};
+use anyhow::Result;
+use std::io::{BufRead, Write};
+
+/// Class column of a label line: a numeric id, or a class name in legacy files.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClassToken {
+    Id(usize),
+    Name(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub class: ClassToken,
+    pub cx: f32,
+    pub cy: f32,
+    pub w: f32,
+    pub h: f32,
+    /// Polygon vertices of a segmentation line; the fields above are then its
+    /// bounding box.
+    pub points: Vec<(f32, f32)>,
+    pub keypoints: Vec<Keypoint>,
+}
+
+/// Keypoints per pose line, and values per keypoint: 2 (`x y`) or 3
+/// (`x y visibility`). Ultralytics' `kpt_shape`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KptShape {
+    pub count: usize,
+    pub dims: usize,
+}
+
+impl KptShape {
+    pub fn fields(self) -> usize {
+        5 + self.count * self.dims
+    }
+}
+
+impl Record {
+    /// The shape of the line; with `obb`, four vertices are the corners of a
+    /// rotated box.
+    pub fn to_bbox(&self, class_name: String, obb: bool) -> BBox {
+        if self.points.is_empty() {
+            let mut b = BBox::new(class_name, self.cx, self.cy, self.w, self.h);
+            b.keypoints = self.keypoints.clone();
+            b
+        } else if obb && self.points.len() == 4 {
+            BBox::from_corners(class_name, self.points.clone())
+        } else {
+            BBox::from_polygon(class_name, self.points.clone())
+        }
+    }
+}
+
+/// Why a label line couldn't be parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LineError {
+    /// fewer than the 5 required fields
+    FieldCount(usize),
+    /// a coordinate that isn't a number
+    NotANumber { field: &'static str, value: String },
+    /// a line of a pose dataset that is neither a plain box nor has the
+    /// keypoint columns of the skeleton
+    PoseFieldCount { expected: usize, found: usize },
+}
+
+impl std::fmt::Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineError::FieldCount(n) => write!(f, "expected 5 fields, found {}", n),
+            LineError::NotANumber { field, value } => write!(f, "{} is not a number: '{}'", field, value),
+            LineError::PoseFieldCount { expected, found } => write!(f, "expected 5 or {} fields for the skeleton, found {}", expected, found),
+        }
+    }
+}
+
+pub const FIELD_NAMES: [&str; 4] = ["cx", "cy", "width", "height"];
+
+/// Number of fields of a segmentation line with `n` vertices is `1 + 2n`;
+/// fewer vertices than this make a detection line.
+pub const MIN_POLYGON_POINTS: usize = 3;
+
+/// Whether a line with `fields` fields is read as a segmentation polygon.
+pub fn is_polygon_line(fields: usize) -> bool {
+    fields > 2 * MIN_POLYGON_POINTS && fields % 2 == 1
+}
+
+/// Parses one non-blank label line, telling what is wrong with it. With a
+/// `kpt` of one or more keypoints, lines are pose lines or plain boxes and
+/// any other field count is an error; without, lines with an even number of
+/// fields after the fifth have them ignored.
+pub fn parse_line_checked(line: &str, kpt: Option<KptShape>) -> Result<Record, LineError> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 5 {
+        return Err(LineError::FieldCount(parts.len()));
+    }
+    let token = parts[0];
+    let class = match token.parse::<usize>() {
+        Ok(id) => ClassToken::Id(id),
+        Err(_) => ClassToken::Name(token.replace('_', " ")),
+    };
+    let num = |field, value: &str| value.parse::<f32>()
+        .map_err(|_| LineError::NotANumber { field, value: value.to_owned() });
+    let pose = kpt.filter(|k| k.count > 0);
+    if let Some(k) = pose && parts.len() != 5 && parts.len() != k.fields() {
+        return Err(LineError::PoseFieldCount { expected: k.fields(), found: parts.len() });
+    }
+    let is_pose = pose.is_some_and(|k| parts.len() == k.fields());
+    if is_polygon_line(parts.len()) && pose.is_none() {
+        let mut points = vec![];
+        for pair in parts[1..].chunks(2) {
+            points.push((num("x", pair[0])?, num("y", pair[1])?));
+        }
+        let b = BBox::from_polygon("", points);
+        return Ok(Record { class, cx: b.cx, cy: b.cy, w: b.w, h: b.h, points: b.polygon, keypoints: vec![] });
+    }
+    let mut v = [0f32; 4];
+    for (i, field) in FIELD_NAMES.iter().enumerate() {
+        v[i] = parts[i + 1].parse::<f32>()
+            .map_err(|_| LineError::NotANumber { field, value: parts[i + 1].to_owned() })?;
+    }
+    let mut keypoints = vec![];
+    if let Some(k) = kpt.filter(|_| is_pose) {
+        for f in parts[5..].chunks(k.dims) {
+            let (x, y) = (num("keypoint x", f[0])?, num("keypoint y", f[1])?);
+            // without a visibility column, 0 0 marks a missing keypoint
+            let v = match f.get(2) {
+                Some(v) => num("visibility", v)?.round().clamp(0.0, 2.0) as u8,
+                None if x == 0.0 && y == 0.0 => 0,
+                None => 2,
+            };
+            keypoints.push(Keypoint { x, y, v });
+        }
+    }
+    Ok(Record { class, cx: v[0], cy: v[1], w: v[2], h: v[3], points: vec![], keypoints })
+}
+
+/// Parses one label line. Blank lines, lines with fewer than 5 fields and
+/// lines with unparsable numbers yield `None`.
+pub fn parse_line(line: &str, kpt: Option<KptShape>) -> Option<Record> {
+    parse_line_checked(line, kpt).ok()
+}
+
+/// Formats a label line; with `kpt`, boxes get the keypoint columns, unlabeled
+/// ones written as zeros.
+pub fn format_line(class_id: usize, b: &BBox, kpt: Option<KptShape>) -> String {
+    if b.is_polygon() {
+        let coords: Vec<String> = b.polygon.iter().map(|(x, y)| format!("{:.6} {:.6}", x, y)).collect();
+        return format!("{} {}", class_id, coords.join(" "));
+    }
+    let mut line = format!("{} {:.6} {:.6} {:.6} {:.6}", class_id, b.cx, b.cy, b.w, b.h);
+    if let Some(k) = kpt.filter(|k| k.count > 0) {
+        for i in 0..k.count {
+            let p = b.keypoints.get(i).copied().filter(|p| p.v > 0).unwrap_or_default();
+            line.push_str(&format!(" {:.6} {:.6}", p.x, p.y));
+            if k.dims == 3 {
+                line.push_str(&format!(" {}", p.v));
+            }
+        }
+    }
+    line
+}
+
+/// Reads boxes, resolving ids (through the registry's id mapping) and legacy
+/// names against `classes`. Unknown names and ids are added to the registry. Returns the boxes and whether
+/// the registry changed. `obb` reads four-vertex lines as rotated boxes.
+pub fn read_boxes(reader: impl BufRead, classes: &mut ClassRegistry, kpt: Option<KptShape>, obb: bool) -> Result<(Vec<BBox>, bool)> {
+    let mut boxes = vec![];
+    let mut changed = false;
+    for line in reader.lines() {
+        let line = line?;
+        let Some(rec) = parse_line(&line, kpt) else { continue };
+        let class_name = match &rec.class {
+            ClassToken::Id(id) => {
+                let (name, added) = classes.name_for_file_id(*id);
+                changed |= added;
+                name
+            }
+            ClassToken::Name(name) => {
+                changed |= classes.ensure(name).1;
+                name.clone()
+            }
+        };
+        boxes.push(rec.to_bbox(class_name, obb));
+    }
+    Ok((boxes, changed))
+}
+
+/// Writes boxes using class ids. Classes missing from the registry are
+/// appended; the returned flag tells whether that happened.
+pub fn write_boxes(mut writer: impl Write, boxes: &[BBox], classes: &mut ClassRegistry, kpt: Option<KptShape>) -> Result<bool> {
+    let mut changed = false;
+    // resolve every id before writing so a bad class doesn't leave a half-written file
+    let mut out = String::new();
+    for b in boxes {
+        let (cid, added) = classes.file_id_or_insert(&b.class_name)?;
+        changed |= added;
+        out.push_str(&format_line(cid, b, kpt));
+        out.push('\n');
+    }
+    writer.write_all(out.as_bytes())?;
+    Ok(changed)
+}
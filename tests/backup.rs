@@ -0,0 +1,47 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::backup::{self, Retention};
+use std::fs;
+use std::path::Path;
+
+// contents of the kept versions, newest first
+fn kept(root: &Path, file: &Path) -> Vec<String> {
+    backup::versions(root, file).unwrap().iter().map(|v| fs::read_to_string(&v.path).unwrap()).collect()
+}
+
+#[test]
+fn keeps_the_newest_versions_and_restores_one() {
+    let root = temp_dir("backup");
+    let file = root.join("labels").join("a.txt");
+    fs::create_dir_all(file.parent().unwrap()).unwrap();
+    let retention = Retention { max_versions: 3, max_age_days: 0 };
+
+    for i in 0..5 {
+        fs::write(&file, format!("version {}\n", i)).unwrap();
+        backup::save_version(&root, &file, &retention).unwrap();
+    }
+    assert_eq!(kept(&root, &file), ["version 4\n", "version 3\n", "version 2\n"]);
+    assert!(backup::history_dir(&root, &file).starts_with(root.join(backup::HISTORY_DIR)));
+
+    // unchanged contents aren't kept twice
+    backup::save_version(&root, &file, &retention).unwrap();
+    assert_eq!(kept(&root, &file).len(), 3);
+
+    fs::write(&file, "edited\n").unwrap();
+    let oldest = backup::versions(&root, &file).unwrap().pop().unwrap();
+    backup::restore(&root, &file, &oldest, &retention).unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "version 2\n");
+    // the contents before the revert are kept, pushing out the restored version
+    assert_eq!(kept(&root, &file), ["edited\n", "version 4\n", "version 3\n"]);
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn missing_files_have_no_versions() {
+    let root = temp_dir("backup-missing");
+    let file = root.join("a.txt");
+    backup::save_version(&root, &file, &Retention::default()).unwrap();
+    assert!(backup::versions(&root, &file).unwrap().is_empty());
+    let _ = fs::remove_dir_all(&root);
+}
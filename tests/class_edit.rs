@@ -0,0 +1,134 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::Dataset;
+use img_annotator::class_edit::{self, ClassEdit};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// classes a, b, c; x has an `a` and a `c` box, y a `b` box
+fn sample(name: &str) -> (PathBuf, Dataset) {
+    let dir = temp_dir(name);
+    for img in ["x", "y"] {
+        image::RgbImage::new(10, 10).save(dir.join(format!("{}.png", img))).unwrap();
+    }
+    fs::write(dir.join("_darknet.labels"), "a\nb\nc\n").unwrap();
+    fs::write(dir.join("x.txt"), "0 0.5 0.5 0.2 0.2\n2 0.3 0.3 0.1 0.1\n").unwrap();
+    fs::write(dir.join("y.txt"), "1 0.5 0.5 0.2 0.2\n").unwrap();
+    let ds = Dataset::open(&dir).unwrap();
+    (dir, ds)
+}
+
+// class ids of the lines of a YOLO file
+fn ids(path: &Path) -> Vec<usize> {
+    fs::read_to_string(path).unwrap().lines().map(|l| l.split_whitespace().next().unwrap().parse().unwrap()).collect()
+}
+
+fn class_file(dir: &Path) -> String {
+    fs::read_to_string(dir.join("_darknet.labels")).unwrap()
+}
+
+fn check_undone(dir: &Path, ds: &Dataset) {
+    assert_eq!(class_file(dir), "a\nb\nc\n");
+    assert_eq!(ds.classes.names(), ["a", "b", "c"]);
+    assert_eq!(ids(&dir.join("x.txt")), [0, 2]);
+    assert_eq!(ids(&dir.join("y.txt")), [1]);
+}
+
+#[test]
+fn plan_writes_nothing() {
+    let (dir, ds) = sample("class-edit-plan");
+    let plan = class_edit::plan(&ds, &ClassEdit::Delete { name: "a".into() }).unwrap();
+    assert_eq!(plan.files.len(), 2);
+    assert_eq!((plan.changed_boxes(), plan.removed_boxes()), (2, 1));
+    check_undone(&dir, &ds);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rename_keeps_ids() {
+    let (dir, mut ds) = sample("class-edit-rename");
+    let edit = ClassEdit::Rename { from: "b".into(), to: "bee".into() };
+    assert!(class_edit::plan(&ds, &edit).unwrap().files.is_empty());
+    let applied = class_edit::apply(&mut ds, &edit).unwrap();
+    assert_eq!(class_file(&dir), "a\nbee\nc\n");
+    assert_eq!(ids(&dir.join("y.txt")), [1]);
+    assert!(class_edit::apply(&mut ds, &ClassEdit::Rename { from: "a".into(), to: "c".into() }).is_err());
+    class_edit::undo(&mut ds, &applied).unwrap();
+    check_undone(&dir, &ds);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn merge_moves_boxes_and_shifts_ids() {
+    let (dir, mut ds) = sample("class-edit-merge");
+    let applied = class_edit::apply(&mut ds, &ClassEdit::Merge { from: "a".into(), into: "c".into() }).unwrap();
+    assert_eq!(class_file(&dir), "b\nc\n");
+    assert_eq!(ids(&dir.join("x.txt")), [1, 1]);
+    assert_eq!(ids(&dir.join("y.txt")), [0]);
+    class_edit::undo(&mut ds, &applied).unwrap();
+    check_undone(&dir, &ds);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn move_renumbers_classes_in_between() {
+    let (dir, mut ds) = sample("class-edit-move");
+    let applied = class_edit::apply(&mut ds, &ClassEdit::Move { name: "c".into(), to: 0 }).unwrap();
+    assert_eq!(class_file(&dir), "c\na\nb\n");
+    assert_eq!(ids(&dir.join("x.txt")), [1, 0]);
+    assert_eq!(ids(&dir.join("y.txt")), [2]);
+    class_edit::undo(&mut ds, &applied).unwrap();
+    check_undone(&dir, &ds);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn delete_drops_boxes_and_undo_brings_them_back() {
+    let (dir, mut ds) = sample("class-edit-delete");
+    let applied = class_edit::apply(&mut ds, &ClassEdit::Delete { name: "a".into() }).unwrap();
+    assert_eq!(class_file(&dir), "b\nc\n");
+    assert_eq!(ids(&dir.join("x.txt")), [1]);
+    assert_eq!(ids(&dir.join("y.txt")), [0]);
+    class_edit::undo(&mut ds, &applied).unwrap();
+    check_undone(&dir, &ds);
+    // undo removes the id mapping file the edit may have created
+    assert!(!dir.join(img_annotator::classes::ID_MAP_FILE).exists());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn edits_stack_and_undo_in_reverse() {
+    let (dir, mut ds) = sample("class-edit-stack");
+    let first = class_edit::apply(&mut ds, &ClassEdit::Rename { from: "a".into(), to: "ant".into() }).unwrap();
+    let second = class_edit::apply(&mut ds, &ClassEdit::Delete { name: "b".into() }).unwrap();
+    assert_eq!(class_file(&dir), "ant\nc\n");
+    assert_eq!(ids(&dir.join("x.txt")), [0, 1]);
+    assert!(ids(&dir.join("y.txt")).is_empty());
+    class_edit::undo(&mut ds, &second).unwrap();
+    assert_eq!(class_file(&dir), "ant\nb\nc\n");
+    class_edit::undo(&mut ds, &first).unwrap();
+    check_undone(&dir, &ds);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn refuses_while_ids_have_no_class() {
+    let (dir, mut ds) = sample("class-edit-unknown-id");
+    fs::write(dir.join("y.txt"), "1 0.5 0.5 0.2 0.2\n7 0.3 0.3 0.1 0.1\n5 0.3 0.3 0.1 0.1\n7 0.2 0.2 0.1 0.1\n").unwrap();
+    let edit = ClassEdit::Move { name: "c".into(), to: 0 };
+    let err = class_edit::plan(&ds, &edit).unwrap_err().to_string();
+    assert!(err.ends_with(&format!("\n{}: 5, 7", dir.join("y.txt").display())), "{}", err);
+    assert!(class_edit::apply(&mut ds, &edit).is_err());
+    assert_eq!(class_file(&dir), "a\nb\nc\n");
+    assert_eq!(ids(&dir.join("x.txt")), [0, 2]);
+    assert_eq!(ids(&dir.join("y.txt")), [1, 7, 5, 7]);
+
+    // once the ids have placeholder classes, their boxes keep them
+    ds.load_annotations(&dir.join("y.png")).unwrap();
+    assert_eq!(class_file(&dir), "a\nb\nc\nclass_3\nclass_4\nclass_5\nclass_6\nclass_7\n");
+    class_edit::apply(&mut ds, &ClassEdit::Delete { name: "a".into() }).unwrap();
+    assert_eq!(ids(&dir.join("y.txt")), [0, 6, 4, 6]);
+    assert_eq!(ds.classes.names()[6], "class_7");
+    let _ = fs::remove_dir_all(&dir);
+}
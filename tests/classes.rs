@@ -0,0 +1,140 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::classes::{ID_MAP_FILE, IdMapping};
+use img_annotator::{ClassRegistry, Dataset};
+use std::collections::BTreeMap;
+use std::fs;
+
+fn registry(names: &[&str]) -> ClassRegistry {
+    ClassRegistry::new(names.iter().map(|s| s.to_string()).collect())
+}
+
+fn table(entries: &[(usize, &str)]) -> IdMapping {
+    IdMapping::Table(entries.iter().map(|(id, n)| (*id, n.to_string())).collect::<BTreeMap<_, _>>())
+}
+
+fn write_mapping(m: &IdMapping) -> String {
+    let mut buf = vec![];
+    m.write_to(&mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn reads_offset_and_table_mappings() {
+    let m = IdMapping::read_from("# old dataset\n\noffset 1\n".as_bytes()).unwrap();
+    assert_eq!(m, IdMapping::Offset(1));
+    let m = IdMapping::read_from("3 dog\n0 cat\n".as_bytes()).unwrap();
+    assert_eq!(m, table(&[(0, "cat"), (3, "dog")]));
+    assert_eq!(IdMapping::read_from("".as_bytes()).unwrap(), IdMapping::default());
+    // names may have spaces
+    assert_eq!(IdMapping::read_from("7 blue ring\n".as_bytes()).unwrap(), table(&[(7, "blue ring")]));
+}
+
+#[test]
+fn rejects_bad_mappings() {
+    for bad in ["offset x\n", "offset 1\n0 cat\n", "0 cat\n0 dog\n", "cat 0\n", "4\n"] {
+        assert!(IdMapping::read_from(bad.as_bytes()).is_err(), "{:?} was accepted", bad);
+    }
+}
+
+#[test]
+fn mappings_write_back_as_read() {
+    for m in [IdMapping::Offset(2), table(&[(0, "cat"), (5, "blue ring")])] {
+        let text = write_mapping(&m);
+        assert_eq!(IdMapping::read_from(text.as_bytes()).unwrap(), m);
+    }
+    assert_eq!(write_mapping(&IdMapping::Offset(1)), "offset 1\n");
+    assert_eq!(write_mapping(&table(&[(5, "dog"), (0, "cat")])), "0 cat\n5 dog\n");
+}
+
+#[test]
+fn offset_mapping_shifts_ids() {
+    let mut c = registry(&["object", "cat", "dog"]);
+    c.set_mapping(IdMapping::Offset(1));
+    assert_eq!(c.file_id("cat"), Some(0));
+    assert_eq!(c.file_id("dog"), Some(1));
+    // the skipped class has no id of its own
+    assert_eq!(c.file_id("object"), None);
+    assert!(c.file_id_or_insert("object").is_err());
+    assert_eq!(c.lookup_file_id(0), Some("cat"));
+    assert_eq!(c.lookup_file_id(2), None);
+
+    assert_eq!(c.name_for_file_id(1), ("dog".to_owned(), false));
+    assert_eq!(c.name_for_file_id(3), ("class_4".to_owned(), true));
+    assert_eq!(c.names(), ["object", "cat", "dog", "class_3", "class_4"]);
+}
+
+#[test]
+fn table_mapping_looks_ids_up() {
+    let mut c = registry(&["cat"]);
+    c.set_mapping(table(&[(0, "cat"), (4, "dog")]));
+    // classes named by the table join the list
+    assert_eq!(c.names(), ["cat", "dog"]);
+    assert_eq!(c.file_id("dog"), Some(4));
+    assert_eq!(c.lookup_file_id(4), Some("dog"));
+    assert_eq!(c.lookup_file_id(1), None);
+
+    assert_eq!(c.name_for_file_id(9), ("class_9".to_owned(), true));
+    assert_eq!(c.file_id("class_9"), Some(9));
+    // new classes take the id after the highest one
+    assert_eq!(c.file_id_or_insert("bird").unwrap(), (10, true));
+    assert_eq!(c.file_id_or_insert("dog").unwrap(), (4, false));
+}
+
+#[test]
+fn rename_keeps_the_file_id() {
+    let mut c = registry(&["cat", "dog"]);
+    c.set_mapping(table(&[(2, "cat"), (7, "dog")]));
+    c.rename("dog", "puppy").unwrap();
+    assert_eq!(c.file_id("puppy"), Some(7));
+    assert_eq!(c.lookup_file_id(7), Some("puppy"));
+}
+
+#[test]
+fn idmap_file_round_trips_through_the_dataset() {
+    let dir = temp_dir("classes-idmap");
+    image::RgbImage::new(10, 10).save(dir.join("a.png")).unwrap();
+    fs::write(dir.join("_darknet.labels"), "cat\ndog\n").unwrap();
+
+    let mut ds = Dataset::open(&dir).unwrap();
+    // the default mapping isn't stored
+    ds.save_classes_file().unwrap();
+    assert!(!dir.join(ID_MAP_FILE).exists());
+
+    ds.classes.set_mapping(table(&[(10, "cat"), (20, "dog")]));
+    ds.save_classes_file().unwrap();
+    assert_eq!(fs::read_to_string(dir.join(ID_MAP_FILE)).unwrap(), "10 cat\n20 dog\n");
+
+    let mut ds = Dataset::open(&dir).unwrap();
+    assert_eq!(ds.classes.mapping(), &table(&[(10, "cat"), (20, "dog")]));
+    fs::write(dir.join("a.txt"), "20 0.5 0.5 0.2 0.2\n").unwrap();
+    let ann = ds.load_annotations(&dir.join("a.png")).unwrap();
+    assert_eq!(ann.boxes[0].class_name, "dog");
+    ds.save_annotations(&ann).unwrap();
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "20 0.500000 0.500000 0.200000 0.200000\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn old_object_first_class_files_need_offset_one() {
+    let dir = temp_dir("classes-legacy");
+    image::RgbImage::new(10, 10).save(dir.join("a.png")).unwrap();
+    // made by a version that numbered classes from the second line
+    fs::write(dir.join("_darknet.labels"), "object\ncat\ndog\n").unwrap();
+    fs::write(dir.join("a.txt"), "0 0.5 0.5 0.2 0.2\n").unwrap();
+
+    let mut ds = Dataset::open(&dir).unwrap();
+    assert!(ds.may_need_legacy_offset());
+    // without a mapping ids are plain indices
+    assert_eq!(ds.load_annotations(&dir.join("a.png")).unwrap().boxes[0].class_name, "object");
+
+    fs::write(dir.join(ID_MAP_FILE), "offset 1\n").unwrap();
+    let mut ds = Dataset::open(&dir).unwrap();
+    assert!(!ds.may_need_legacy_offset());
+    let ann = ds.load_annotations(&dir.join("a.png")).unwrap();
+    assert_eq!(ann.boxes[0].class_name, "cat");
+    ds.save_annotations(&ann).unwrap();
+    assert!(fs::read_to_string(dir.join("a.txt")).unwrap().starts_with("0 "));
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,189 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::classes::IdMapping;
+use img_annotator::{Dataset, LabelFormat, coco};
+use std::fs;
+use std::path::Path;
+
+fn image(path: &Path, w: u32, h: u32) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    image::RgbImage::new(w, h).save(path).unwrap();
+}
+
+#[test]
+fn exports_pixel_boxes_polygons_and_shifted_category_ids() {
+    let dir = temp_dir("coco-export");
+    image(&dir.join("a.png"), 100, 50);
+    fs::write(dir.join("_darknet.labels"), "cat\ndog\n").unwrap();
+    let labels = "1 0.5 0.5 0.2 0.4\n0 0.1 0.1 0.3 0.1 0.2 0.3\n";
+    fs::write(dir.join("a.txt"), labels).unwrap();
+
+    let ds = Dataset::open(&dir).unwrap();
+    let c = coco::export(&ds).unwrap();
+    assert_eq!(c.images.len(), 1);
+    assert_eq!((c.images[0].file_name.as_str(), c.images[0].width, c.images[0].height), ("a.png", 100, 50));
+    let cats: Vec<(u64, &str)> = c.categories.iter().map(|c| (c.id, c.name.as_str())).collect();
+    assert_eq!(cats, [(1, "cat"), (2, "dog")]);
+
+    let dog = &c.annotations[0];
+    assert_eq!(dog.category_id, 2);
+    assert_eq!(dog.bbox.map(|v| v.round()), [40.0, 15.0, 20.0, 20.0]);
+    assert!((dog.area - 400.0).abs() < 1e-3);
+    assert!(dog.segmentation.polygons().is_empty());
+
+    let cat = &c.annotations[1];
+    assert_eq!(cat.category_id, 1);
+    let poly: Vec<i64> = cat.segmentation.polygons()[0].iter().map(|v| v.round() as i64).collect();
+    assert_eq!(poly, [10, 5, 30, 5, 20, 15]);
+    assert!((cat.area - 100.0).abs() < 1e-2);
+    // export only reads
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), labels);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn category_ids_follow_the_id_mapping() {
+    let dir = temp_dir("coco-offset");
+    image(&dir.join("a.png"), 10, 10);
+    fs::write(dir.join("_darknet.labels"), "object\ncat\ndog\n").unwrap();
+    fs::write(dir.join("_darknet.idmap"), "offset 1\n").unwrap();
+    fs::write(dir.join("a.txt"), "1 0.5 0.5 0.2 0.2\n4 0.5 0.5 0.2 0.2\n").unwrap();
+
+    let ds = Dataset::open(&dir).unwrap();
+    let c = coco::export(&ds).unwrap();
+    // `object` has no file id and so no category; the unknown id 4 fills the
+    // list with placeholders up to it
+    let cats: Vec<(u64, &str)> = c.categories.iter().map(|c| (c.id, c.name.as_str())).collect();
+    assert_eq!(cats, [(1, "cat"), (2, "dog"), (3, "class_3"), (4, "class_4"), (5, "class_5")]);
+    assert_eq!(c.annotations.iter().map(|a| a.category_id).collect::<Vec<_>>(), [2, 5]);
+    assert_eq!(ds.classes.mapping(), &IdMapping::Offset(1));
+    assert_eq!(fs::read_to_string(dir.join("_darknet.labels")).unwrap(), "object\ncat\ndog\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn imports_boxes_polygons_and_run_length_masks() {
+    let dir = temp_dir("coco-import");
+    image(&dir.join("a.png"), 100, 50);
+    let json = r#"{
+        "images": [{"id": 7, "file_name": "a.png", "width": 100, "height": 50}],
+        "categories": [{"id": 3, "name": "dog"}, {"id": 1, "name": "cat"}],
+        "annotations": [
+            {"id": 1, "image_id": 7, "category_id": 3, "bbox": [40, 15, 20, 20]},
+            {"id": 2, "image_id": 7, "category_id": 1, "bbox": [10, 5, 20, 10],
+             "segmentation": [[10, 5, 30, 5, 20, 15]]},
+            {"id": 3, "image_id": 7, "category_id": 1, "bbox": [0, 0, 50, 25], "iscrowd": 1,
+             "segmentation": {"counts": [0, 10, 4990], "size": [50, 100]}}
+        ]
+    }"#;
+    let input = dir.join("in.json");
+    fs::write(&input, json).unwrap();
+
+    let mut ds = Dataset::open(&dir).unwrap();
+    let s = coco::import_file(&input, &mut ds).unwrap();
+    assert_eq!((s.images, s.boxes, s.categories), (1, 3, 2));
+    // categories ordered by id
+    assert_eq!(fs::read_to_string(dir.join("_darknet.labels")).unwrap(), "cat\ndog\n");
+
+    let ann = ds.load_annotations(&dir.join("a.png")).unwrap();
+    assert_eq!(ann.boxes.len(), 3);
+    let dog = &ann.boxes[0];
+    assert_eq!(dog.class_name, "dog");
+    assert!(!dog.is_polygon());
+    assert!((dog.cx - 0.5).abs() < 1e-5 && (dog.cy - 0.5).abs() < 1e-5 && (dog.w - 0.2).abs() < 1e-5 && (dog.h - 0.4).abs() < 1e-5);
+    assert!(ann.boxes[1].is_polygon());
+    assert_eq!(ann.boxes[1].polygon.len(), 3);
+    let mask = &ann.boxes[2];
+    assert!(!mask.is_polygon());
+    assert!((mask.cx - 0.25).abs() < 1e-5 && (mask.w - 0.5).abs() < 1e-5 && (mask.h - 0.5).abs() < 1e-5);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn import_keeps_the_id_mapping() {
+    let dir = temp_dir("coco-import-offset");
+    image(&dir.join("a.png"), 10, 10);
+    fs::write(dir.join("_darknet.labels"), "object\nold\n").unwrap();
+    fs::write(dir.join("_darknet.idmap"), "offset 1\n").unwrap();
+    let json = r#"{"images": [{"id": 1, "file_name": "a.png"}], "categories": [{"id": 1, "name": "cat"}, {"id": 2, "name": "dog"}],
+        "annotations": [{"id": 1, "image_id": 1, "category_id": 2, "bbox": [0, 0, 5, 5]}]}"#;
+    let c: coco::CocoFile = serde_json::from_str(json).unwrap();
+    let mut ds = Dataset::open(&dir).unwrap();
+    coco::import(&c, &mut ds).unwrap();
+    assert_eq!(fs::read_to_string(dir.join("_darknet.labels")).unwrap(), "object\ncat\ndog\n");
+    assert_eq!(fs::read_to_string(dir.join("_darknet.idmap")).unwrap(), "offset 1\n");
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "1 0.250000 0.250000 0.500000 0.500000\n");
+    // export gives the same categories back
+    let again = coco::export(&Dataset::open(&dir).unwrap()).unwrap();
+    assert_eq!(again.annotations[0].category_id, 2);
+
+    // a table takes the category ids
+    let table = temp_dir("coco-import-table");
+    image(&table.join("a.png"), 10, 10);
+    fs::write(table.join("_darknet.labels"), "x\n").unwrap();
+    fs::write(table.join("_darknet.idmap"), "3 x\n").unwrap();
+    let json = r#"{"images": [{"id": 1, "file_name": "a.png"}], "categories": [{"id": 9, "name": "dog"}, {"id": 4, "name": "cat"}],
+        "annotations": [{"id": 1, "image_id": 1, "category_id": 9, "bbox": [0, 0, 5, 5]}]}"#;
+    let c: coco::CocoFile = serde_json::from_str(json).unwrap();
+    let mut ds = Dataset::open(&table).unwrap();
+    let s = coco::import(&c, &mut ds).unwrap();
+    assert_eq!(ds.classes.mapping(), &IdMapping::Table([(3, "cat".to_owned()), (8, "dog".to_owned())].into()));
+    assert_eq!(fs::read_to_string(table.join("a.txt")).unwrap(), "8 0.250000 0.250000 0.500000 0.500000\n");
+    assert_eq!(s.written[0], table.join("a.txt"));
+    let _ = fs::remove_dir_all(&dir);
+    let _ = fs::remove_dir_all(&table);
+}
+
+#[test]
+fn import_merges_categories_with_the_same_name() {
+    let dir = temp_dir("coco-import-duplicates");
+    image(&dir.join("a.png"), 10, 10);
+    let json = r#"{"images": [{"id": 1, "file_name": "a.png"}],
+        "categories": [{"id": 1, "name": "cat"}, {"id": 2, "name": "dog"}, {"id": 3, "name": "cat"}],
+        "annotations": [{"id": 1, "image_id": 1, "category_id": 3, "bbox": [0, 0, 5, 5]},
+                        {"id": 2, "image_id": 1, "category_id": 2, "bbox": [0, 0, 5, 5]},
+                        {"id": 3, "image_id": 1, "category_id": 1, "bbox": [0, 0, 5, 5]}]}"#;
+    let c: coco::CocoFile = serde_json::from_str(json).unwrap();
+    let mut ds = Dataset::open(&dir).unwrap();
+    coco::import(&c, &mut ds).unwrap();
+    assert_eq!(fs::read_to_string(dir.join("_darknet.labels")).unwrap(), "cat\ndog\n");
+    let labels = fs::read_to_string(dir.join("a.txt")).unwrap();
+    let ids: Vec<&str> = labels.lines().filter_map(|l| l.split_whitespace().next()).collect();
+    assert_eq!(ids, ["0", "1", "0"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn import_rejects_unknown_categories() {
+    let dir = temp_dir("coco-unknown");
+    image(&dir.join("a.png"), 10, 10);
+    let json = r#"{"images": [{"id": 1, "file_name": "a.png"}], "categories": [{"id": 1, "name": "cat"}],
+        "annotations": [{"id": 1, "image_id": 1, "category_id": 2, "bbox": [0, 0, 5, 5]}]}"#;
+    let coco: coco::CocoFile = serde_json::from_str(json).unwrap();
+    let mut ds = Dataset::open(&dir).unwrap();
+    let err = coco::import(&coco, &mut ds).unwrap_err();
+    assert!(err.to_string().contains("unknown category 2"), "{}", err);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn export_and_convert_cover_every_split() {
+    let dir = temp_dir("coco-splits");
+    fs::write(dir.join("data.yaml"), "names: [cat]\n").unwrap();
+    for split in ["train", "val"] {
+        image(&dir.join("images").join(split).join(format!("{}.png", split)), 10, 10);
+        fs::create_dir_all(dir.join("labels").join(split)).unwrap();
+        fs::write(dir.join("labels").join(split).join(format!("{}.txt", split)), "0 0.5 0.5 0.2 0.2\n").unwrap();
+    }
+
+    let mut ds = Dataset::open(&dir).unwrap();
+    let c = coco::export(&ds).unwrap();
+    let names: Vec<&str> = c.images.iter().map(|i| i.file_name.as_str()).collect();
+    assert_eq!(names, ["images/train/train.png", "images/val/val.png"]);
+    assert_eq!(c.annotations.len(), 2);
+
+    assert_eq!(ds.convert_all(LabelFormat::Yolo, LabelFormat::Voc).unwrap(), 2);
+    assert!(dir.join("labels/val/val.xml").exists());
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,10 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// Fresh scratch directory for one test, unique per test process.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("img-annotator-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
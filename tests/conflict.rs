@@ -0,0 +1,77 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::conflict::{merge, missing_from, same_boxes};
+use img_annotator::{BBox, Dataset, ImageAnnotations};
+use std::fs;
+
+fn cat() -> BBox { BBox::new("cat", 0.25, 0.25, 0.2, 0.2) }
+fn dog() -> BBox { BBox::new("dog", 0.5, 0.5, 0.2, 0.2) }
+fn bird() -> BBox { BBox::new("bird", 0.75, 0.75, 0.1, 0.1) }
+
+fn moved(mut b: BBox, dx: f32) -> BBox {
+    b.cx += dx;
+    b
+}
+
+#[test]
+fn label_file_rounding_is_not_a_change() {
+    let mut rounded = dog();
+    rounded.cx += 0.0004;
+    assert!(same_boxes(&[cat(), dog()], &[rounded, cat()]));
+    assert!(!same_boxes(&[cat(), dog()], &[cat(), moved(dog(), 0.01)]));
+    assert_eq!(missing_from(&[cat(), dog()], &[dog()]), [cat()]);
+}
+
+#[test]
+fn local_and_outside_edits_are_both_kept() {
+    let base = [cat(), dog()];
+    // we moved the cat, the other program added a bird
+    let ours = [moved(cat(), 0.1), dog()];
+    let theirs = [cat(), dog(), bird()];
+    assert_eq!(merge(&base, &ours, &theirs), [moved(cat(), 0.1), dog(), bird()]);
+}
+
+#[test]
+fn box_changed_on_both_sides_is_kept_twice() {
+    let base = [cat()];
+    let ours = [moved(cat(), 0.1)];
+    let theirs = [moved(cat(), -0.1)];
+    assert_eq!(merge(&base, &ours, &theirs), [moved(cat(), 0.1), moved(cat(), -0.1)]);
+}
+
+#[test]
+fn outside_delete_removes_the_box_unless_we_changed_it() {
+    let base = [cat(), dog()];
+    // we added a bird; the other program deleted the dog
+    assert_eq!(merge(&base, &[cat(), dog(), bird()], &[cat()]), [cat(), bird()]);
+    // we moved the dog it deleted: our version stays
+    assert_eq!(merge(&base, &[cat(), moved(dog(), 0.1)], &[cat()]), [cat(), moved(dog(), 0.1)]);
+    // everything deleted outside, nothing changed here
+    assert!(merge(&base, &base, &[]).is_empty());
+}
+
+#[test]
+fn merge_with_a_label_file_changed_on_disk() {
+    let dir = temp_dir("conflict-disk");
+    let image = dir.join("a.png");
+    image::RgbImage::new(100, 100).save(&image).unwrap();
+    fs::write(dir.join("_darknet.labels"), "cat\ndog\nbird\n").unwrap();
+    let mut ds = Dataset::open(&dir).unwrap();
+    let mut ann = ImageAnnotations::new(&image);
+    ann.boxes = vec![cat(), dog()];
+    ds.save_annotations(&ann).unwrap();
+    let base = ds.load_annotations(&image).unwrap().boxes;
+
+    // another program drops the dog and adds a bird while we move the cat
+    fs::write(dir.join("a.txt"), "0 0.25 0.25 0.2 0.2\n2 0.75 0.75 0.1 0.1\n").unwrap();
+    let ours = vec![moved(base[0].clone(), 0.1), base[1].clone()];
+    let theirs = ds.load_annotations(&image).unwrap().boxes;
+    assert!(!same_boxes(&theirs, &base));
+
+    ann.boxes = merge(&base, &ours, &theirs);
+    ds.save_annotations(&ann).unwrap();
+    let on_disk = ds.load_annotations(&image).unwrap().boxes;
+    assert!(same_boxes(&on_disk, &[moved(cat(), 0.1), bird()]));
+    let _ = fs::remove_dir_all(&dir);
+}
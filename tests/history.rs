@@ -0,0 +1,174 @@
+use img_annotator::BBox;
+use img_annotator::history::History;
+
+fn sample() -> Vec<BBox> {
+    vec![
+        BBox::new("cat", 0.25, 0.25, 0.2, 0.2),
+        BBox::new("dog", 0.5, 0.5, 0.2, 0.2),
+        BBox::new("cat", 0.75, 0.75, 0.2, 0.2),
+    ]
+}
+
+#[test]
+fn create_is_one_entry() {
+    let mut boxes = sample();
+    let mut h = History::default();
+    h.begin(&boxes);
+    boxes.push(BBox::new("dog", 0.1, 0.1, 0.1, 0.1));
+    assert!(h.commit("Create box", &boxes));
+    assert_eq!(h.labels().collect::<Vec<_>>(), ["Create box"]);
+
+    assert!(h.undo(&mut boxes));
+    assert_eq!(boxes, sample());
+    assert!(h.redo(&mut boxes));
+    assert_eq!(boxes.len(), 4);
+    assert_eq!(boxes[3].class_name, "dog");
+}
+
+#[test]
+fn create_below_min_size_records_nothing() {
+    let boxes = sample();
+    let mut h = History::default();
+    h.begin(&boxes);
+    assert!(!h.commit("Create box", &boxes));
+    assert!(!h.can_undo());
+}
+
+#[test]
+fn move_over_many_frames_is_one_entry() {
+    let mut boxes = sample();
+    let mut h = History::default();
+    h.begin(&boxes);
+    for _ in 0..30 {
+        boxes[1].cx += 0.005;
+        boxes[1].cy -= 0.002;
+    }
+    assert!(h.commit("Move box", &boxes));
+    assert_eq!(h.len(), 1);
+
+    h.undo(&mut boxes);
+    assert_eq!(boxes, sample());
+}
+
+#[test]
+fn resize_is_one_entry() {
+    let mut boxes = sample();
+    let mut h = History::default();
+    h.begin(&boxes);
+    for step in 1..=10 {
+        let s = step as f32 * 0.01;
+        boxes[0].w = 0.2 + s;
+        boxes[0].h = 0.2 + s;
+        boxes[0].cx = 0.25 + s / 2.0;
+        boxes[0].cy = 0.25 + s / 2.0;
+    }
+    assert!(h.commit("Resize box", &boxes));
+    assert_eq!(h.len(), 1);
+    let resized = boxes.clone();
+
+    h.undo(&mut boxes);
+    assert_eq!(boxes, sample());
+    h.redo(&mut boxes);
+    assert_eq!(boxes, resized);
+}
+
+#[test]
+fn click_without_moving_records_nothing() {
+    let mut boxes = sample();
+    let mut h = History::default();
+    // a press and release on a box, then the box staying selected for a while
+    for _ in 0..100 {
+        h.begin(&boxes);
+        h.commit("Move box", &boxes);
+    }
+    assert!(h.is_empty());
+    assert!(!h.undo(&mut boxes));
+}
+
+#[test]
+fn drag_back_to_start_records_nothing() {
+    let mut boxes = sample();
+    let mut h = History::default();
+    h.begin(&boxes);
+    boxes[2].cx = 0.9;
+    boxes[2].cx = 0.75;
+    assert!(!h.commit("Move box", &boxes));
+    assert!(h.is_empty());
+}
+
+#[test]
+fn class_change_is_undoable() {
+    let mut boxes = sample();
+    let mut h = History::default();
+    h.begin(&boxes);
+    boxes[1].class_name = "cat".to_owned();
+    assert!(h.commit("Change class", &boxes));
+
+    h.undo(&mut boxes);
+    assert_eq!(boxes[1].class_name, "dog");
+    h.redo(&mut boxes);
+    assert_eq!(boxes[1].class_name, "cat");
+}
+
+#[test]
+fn delete_restores_box_in_place() {
+    let mut boxes = sample();
+    let mut h = History::default();
+    h.begin(&boxes);
+    boxes.remove(1);
+    assert!(h.commit("Delete box", &boxes));
+
+    h.undo(&mut boxes);
+    assert_eq!(boxes, sample());
+}
+
+#[test]
+fn new_edit_clears_redo() {
+    let mut boxes = sample();
+    let mut h = History::default();
+    h.begin(&boxes);
+    boxes[0].cx = 0.3;
+    h.commit("Move box", &boxes);
+    h.undo(&mut boxes);
+    assert!(h.can_redo());
+
+    h.begin(&boxes);
+    boxes[2].class_name = "dog".to_owned();
+    h.commit("Change class", &boxes);
+    assert!(!h.can_redo());
+    assert_eq!(h.labels().collect::<Vec<_>>(), ["Change class"]);
+}
+
+#[test]
+fn go_to_walks_back_and_forth() {
+    let mut boxes = sample();
+    let mut h = History::default();
+    let mut states = vec![boxes.clone()];
+    for i in 0..3 {
+        h.begin(&boxes);
+        boxes[i].cx += 0.05;
+        h.commit("Move box", &boxes);
+        states.push(boxes.clone());
+    }
+    h.go_to(1, &mut boxes);
+    assert_eq!(boxes, states[1]);
+    assert_eq!(h.position(), 1);
+    h.go_to(3, &mut boxes);
+    assert_eq!(boxes, states[3]);
+    h.go_to(0, &mut boxes);
+    assert_eq!(boxes, states[0]);
+}
+
+#[test]
+fn limit_drops_oldest() {
+    let mut boxes = sample();
+    let mut h = History::with_limit(2);
+    for _ in 0..3 {
+        h.begin(&boxes);
+        boxes[0].cx += 0.01;
+        h.commit("Move box", &boxes);
+    }
+    assert_eq!(h.len(), 2);
+    while h.undo(&mut boxes) {}
+    assert!((boxes[0].cx - 0.26).abs() < 1e-6);
+}
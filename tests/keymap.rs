@@ -0,0 +1,53 @@
+mod common;
+
+use common::temp_dir;
+use egui::{Key, KeyboardShortcut, Modifiers};
+use img_annotator::keymap::{self, Action, Keymap};
+use std::fs;
+
+#[test]
+fn parses_and_formats_shortcuts() {
+    assert_eq!(keymap::parse_shortcut("Ctrl+Shift+Z").unwrap(), KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z));
+    // case and spacing don't matter, Cmd is Ctrl
+    assert_eq!(keymap::parse_shortcut("cmd + pagedown").unwrap(), KeyboardShortcut::new(Modifiers::COMMAND, Key::PageDown));
+    assert_eq!(keymap::parse_shortcut("3").unwrap(), KeyboardShortcut::new(Modifiers::NONE, Key::Num3));
+    assert_eq!(keymap::format_shortcut(&keymap::parse_shortcut("Alt+shift+f1").unwrap()), "Alt+Shift+F1");
+
+    assert_eq!(keymap::parse_shortcut("Hyper+A").unwrap_err().to_string(), "unknown modifier 'Hyper' in 'Hyper+A'");
+    assert_eq!(keymap::parse_shortcut("Ctrl+Banana").unwrap_err().to_string(), "unknown key 'Banana'");
+    assert!(keymap::parse_shortcut("Ctrl+").is_err());
+}
+
+#[test]
+fn a_keymap_overrides_only_the_actions_it_names() {
+    let km = Keymap::parse("next_image = \"Space\"\nclass_1 = [\"Q\", \"Shift+1\"]\n").unwrap();
+    assert_eq!(km.shortcuts(Action::NextImage), [KeyboardShortcut::new(Modifiers::NONE, Key::Space)]);
+    assert_eq!(km.shortcuts(Action::Class(0)).len(), 2);
+    assert!(km.binds(Action::Class(0), &KeyboardShortcut::new(Modifiers::SHIFT, Key::Num1)));
+    assert_eq!(km.shortcuts(Action::PrevImage), Keymap::default().shortcuts(Action::PrevImage));
+
+    assert_eq!(Keymap::parse("jump = \"J\"").unwrap_err().to_string(), "unknown action 'jump'");
+    assert_eq!(format!("{:#}", Keymap::parse("save = \"Ctrl+Nope\"").unwrap_err()), "in 'save': unknown key 'Nope'");
+}
+
+#[test]
+fn refuses_a_key_bound_to_two_actions() {
+    // D is next_image by default
+    assert_eq!(Keymap::parse("draw = \"D\"").unwrap_err().to_string(), "'D' is bound to both 'next_image' and 'draw'");
+    assert_eq!(Keymap::parse("save = \"Cmd+Z\"").unwrap_err().to_string(), "'Ctrl+Z' is bound to both 'undo' and 'save'");
+    // moving the key off its default action first is fine
+    assert!(Keymap::parse("draw = \"D\"\nnext_image = \"PageDown\"").is_ok());
+    // a modifier makes it another key
+    assert!(Keymap::parse("draw = \"Shift+D\"").is_ok());
+}
+
+#[test]
+fn the_default_file_reads_back_as_the_defaults() {
+    let dir = temp_dir("keymap-default");
+    let path = dir.join("config").join(keymap::KEYMAP_FILE);
+    assert_eq!(Keymap::load_or_create(&path).unwrap(), Keymap::default());
+    assert_eq!(Keymap::parse(&fs::read_to_string(&path).unwrap()).unwrap(), Keymap::default());
+    fs::write(&path, "undo = \"Ctrl+Y\"\n").unwrap();
+    assert!(Keymap::load_or_create(&path).unwrap_err().to_string().starts_with("reading "));
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::{Dataset, render};
+use std::fs;
+
+#[test]
+fn draws_boxes_into_copies() {
+    let dir = temp_dir("render");
+    image::RgbImage::new(40, 40).save(dir.join("a.png")).unwrap();
+    fs::write(dir.join("_darknet.labels"), "cat\n").unwrap();
+    fs::write(dir.join("a.txt"), "0 0.5 0.5 0.5 0.5\n").unwrap();
+
+    let ds = Dataset::open(&dir).unwrap();
+    let out = dir.join("out");
+    assert_eq!(render::render(&ds, &out).unwrap(), 1);
+    let img = image::open(out.join("a.png")).unwrap().to_rgb8();
+    assert_eq!(img.get_pixel(10, 20).0, render::class_color(0));
+    assert_eq!(img.get_pixel(20, 20).0, [0, 0, 0]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn refuses_to_write_over_the_images() {
+    let dir = temp_dir("render-in-place");
+    let img = dir.join("a.png");
+    image::RgbImage::new(40, 40).save(&img).unwrap();
+    fs::write(dir.join("a.txt"), "0 0.5 0.5 0.5 0.5\n").unwrap();
+    let before = fs::read(&img).unwrap();
+
+    let ds = Dataset::open(&dir).unwrap();
+    // the same folder spelled differently
+    assert!(render::render(&ds, &dir.join(".")).is_err());
+    assert!(render::render(&ds, &dir.join("out").join("..")).is_err());
+    assert_eq!(fs::read(&img).unwrap(), before);
+    let _ = fs::remove_dir_all(&dir);
+}
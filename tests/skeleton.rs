@@ -0,0 +1,108 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::skeleton::SKELETON_FILE;
+use img_annotator::{Dataset, Skeleton};
+use std::fs;
+use std::path::PathBuf;
+
+fn pose_dataset(name: &str) -> (PathBuf, Skeleton) {
+    let dir = temp_dir(name);
+    for name in ["a", "b"] {
+        image::RgbImage::new(20, 20).save(dir.join(format!("{}.png", name))).unwrap();
+        fs::write(dir.join(format!("{}.txt", name)), "0 0.5 0.5 0.4 0.4 0.4 0.4 2 0.6 0.6 1\n").unwrap();
+    }
+    fs::write(dir.join("_darknet.labels"), "person\n").unwrap();
+    let skeleton = Skeleton { keypoints: vec!["left".into(), "right".into()], dims: 3, edges: vec![(0, 1)], flip_pairs: vec![(0, 1)] };
+    skeleton.save(&dir.join(SKELETON_FILE)).unwrap();
+    (dir, skeleton)
+}
+
+#[test]
+fn changing_the_keypoints_rewrites_every_image() {
+    let (dir, mut skeleton) = pose_dataset("skeleton-migrate");
+    let mut ds = Dataset::open(&dir).unwrap();
+    ds.load_annotations(&dir.join("a.png")).unwrap();
+
+    skeleton.remove_keypoint(0);
+    assert_eq!(ds.set_skeleton(Some(skeleton.clone()), Some(0)).unwrap().len(), 2);
+    // the other image, never opened, follows the new skeleton too
+    let b = ds.load_annotations(&dir.join("b.png")).unwrap();
+    assert_eq!(b.boxes[0].keypoints.len(), 1);
+    assert!((b.boxes[0].keypoints[0].x - 0.6).abs() < 1e-4 && b.boxes[0].keypoints[0].v == 1);
+    assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "0 0.500000 0.500000 0.400000 0.400000 0.600000 0.600000 1\n");
+
+    // added keypoints and dropped visibility columns are written everywhere
+    skeleton.keypoints.push("head".into());
+    skeleton.dims = 2;
+    ds.set_skeleton(Some(skeleton.clone()), None).unwrap();
+    let reopened = Dataset::open(&dir).unwrap();
+    assert_eq!(reopened.skeleton.as_ref(), Some(&skeleton));
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "0 0.500000 0.500000 0.400000 0.400000 0.600000 0.600000 0.000000 0.000000\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn edges_alone_leave_the_label_files() {
+    let (dir, mut skeleton) = pose_dataset("skeleton-edges");
+    let mut ds = Dataset::open(&dir).unwrap();
+    skeleton.edges.clear();
+    assert!(ds.set_skeleton(Some(skeleton), None).unwrap().is_empty());
+    assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "0 0.5 0.5 0.4 0.4 0.4 0.4 2 0.6 0.6 1\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn refuses_files_the_keypoints_dont_fit() {
+    let (dir, mut skeleton) = pose_dataset("skeleton-refuse");
+    fs::write(dir.join("b.txt"), "0 0.5 0.5 0.4 0.4 0.4 0.4 2\n").unwrap();
+    let mut ds = Dataset::open(&dir).unwrap();
+    let before = skeleton.clone();
+    skeleton.keypoints.push("head".into());
+    let err = ds.set_skeleton(Some(skeleton), None).unwrap_err().to_string();
+    assert!(err.contains(&format!("{}:1: expected 5 or 11 fields for the skeleton, found 8", dir.join("b.txt").display())), "{}", err);
+    assert_eq!(ds.skeleton.as_ref(), Some(&before));
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "0 0.5 0.5 0.4 0.4 0.4 0.4 2 0.6 0.6 1\n");
+    assert_eq!(Skeleton::load(&dir.join(SKELETON_FILE)).unwrap(), Some(before));
+
+    // a polygon in a folder that gets its first keypoint
+    let plain = temp_dir("skeleton-refuse-polygon");
+    image::RgbImage::new(20, 20).save(plain.join("a.png")).unwrap();
+    fs::write(plain.join("a.txt"), "0 0.1 0.1 0.5 0.1 0.3 0.4\n").unwrap();
+    let mut ds = Dataset::open(&plain).unwrap();
+    let first = Skeleton { keypoints: vec!["head".into()], ..Default::default() };
+    assert!(ds.set_skeleton(Some(first), None).unwrap_err().to_string().ends_with("a.txt:1: polygon"));
+    assert_eq!(ds.skeleton, None);
+    let _ = fs::remove_dir_all(&dir);
+    let _ = fs::remove_dir_all(&plain);
+}
+
+#[test]
+fn removing_the_skeleton_drops_it_from_disk() {
+    let (dir, _) = pose_dataset("skeleton-remove");
+    let mut ds = Dataset::open(&dir).unwrap();
+    assert_eq!(ds.set_skeleton(None, None).unwrap().len(), 2);
+    assert!(!dir.join(SKELETON_FILE).exists());
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "0 0.500000 0.500000 0.400000 0.400000\n");
+    assert_eq!(Dataset::open(&dir).unwrap().skeleton, None);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn removing_the_skeleton_keeps_the_rest_of_data_yaml() {
+    let dir = temp_dir("skeleton-remove-yaml");
+    fs::create_dir_all(dir.join("images/train")).unwrap();
+    image::RgbImage::new(20, 20).save(dir.join("images/train/a.png")).unwrap();
+    let yaml = dir.join("data.yaml");
+    fs::write(&yaml, "names: [person]\n").unwrap();
+    Skeleton { keypoints: vec!["head".into()], ..Default::default() }.save(&yaml).unwrap();
+
+    let mut ds = Dataset::open(&dir).unwrap();
+    assert!(ds.skeleton.is_some());
+    ds.set_skeleton(None, None).unwrap();
+    assert_eq!(fs::read_to_string(&yaml).unwrap(), "names:\n- person\n");
+    let reopened = Dataset::open(&dir).unwrap();
+    assert_eq!(reopened.skeleton, None);
+    assert_eq!(reopened.classes.names(), ["person"]);
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::split::{self, SplitOptions};
+use img_annotator::{Dataset, Layout, Skeleton};
+use std::fs;
+
+#[test]
+fn split_keeps_pose_keypoints() {
+    let dir = temp_dir("split-pose");
+    let src = dir.join("src");
+    fs::create_dir_all(&src).unwrap();
+    for name in ["a", "b", "c"] {
+        image::RgbImage::new(20, 20).save(src.join(format!("{}.png", name))).unwrap();
+        fs::write(src.join(format!("{}.txt", name)), "0 0.5 0.5 0.4 0.4 0.4 0.4 2 0.6 0.6 1\n").unwrap();
+    }
+    fs::write(src.join("_darknet.labels"), "person\n").unwrap();
+    let skeleton = Skeleton { keypoints: vec!["left".into(), "right".into()], dims: 3, edges: vec![(0, 1)], flip_pairs: vec![(0, 1)] };
+    skeleton.save(&src.join(img_annotator::skeleton::SKELETON_FILE)).unwrap();
+
+    let ds = Dataset::open(&src).unwrap();
+    let out = dir.join("out");
+    split::split(&ds, &out, &SplitOptions { val: 0.34, test: 0.0, seed: 1 }).unwrap();
+
+    let mut target = Dataset::open(&out).unwrap();
+    assert_eq!(target.skeleton.as_ref(), Some(&skeleton));
+    let Layout::Ultralytics { splits, .. } = target.layout.clone() else { panic!("not an Ultralytics layout") };
+    let mut boxes = 0;
+    for s in splits {
+        target.set_split(&s).unwrap();
+        for entry in target.images.clone() {
+            for b in target.load_annotations(&entry.path).unwrap().boxes {
+                assert_eq!(b.keypoints.len(), 2);
+                assert!((b.keypoints[0].x - 0.4).abs() < 1e-4 && b.keypoints[0].v == 2);
+                assert!((b.keypoints[1].y - 0.6).abs() < 1e-4 && b.keypoints[1].v == 1);
+                boxes += 1;
+            }
+        }
+    }
+    assert_eq!(boxes, 3);
+    let _ = fs::remove_dir_all(&dir);
+}
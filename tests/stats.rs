@@ -0,0 +1,101 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::Dataset;
+use img_annotator::stats::{self, Bin, Stats};
+use std::fs;
+use std::path::PathBuf;
+
+fn counts(bins: &[Bin]) -> Vec<(&str, usize)> {
+    bins.iter().map(|b| (b.label.as_str(), b.count)).collect()
+}
+
+// a: 100x100 with a 20x20 cat and a 30x10 dog; b: 200x100 with a 10x10 cat; c: unlabeled
+fn sample(name: &str) -> (PathBuf, Stats) {
+    let dir = temp_dir(name);
+    image::RgbImage::new(100, 100).save(dir.join("a.png")).unwrap();
+    image::RgbImage::new(200, 100).save(dir.join("b.png")).unwrap();
+    image::RgbImage::new(50, 50).save(dir.join("c.png")).unwrap();
+    fs::write(dir.join("_darknet.labels"), "cat\ndog\nbird\n").unwrap();
+    fs::write(dir.join("a.txt"), "0 0.5 0.5 0.2 0.2\n1 0.5 0.5 0.3 0.1\n").unwrap();
+    fs::write(dir.join("b.txt"), "0 0.5 0.5 0.05 0.1\n").unwrap();
+    let s = stats::compute(&Dataset::open(&dir).unwrap()).unwrap();
+    (dir, s)
+}
+
+#[test]
+fn counts_images_boxes_and_classes() {
+    let (dir, s) = sample("stats-counts");
+    assert_eq!((s.images, s.unlabeled_images, s.boxes), (3, 1, 3));
+    let classes: Vec<(&str, Option<usize>, usize, usize)> = s.classes.iter().map(|c| (c.name.as_str(), c.id, c.boxes, c.images)).collect();
+    assert_eq!(classes, [("cat", Some(0), 2, 2), ("dog", Some(1), 1, 1), ("bird", Some(2), 0, 0)]);
+    // one image each with 0, 1 and 2 boxes
+    assert_eq!(s.boxes_per_image, [1, 1, 1]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn bins_box_sizes_and_aspect_ratios_in_pixels() {
+    let (dir, s) = sample("stats-bins");
+    // sqrt of the area: 20, 17.3 and 10 px
+    assert_eq!(counts(&s.box_size_px.bins), [
+        ("< 8", 0), ("8 - 16", 1), ("16 - 32", 2), ("32 - 64", 0), ("64 - 128", 0),
+        ("128 - 256", 0), ("256 - 512", 0), ("512 - 1024", 0), (">= 1024", 0),
+    ]);
+    assert_eq!(s.box_size_px.count, 3);
+    assert!((s.box_size_px.min - 10.0).abs() < 1e-3 && (s.box_size_px.max - 20.0).abs() < 1e-3);
+    assert!((s.box_size_px.median - 300f64.sqrt()).abs() < 1e-3);
+    assert!((s.box_size_px.mean - (30.0 + 300f64.sqrt()) / 3.0).abs() < 1e-3);
+
+    // 1, 3 and 1
+    assert_eq!(counts(&s.aspect_ratio.bins), [
+        ("< 0.25", 0), ("0.25 - 0.5", 0), ("0.5 - 1", 0), ("1 - 2", 2), ("2 - 4", 1), (">= 4", 0),
+    ]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn an_unlabeled_dataset_has_empty_bins() {
+    let dir = temp_dir("stats-empty");
+    image::RgbImage::new(10, 10).save(dir.join("a.png")).unwrap();
+    let s = stats::compute(&Dataset::open(&dir).unwrap()).unwrap();
+    assert_eq!((s.images, s.unlabeled_images, s.boxes), (1, 1, 0));
+    assert_eq!(s.box_size_px.count, 0);
+    assert_eq!(s.box_size_px.bins.len(), stats::SIZE_BIN_EDGES.len() + 1);
+    assert!(s.aspect_ratio.bins.iter().all(|b| b.count == 0));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn writes_csv_rows() {
+    let (dir, s) = sample("stats-csv");
+    let mut out = vec![];
+    stats::write_csv(&s, &mut out).unwrap();
+    let csv = String::from_utf8(out).unwrap();
+    for row in ["metric,key,value", "images,,3", "class_boxes,cat,2", "boxes_per_image,2,1", "box_size_px,16 - 32,2", "aspect_ratio,2 - 4,1"] {
+        assert!(csv.lines().any(|l| l == row), "missing {:?} in\n{}", row, csv);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn covers_every_split() {
+    let dir = temp_dir("stats-splits");
+    for (split, label) in [("train", "0 0.5 0.5 0.2 0.2\n0 0.2 0.2 0.1 0.1\n"), ("val", "1 0.5 0.5 0.4 0.1\n")] {
+        for sub in ["images", "labels"] {
+            fs::create_dir_all(dir.join(sub).join(split)).unwrap();
+        }
+        image::RgbImage::new(100, 100).save(dir.join("images").join(split).join("a.png")).unwrap();
+        fs::write(dir.join("labels").join(split).join("a.txt"), label).unwrap();
+    }
+    fs::write(dir.join("data.yaml"), "names: [cat, dog]\n").unwrap();
+    let ds = Dataset::open(&dir.join("images/val")).unwrap();
+    assert_eq!(ds.images.len(), 1);
+
+    let s = stats::compute(&ds).unwrap();
+    assert_eq!((s.images, s.boxes), (2, 3));
+    assert_eq!(s.classes.iter().map(|c| (c.boxes, c.images)).collect::<Vec<_>>(), [(2, 1), (1, 1)]);
+    assert_eq!(s.boxes_per_image, [0, 1, 1]);
+    assert_eq!(s.aspect_ratio.count, 3);
+    let _ = fs::remove_dir_all(&dir);
+}
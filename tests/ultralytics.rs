@@ -0,0 +1,75 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::classes::IdMapping;
+use img_annotator::ultralytics::{self, find_root, label_path_for_image};
+use img_annotator::{Dataset, Layout};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn label_path_swaps_the_last_images_component() {
+    assert_eq!(label_path_for_image(Path::new("/data/set/images/train/a.jpg")), Path::new("/data/set/labels/train/a.jpg"));
+    assert_eq!(label_path_for_image(Path::new("images/a.png")), Path::new("labels/a.png"));
+    // only the one next to the dataset, not an `images` folder higher up
+    assert_eq!(label_path_for_image(Path::new("/images/set/images/val/b.png")), Path::new("/images/set/labels/val/b.png"));
+    // a name that merely contains "images" isn't the folder
+    assert_eq!(label_path_for_image(Path::new("/data/my_images/c.png")), Path::new("/data/my_images/c.png"));
+}
+
+#[test]
+fn finds_the_root_from_itself_or_a_split() {
+    let dir = temp_dir("ultralytics-root");
+    fs::create_dir_all(dir.join("images/train/nested")).unwrap();
+    fs::create_dir_all(dir.join("images/val")).unwrap();
+    fs::create_dir_all(dir.join("labels/train")).unwrap();
+
+    assert_eq!(find_root(&dir), Some((dir.clone(), None)));
+    assert_eq!(find_root(&dir.join("images")), Some((dir.clone(), None)));
+    assert_eq!(find_root(&dir.join("images/val")), Some((dir.clone(), Some("val".into()))));
+    assert_eq!(find_root(&dir.join("images/train/nested")), Some((dir.clone(), Some("train".into()))));
+    assert_eq!(ultralytics::splits(&dir), ["train", "val"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn data_yaml_without_images_is_a_plain_folder() {
+    let dir = temp_dir("ultralytics-yaml");
+    fs::write(dir.join("data.yaml"), "names: [a]\n").unwrap();
+    image::RgbImage::new(10, 10).save(dir.join("a.png")).unwrap();
+    assert_eq!(find_root(&dir), None);
+    let ds = Dataset::open(&dir).unwrap();
+    assert_eq!(ds.layout, Layout::Flat);
+    assert_eq!(ds.images.len(), 1);
+
+    // with images/, data.yaml is enough
+    fs::create_dir_all(dir.join("images/test")).unwrap();
+    assert_eq!(find_root(&dir), Some((dir.clone(), None)));
+    assert_eq!(find_root(&dir.join("images/test")), Some((dir.clone(), Some("test".into()))));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn an_images_subfolder_alone_is_a_plain_folder() {
+    let dir = temp_dir("ultralytics-plain");
+    fs::create_dir_all(dir.join("images/2024")).unwrap();
+    assert_eq!(find_root(&dir), None);
+    assert_eq!(find_root(&dir.join("images/2024")), None);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn names_with_gaps_become_a_table_mapping() {
+    let dir = temp_dir("ultralytics-names");
+    let yaml = dir.join("data.yaml");
+    fs::write(&yaml, "names: [cat, dog]\n").unwrap();
+    let classes = ultralytics::read_classes(&yaml).unwrap();
+    assert_eq!(classes.names(), ["cat", "dog"]);
+    assert!(classes.mapping().is_identity());
+
+    fs::write(&yaml, "names:\n  0: cat\n  5: dog\n").unwrap();
+    let classes = ultralytics::read_classes(&yaml).unwrap();
+    assert_eq!(classes.file_id("dog"), Some(5));
+    assert!(matches!(classes.mapping(), IdMapping::Table(_)));
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::Dataset;
+use img_annotator::validate::{Issue, Severity, validate};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn dataset(name: &str, labels: &str) -> (PathBuf, Vec<Issue>) {
+    let dir = temp_dir(name);
+    image::RgbImage::new(100, 100).save(dir.join("a.png")).unwrap();
+    fs::write(dir.join("_darknet.labels"), "cat\ndog\n").unwrap();
+    fs::write(dir.join("a.txt"), labels).unwrap();
+    let issues = validate(&Dataset::open(&dir).unwrap()).unwrap();
+    (dir, issues)
+}
+
+// (line, severity, message) of each issue
+fn summary(issues: &[Issue]) -> Vec<(Option<usize>, Severity, String)> {
+    issues.iter().map(|i| (i.line, i.severity, i.message.clone())).collect()
+}
+
+#[test]
+fn a_clean_file_has_no_issues() {
+    let (dir, issues) = dataset("validate-clean", "0 0.5 0.5 0.2 0.2\n\n1 0.2 0.2 0.1 0.1\n");
+    assert!(issues.is_empty(), "{:?}", issues);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reports_malformed_lines() {
+    let (dir, issues) = dataset("validate-malformed", "0 0.5 0.5\n0 0.5 x 0.2 0.2\n0 0.5 0.5 0.2 0.2 0.9\n");
+    use Severity::*;
+    assert_eq!(summary(&issues), [
+        (Some(1), Error, "expected 5 fields, found 3".to_owned()),
+        (Some(2), Error, "cy is not a number: 'x'".to_owned()),
+        (Some(3), Warning, "1 extra fields ignored".to_owned()),
+    ]);
+    // malformed lines don't become boxes
+    assert_eq!(issues[0].box_index, None);
+    assert_eq!(issues[2].box_index, Some(0));
+    assert_eq!(issues[0].image.as_deref(), Some(dir.join("a.png").as_path()));
+    assert_eq!(issues[0].to_string(), format!("{}:1: error: expected 5 fields, found 3", dir.join("a.txt").display()));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reports_unknown_classes_and_names() {
+    let (dir, issues) = dataset("validate-classes", "7 0.5 0.5 0.2 0.2\ndog 0.2 0.2 0.1 0.1\nbird 0.8 0.8 0.1 0.1\n");
+    use Severity::*;
+    assert_eq!(summary(&issues), [
+        (Some(1), Error, "unknown class id 7".to_owned()),
+        (Some(2), Warning, "class given by name 'dog' instead of id".to_owned()),
+        (Some(3), Warning, "unknown class name 'bird'".to_owned()),
+    ]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reports_bad_geometry_and_duplicates() {
+    let labels = "0 0.5 0.5 0 0.2\n0 1.2 0.5 0.2 0.2\n0 0.95 0.5 0.2 0.2\n1 0.3 0.3 0.1 0.1\n1 0.3 0.3 0.1 0.1\n0 0.1 0.1 1.1 0.1 0.5 0.5\n";
+    let (dir, issues) = dataset("validate-geometry", labels);
+    use Severity::*;
+    assert_eq!(summary(&issues), [
+        (Some(1), Error, "zero-area box (0 x 0.2)".to_owned()),
+        (Some(2), Error, "cx outside 0..1".to_owned()),
+        (Some(3), Warning, "box extends past the image by up to 5.0 px".to_owned()),
+        (Some(5), Warning, "duplicate of the box on line 4".to_owned()),
+        (Some(6), Error, "vertex 2 outside 0..1".to_owned()),
+    ]);
+    assert_eq!(issues[3].box_index, Some(4));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reports_label_files_without_an_image() {
+    let (dir, _) = dataset("validate-orphan", "0 0.5 0.5 0.2 0.2\n");
+    fs::write(dir.join("gone.txt"), "0 0.5 0.5 0.2 0.2\n").unwrap();
+    fs::write(dir.join("classes.txt"), "cat\n").unwrap();
+    let issues = validate(&Dataset::open(&dir).unwrap()).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].file, dir.join("gone.txt"));
+    assert_eq!((issues[0].severity, issues[0].image.as_deref()), (Severity::Warning, None::<&Path>));
+    assert_eq!(issues[0].message, "label file without an image");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reports_voc_problems() {
+    let dir = temp_dir("validate-voc");
+    image::RgbImage::new(100, 100).save(dir.join("a.png")).unwrap();
+    image::RgbImage::new(100, 100).save(dir.join("b.png")).unwrap();
+    fs::write(dir.join("_darknet.labels"), "cat\n").unwrap();
+    fs::write(dir.join("a.xml"), "<annotation><size><width>50</width><height>100</height></size>\
+        <object><name>bird</name><bndbox><xmin>10</xmin><ymin>10</ymin><xmax>20</xmax><ymax>20</ymax></bndbox></object>\
+        </annotation>").unwrap();
+    fs::write(dir.join("b.xml"), "<annotation><object><bndbox></bndbox></object></annotation>").unwrap();
+
+    let issues = validate(&Dataset::open(&dir).unwrap()).unwrap();
+    use Severity::*;
+    assert_eq!(summary(&issues), [
+        (None, Warning, "<size> is 50x100 but the image is 100x100".to_owned()),
+        (None, Warning, "class 'bird' is not in the class list".to_owned()),
+        (None, Error, "object at (0, 0) has no <name>".to_owned()),
+    ]);
+    assert_eq!(issues[2].file, dir.join("b.xml"));
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::voc::{self, VocAnnotation};
+use img_annotator::{BBox, Dataset, LabelFormat};
+use std::fs;
+
+// VOC person layout: the parts carry their own name and box
+const PERSON_LAYOUT: &str = r#"<annotation>
+    <filename>2007_000129.jpg</filename>
+    <size><width>334</width><height>500</height><depth>3</depth></size>
+    <object>
+        <name>person</name>
+        <difficult>1</difficult>
+        <bndbox><xmin>70</xmin><ymin>202</ymin><xmax>255</xmax><ymax>500</ymax></bndbox>
+        <part>
+            <name>head</name>
+            <bndbox><xmin>139</xmin><ymin>200</ymin><xmax>207</xmax><ymax>255</ymax></bndbox>
+        </part>
+        <part>
+            <name>hand</name>
+            <bndbox><xmin>76</xmin><ymin>329</ymin><xmax>106</xmax><ymax>361</ymax></bndbox>
+        </part>
+    </object>
+    <object>
+        <name>bicycle</name>
+        <truncated>1</truncated>
+        <bndbox><xmin>1</xmin><ymin>2</ymin><xmax>3</xmax><ymax>4</ymax></bndbox>
+    </object>
+</annotation>"#;
+
+#[test]
+fn parts_do_not_overwrite_their_object() {
+    let ann = voc::parse(PERSON_LAYOUT).unwrap();
+    assert_eq!((ann.filename.as_str(), ann.size()), ("2007_000129.jpg", Some((334, 500))));
+    assert_eq!(ann.objects.len(), 2);
+    let person = &ann.objects[0];
+    assert_eq!(person.name, "person");
+    assert!(person.difficult && !person.truncated);
+    assert_eq!((person.xmin, person.ymin, person.xmax, person.ymax), (70.0, 202.0, 255.0, 500.0));
+    let bike = &ann.objects[1];
+    assert_eq!(bike.name, "bicycle");
+    assert!(bike.truncated);
+    assert_eq!((bike.xmin, bike.ymin, bike.xmax, bike.ymax), (1.0, 2.0, 3.0, 4.0));
+}
+
+#[test]
+fn rejects_objects_without_a_name_and_bad_numbers() {
+    assert!(voc::parse("<annotation><object><bndbox><xmin>1</xmin></bndbox></object></annotation>").is_err());
+    assert!(voc::parse("<annotation><object><name>a</name><bndbox><xmin>x</xmin></bndbox></object></annotation>").is_err());
+}
+
+#[test]
+fn xml_round_trips() {
+    let mut cat = BBox::new("cat & dog", 0.25, 0.5, 0.2, 0.4);
+    cat.difficult = true;
+    let boxes = vec![cat, BBox::new("bird", 0.75, 0.25, 0.1, 0.1)];
+    let ann = VocAnnotation::from_boxes("a.png", (200, 100), &boxes);
+    let read = voc::parse(&voc::to_xml(&ann)).unwrap();
+    assert_eq!(read, ann);
+    assert_eq!(read.to_boxes((200, 100)), boxes);
+}
+
+#[test]
+fn export_then_import_keeps_the_boxes() {
+    let dir = temp_dir("voc-round-trip");
+    let img = dir.join("a.png");
+    image::RgbImage::new(200, 100).save(&img).unwrap();
+    fs::write(dir.join("_darknet.labels"), "cat\nbird\n").unwrap();
+    fs::write(dir.join("a.txt"), "0 0.250000 0.500000 0.200000 0.400000\n1 0.750000 0.250000 0.100000 0.100000\n").unwrap();
+
+    let mut ds = Dataset::open(&dir).unwrap();
+    assert_eq!(ds.convert_all(LabelFormat::Yolo, LabelFormat::Voc).unwrap(), 1);
+    fs::remove_file(dir.join("a.txt")).unwrap();
+    assert!(fs::read_to_string(dir.join("a.xml")).unwrap().contains("<xmin>30</xmin>"));
+
+    let mut ds = Dataset::open(&dir).unwrap();
+    assert_eq!(ds.format, LabelFormat::Voc);
+    let ann = ds.load_annotations(&img).unwrap();
+    assert_eq!(ann.boxes, [BBox::new("cat", 0.25, 0.5, 0.2, 0.4), BBox::new("bird", 0.75, 0.25, 0.1, 0.1)]);
+
+    assert_eq!(ds.convert_all(LabelFormat::Voc, LabelFormat::Yolo).unwrap(), 1);
+    assert_eq!(
+        fs::read_to_string(dir.join("a.txt")).unwrap(),
+        "0 0.250000 0.500000 0.200000 0.400000\n1 0.750000 0.250000 0.100000 0.100000\n"
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::temp_dir;
+use img_annotator::watch::{Changes, Watcher};
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// collects changes until `done` holds or a few seconds pass
+fn wait_for(watcher: &Watcher, done: impl Fn(&Changes) -> bool) -> Changes {
+    let start = Instant::now();
+    let mut all = Changes::default();
+    while !done(&all) && start.elapsed() < Duration::from_secs(5) {
+        thread::sleep(Duration::from_millis(10));
+        let c = watcher.poll();
+        all.added.extend(c.added);
+        all.removed.extend(c.removed);
+        all.modified.extend(c.modified);
+    }
+    all
+}
+
+#[test]
+fn reports_added_modified_and_removed_files() {
+    let dir = temp_dir("watch-changes");
+    let (a, b) = (dir.join("a.txt"), dir.join("b.png"));
+    fs::write(&a, "0 0.5 0.5 0.2 0.2\n").unwrap();
+    let watcher = Watcher::new(vec![dir.clone()], Duration::from_millis(20), || {});
+    assert_eq!(watcher.dirs(), std::slice::from_ref(&dir));
+
+    fs::write(&b, "x").unwrap();
+    let c = wait_for(&watcher, |c| c.added.contains(&b));
+    assert!(c.added.contains(&b));
+    assert!(!c.modified.contains(&a));
+
+    // a different size, so coarse modification times don't hide it
+    fs::write(&a, "0 0.5 0.5 0.2 0.2\n1 0.1 0.1 0.1 0.1\n").unwrap();
+    let c = wait_for(&watcher, |c| c.modified.contains(&a));
+    assert!(c.modified.contains(&a));
+
+    fs::remove_file(&b).unwrap();
+    let c = wait_for(&watcher, |c| c.removed.contains(&b));
+    assert!(c.removed.contains(&b));
+    assert_eq!(c.paths().count(), 1);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn hidden_and_acknowledged_files_are_not_reported() {
+    let dir = temp_dir("watch-quiet");
+    let a = dir.join("a.txt");
+    fs::write(&a, "old").unwrap();
+    let watcher = Watcher::new(vec![dir.clone()], Duration::from_millis(200), || {});
+
+    // our own write, as the annotator saves
+    fs::write(&a, "written by us").unwrap();
+    watcher.acknowledge(&a);
+    fs::write(dir.join(".a.txt.123.tmp"), "temporary").unwrap();
+    let marker = dir.join("marker.txt");
+    fs::write(&marker, "x").unwrap();
+
+    // once the marker shows up the other files have been scanned as well
+    let c = wait_for(&watcher, |c| c.added.contains(&marker));
+    assert!(c.added.contains(&marker));
+    assert_eq!(c.paths().count(), 1);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn notifies_on_change() {
+    let dir = temp_dir("watch-notify");
+    let (tx, rx) = std::sync::mpsc::channel();
+    let _watcher = Watcher::new(vec![dir.clone()], Duration::from_millis(20), move || { let _ = tx.send(()); });
+    fs::write(dir.join("new.txt"), "x").unwrap();
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,105 @@
+use img_annotator::yolo::{self, ClassToken, KptShape, LineError};
+use img_annotator::{BBox, ClassRegistry, Keypoint};
+
+fn registry(names: &[&str]) -> ClassRegistry {
+    ClassRegistry::new(names.iter().map(|s| s.to_string()).collect())
+}
+
+#[test]
+fn parses_a_detection_line() {
+    let rec = yolo::parse_line("1 0.402686 0.633823 0.076793 0.046866", None).unwrap();
+    assert_eq!(rec.class, ClassToken::Id(1));
+    assert_eq!((rec.cx, rec.cy, rec.w, rec.h), (0.402686, 0.633823, 0.076793, 0.046866));
+}
+
+#[test]
+fn legacy_class_names_get_spaces_back() {
+    let rec = yolo::parse_line("blue_ring 0.5 0.5 0.1 0.1", None).unwrap();
+    assert_eq!(rec.class, ClassToken::Name("blue ring".into()));
+}
+
+#[test]
+fn rejects_short_lines_and_bad_numbers() {
+    assert_eq!(yolo::parse_line("", None), None);
+    assert_eq!(yolo::parse_line("0 0.5 0.5 0.1", None), None);
+    assert_eq!(yolo::parse_line("0 0.5 abc 0.1 0.1", None), None);
+    assert_eq!(yolo::parse_line("0 0.5 0.5 0.1 tall", None), None);
+}
+
+#[test]
+fn out_of_range_values_are_read_unchanged() {
+    // the reader keeps what the file says; clamping is left to the editor
+    let rec = yolo::parse_line("0 1.25 -0.1 2 0", None).unwrap();
+    assert_eq!((rec.cx, rec.cy, rec.w, rec.h), (1.25, -0.1, 2.0, 0.0));
+}
+
+#[test]
+fn formats_six_decimals() {
+    let b = BBox::new("cat", 0.5, 0.25, 0.1, 1.0 / 3.0);
+    assert_eq!(yolo::format_line(3, &b, None), "3 0.500000 0.250000 0.100000 0.333333");
+}
+
+#[test]
+fn write_then_read_round_trips() {
+    let boxes = vec![
+        BBox::new("cat", 0.25, 0.25, 0.2, 0.2),
+        BBox::new("dog", 0.5, 0.75, 0.3, 0.1),
+        BBox::new("bird", 0.125, 0.875, 0.05, 0.05),
+    ];
+    let mut classes = registry(&["cat", "dog"]);
+    let mut buf = vec![];
+    assert!(yolo::write_boxes(&mut buf, &boxes, &mut classes, None).unwrap());
+    assert_eq!(classes.names(), ["cat", "dog", "bird"]);
+    assert_eq!(String::from_utf8(buf.clone()).unwrap().lines().next(), Some("0 0.250000 0.250000 0.200000 0.200000"));
+
+    let mut reread = classes.clone();
+    let (read, changed) = yolo::read_boxes(&buf[..], &mut reread, None, false).unwrap();
+    assert!(!changed);
+    assert_eq!(read, boxes);
+}
+
+#[test]
+fn unknown_ids_get_placeholder_classes() {
+    let mut classes = registry(&["cat"]);
+    let (read, changed) = yolo::read_boxes("2 0.5 0.5 0.1 0.1\nnot a line\n".as_bytes(), &mut classes, None, false).unwrap();
+    assert!(changed);
+    assert_eq!(read.len(), 1);
+    assert_eq!(read[0].class_name, "class_2");
+    assert_eq!(classes.names(), ["cat", "class_1", "class_2"]);
+}
+
+#[test]
+fn four_vertex_lines_are_rotated_only_in_obb_datasets() {
+    // a parallelogram, as a sheared quadrilateral polygon would be
+    let line = "0 0.1 0.1 0.5 0.1 0.6 0.4 0.2 0.4\n";
+    let mut classes = registry(&["cat"]);
+    let (read, _) = yolo::read_boxes(line.as_bytes(), &mut classes, None, false).unwrap();
+    assert!(read[0].is_polygon() && !read[0].rotated);
+    let (read, _) = yolo::read_boxes(line.as_bytes(), &mut classes, None, true).unwrap();
+    assert!(read[0].rotated);
+
+    // other polygons stay polygons either way
+    let (read, _) = yolo::read_boxes("0 0.1 0.1 0.5 0.1 0.3 0.4\n".as_bytes(), &mut classes, None, true).unwrap();
+    assert!(!read[0].rotated);
+
+    let mut buf = vec![];
+    yolo::write_boxes(&mut buf, &read, &mut classes, None).unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), "0 0.100000 0.100000 0.500000 0.100000 0.300000 0.400000\n");
+}
+
+#[test]
+fn pose_lines_must_match_the_skeleton() {
+    let kpt = Some(KptShape { count: 2, dims: 3 });
+    let rec = yolo::parse_line_checked("0 0.5 0.5 0.2 0.2 0.4 0.4 2 0.6 0.6 1", kpt).unwrap();
+    assert_eq!(rec.keypoints, [Keypoint { x: 0.4, y: 0.4, v: 2 }, Keypoint { x: 0.6, y: 0.6, v: 1 }]);
+    assert!(yolo::parse_line_checked("0 0.5 0.5 0.2 0.2", kpt).unwrap().keypoints.is_empty());
+
+    // one keypoint short: odd and even field counts, neither a polygon nor a truncated box
+    for line in ["0 0.5 0.5 0.2 0.2 0.4 0.4 2", "0 0.5 0.5 0.2 0.2 0.4 0.4 2 0.6"] {
+        let found = line.split_whitespace().count();
+        assert_eq!(yolo::parse_line_checked(line, kpt), Err(LineError::PoseFieldCount { expected: 11, found }));
+    }
+    assert_eq!(yolo::parse_line_checked("0 0.1 0.1 0.5 0.1 0.3 0.4", kpt).unwrap_err().to_string(), "expected 5 or 11 fields for the skeleton, found 7");
+    // a skeleton without keypoints yet leaves polygons alone
+    assert_eq!(yolo::parse_line_checked("0 0.1 0.1 0.5 0.1 0.3 0.4", Some(KptShape { count: 0, dims: 3 })).unwrap().points.len(), 3);
+}